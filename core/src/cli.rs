@@ -0,0 +1,247 @@
+use crate::state::machine::{Machine, MachineRunOptions};
+use crate::state::machine_v1::new_v1_machine;
+use crate::state::tuneables::Tunables;
+use crate::surfacev::err::VResult;
+use crate::surfacev::vsurface::{PlanOverlay, SurfaceDiff, VSurface, VSurfacePixelAsVs};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use std::path::{Path, PathBuf};
+
+#[derive(Parser, Debug)]
+#[command(about = "Plan a rail loop mining network from a scanned Factorio surface")]
+pub struct CliArgs {
+    #[command(subcommand)]
+    pub command: CliCommand,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum CliCommand {
+    /// Run the step pipeline, skipping steps with existing output
+    Run {
+        #[command(flatten)]
+        machine: CliMachineArgs,
+        /// Only consider steps in this inclusive range, eg step10-base..step20-nav .
+        /// Either side may be omitted
+        #[arg(long, conflicts_with = "stop_at")]
+        steps: Option<String>,
+        /// Delete output of this step and all later steps so they re-run
+        #[arg(long)]
+        force_from: Option<String>,
+        /// Stop after this step completes
+        #[arg(long)]
+        stop_at: Option<String>,
+        #[command(flatten)]
+        tunables: CliTunablesArgs,
+    },
+    /// List pipeline steps and if they have output
+    Steps {
+        #[command(flatten)]
+        machine: CliMachineArgs,
+    },
+    /// Write the Pixel color lookup image
+    LookupImage,
+    /// Compare two step output directories
+    Diff {
+        before: PathBuf,
+        after: PathBuf,
+        /// Write pixel-diff.png of added and removed rails to this directory
+        #[arg(long)]
+        image_dir: Option<PathBuf>,
+    },
+    /// Write a tile pyramid and HTML viewer for browsing a step output in a browser.
+    ///
+    /// The viewer loads Leaflet from unpkg.com, so the browser needs network access
+    Tiles {
+        step_dir: PathBuf,
+        /// Default is tiles/ in the step directory
+        #[arg(long)]
+        out_dir: Option<PathBuf>,
+    },
+    /// Write plan.svg and plan.geojson of patches, mines and rails
+    Overlay {
+        step_dir: PathBuf,
+        /// Default is the step directory
+        #[arg(long)]
+        out_dir: Option<PathBuf>,
+    },
+    /// Check surface invariants of a step output directory
+    Validate {
+        step_dir: PathBuf,
+        /// Write surface-validation.json to this directory
+        #[arg(long)]
+        report_dir: Option<PathBuf>,
+    },
+}
+
+#[derive(Args, Debug)]
+pub struct CliMachineArgs {
+    /// Directory containing out0/ and state.json
+    #[arg(long, default_value = "work")]
+    pub work_dir: PathBuf,
+    #[arg(long, value_enum, default_value_t = CliPipeline::V1)]
+    pub pipeline: CliPipeline,
+}
+
+#[derive(Args, Debug)]
+pub struct CliTunablesArgs {
+    /// TOML or JSON tunables file. Default is tunables.toml or tunables.json in the work dir
+    #[arg(long = "tunables")]
+    pub file: Option<PathBuf>,
+    /// Override a single tunable, eg mori.turn_cost_unit=3 . Repeat for more.
+    /// Also read from LOOPMINER_TUNE as ; separated overrides, applied before these
+    #[arg(long = "tune")]
+    pub overrides: Vec<String>,
+}
+
+const TUNE_ENV: &str = "LOOPMINER_TUNE";
+
+impl CliTunablesArgs {
+    pub fn load(&self, work_dir: &Path) -> VResult<Tunables> {
+        let overrides = merge_overrides(std::env::var(TUNE_ENV).ok().as_deref(), &self.overrides);
+        Tunables::load(work_dir, self.file.as_deref(), &overrides)
+    }
+}
+
+/// Values may contain commas, eg lists, so only the env var is split
+fn merge_overrides(env: Option<&str>, cli: &[String]) -> Vec<String> {
+    env.into_iter()
+        .flat_map(|v| v.split(';'))
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+        .map(str::to_string)
+        .chain(cli.iter().cloned())
+        .collect()
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum CliPipeline {
+    V1,
+}
+
+impl CliPipeline {
+    pub fn new_machine(&self) -> Machine {
+        match self {
+            CliPipeline::V1 => new_v1_machine(),
+        }
+    }
+}
+
+impl CliCommand {
+    pub fn run_options(
+        steps: Option<String>,
+        force_from: Option<String>,
+        stop_at: Option<String>,
+    ) -> MachineRunOptions {
+        let (start_at, stop_at) = match steps {
+            Some(steps) => match steps.split_once("..") {
+                Some((start, stop)) => (non_empty(start), non_empty(stop)),
+                // single step range
+                None => (Some(steps.clone()), Some(steps)),
+            },
+            None => (None, stop_at),
+        };
+        MachineRunOptions {
+            start_at,
+            stop_at,
+            force_from,
+        }
+    }
+}
+
+pub fn run_diff(before: &Path, after: &Path, image_dir: Option<&Path>) -> VResult<()> {
+    let before = VSurface::load(before)?;
+    let after = VSurface::load(after)?;
+    SurfaceDiff::new(&before, &after).log_report();
+    if let Some(image_dir) = image_dir {
+        after
+            .pixels()
+            .paint_pixel_diff(before.pixels())
+            .save_to_file(image_dir)?;
+    }
+    Ok(())
+}
+
+pub fn run_tiles(step_dir: &Path, out_dir: Option<&Path>) -> VResult<()> {
+    let out_dir = match out_dir {
+        Some(out_dir) => out_dir.to_path_buf(),
+        None => step_dir.join("tiles"),
+    };
+    VSurface::load(step_dir)?.pixels().export_tiles(&out_dir)
+}
+
+pub fn run_overlay(step_dir: &Path, out_dir: Option<&Path>) -> VResult<()> {
+    let surface = VSurface::load(step_dir)?;
+    PlanOverlay::new(&surface).save(out_dir.unwrap_or(step_dir))
+}
+
+pub fn run_validate(step_dir: &Path, report_dir: Option<&Path>) -> VResult<()> {
+    let validation = VSurface::load(step_dir)?.validate();
+    validation.log_report();
+    if let Some(report_dir) = report_dir {
+        validation.save(report_dir)?;
+    }
+    Ok(())
+}
+
+fn non_empty(input: &str) -> Option<String> {
+    if input.is_empty() {
+        None
+    } else {
+        Some(input.to_string())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::cli::{CliArgs, CliCommand, merge_overrides};
+    use clap::Parser;
+
+    #[test]
+    fn test_steps_range() {
+        let options = CliCommand::run_options(
+            Some("step10-base..step20-nav".to_string()),
+            None,
+            Some("ignored".to_string()),
+        );
+        assert_eq!(options.start_at.as_deref(), Some("step10-base"));
+        assert_eq!(options.stop_at.as_deref(), Some("step20-nav"));
+
+        let options = CliCommand::run_options(Some("..step20-nav".to_string()), None, None);
+        assert_eq!(options.start_at, None);
+        assert_eq!(options.stop_at.as_deref(), Some("step20-nav"));
+
+        let options = CliCommand::run_options(Some("step10-base".to_string()), None, None);
+        assert_eq!(options.start_at.as_deref(), Some("step10-base"));
+        assert_eq!(options.stop_at.as_deref(), Some("step10-base"));
+    }
+
+    #[test]
+    fn test_tune_overrides() {
+        let args = CliArgs::try_parse_from([
+            "loopminer",
+            "run",
+            "--tune",
+            "crop.radius=1,2",
+            "--tune",
+            "mori.turn_cost_unit=3",
+        ])
+        .unwrap();
+        let CliCommand::Run { tunables, .. } = args.command else {
+            panic!("not run")
+        };
+        assert_eq!(
+            tunables.overrides,
+            ["crop.radius=1,2", "mori.turn_cost_unit=3"]
+        );
+
+        let merged = merge_overrides(Some("a.b=1,2; c.d=3;"), &tunables.overrides);
+        assert_eq!(
+            merged,
+            [
+                "a.b=1,2",
+                "c.d=3",
+                "crop.radius=1,2",
+                "mori.turn_cost_unit=3"
+            ]
+        );
+    }
+}
@@ -1,152 +1,318 @@
 use crate::surface::pixel::Pixel;
-use crate::surfacev::err::VResult;
-use facto_loop_miner_common::err_bt::PrettyUnwrapMyBacktrace;
-use serde::Deserialize;
-use simd_json::{BorrowedValue, OwnedValue, StaticNode, to_borrowed_value, to_owned_value};
-use std::io;
-use std::io::ErrorKind;
+use crate::surfacev::err::{CoreConvertPathResult, VError, VResult};
+use facto_loop_miner_common::err_utils::xbt;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{ErrorKind, Read};
+use std::path::{Path, PathBuf};
+
+const READ_BUFFER_SIZE: usize = 1024 * 1024;
+
+pub struct ExportParsed<V> {
+    pub items: Vec<V>,
+    /// Skipped entries by name
+    pub unknown_names: HashMap<String, usize>,
+}
 
 /// Parse compressed JSON from loop-miner-exporter mod in format `[name1, x1, y1, name2, ....]`
+/// or the extended format with resource amount `[name1, x1, y1, amount1, name2, ....]`
 ///
-/// On 45 million entities, simd_json in raw borrowed mode parses in 9 seconds.
-/// Serde simd_json with conversion takes 30 seconds.
-pub fn parse_exported_lua_data<V, C>(input: &mut [u8], to_object: C) -> VResult<Vec<V>>
+/// Streams the file instead of building a 45 million entry JSON tree first.
+pub fn parse_exported_lua_file<V, C>(path: &Path, to_object: C) -> VResult<ExportParsed<V>>
 where
-    C: Fn(Pixel, f32, f32) -> V,
+    C: FnMut(Pixel, f32, f32, Option<u32>) -> V,
 {
-    match 2 {
-        // 0 => parse_exported_lua_data_simd_borrowed(input, to_object),
-        2 => parse_exported_lua_data_simd_owned(input, to_object),
-        _ => panic!("unknown"),
-    }
+    let file = File::open(path).convert(path)?;
+    parse_exported_lua_reader(file, path, to_object)
 }
 
-fn parse_exported_lua_data_simd_borrowed<V, C>(input: &mut [u8], to_object: C) -> VResult<Vec<V>>
+pub fn parse_exported_lua_reader<R, V, C>(
+    reader: R,
+    path: &Path,
+    mut to_object: C,
+) -> VResult<ExportParsed<V>>
 where
-    C: Fn(Pixel, f32, f32) -> V,
+    R: Read,
+    C: FnMut(Pixel, f32, f32, Option<u32>) -> V,
 {
-    let main_array = if let Ok(BorrowedValue::Array(raw)) = to_borrowed_value(input) {
-        raw
-    } else {
-        panic!("no wrapper array?")
-    };
-
-    let mut result = Vec::new();
-    for [name_value, x_value, y_value] in main_array.into_iter().array_chunks() {
-        let name = if let BorrowedValue::String(raw) = name_value {
-            raw
-        } else {
-            panic!("not a name");
-        };
-        let x = if let BorrowedValue::Static(StaticNode::F64(raw)) = x_value {
-            raw as f32
-        } else {
-            panic!("not x");
-        };
-        let y = if let BorrowedValue::Static(StaticNode::F64(raw)) = y_value {
-            raw as f32
-        } else {
-            panic!("not y");
-        };
-        result.push(to_object(Pixel::from_string(&name).pretty_unwrap(), x, y));
+    let mut tokenizer = ExportTokenizer::new(reader, path);
+    let mut items = Vec::new();
+    let mut unknown_names: HashMap<String, usize> = HashMap::new();
+    let mut known_names: Vec<(String, Option<Pixel>)> = Vec::new();
+
+    tokenizer.skip_whitespace()?;
+    tokenizer.expect(b'[')?;
+    tokenizer.skip_whitespace()?;
+    if tokenizer.peek()? == Some(b']') {
+        tokenizer.bump();
+        tokenizer.expect_end()?;
+        return Ok(ExportParsed {
+            items,
+            unknown_names,
+        });
     }
-    Ok(result)
-}
 
-fn parse_exported_lua_data_simd_owned<V, C>(input: &mut [u8], to_object: C) -> VResult<Vec<V>>
-where
-    C: Fn(Pixel, f32, f32) -> V,
-{
-    let mut result = Vec::new();
-
-    let main_array = if let Ok(OwnedValue::Array(raw)) = to_owned_value(input) {
-        raw
-    } else {
-        panic!("no wrapper array?")
-    };
-
-    for [name_value, x_value, y_value] in main_array.into_iter().array_chunks() {
-        let name = if let OwnedValue::String(raw) = name_value {
-            raw
-        } else {
-            panic!("not a name");
-        };
-        let x = if let OwnedValue::Static(StaticNode::F64(raw)) = x_value {
-            raw as f32
-        } else {
-            panic!("not x");
-        };
-        let y = if let OwnedValue::Static(StaticNode::F64(raw)) = y_value {
-            raw as f32
-        } else {
-            panic!("not y");
+    loop {
+        let name = tokenizer.read_string()?;
+        // Few distinct names. Also Pixel::from_string captures a backtrace on unknown names
+        let pixel = match known_names.iter().find(|(known, _)| known == name) {
+            Some((_, pixel)) => *pixel,
+            None => {
+                let pixel = Pixel::from_string(name).ok();
+                known_names.push((name.to_string(), pixel));
+                pixel
+            }
         };
-        result.push(to_object(Pixel::from_string(&name).pretty_unwrap(), x, y));
+        let unknown_name = pixel.is_none().then(|| name.to_string());
+
+        tokenizer.expect_separator()?;
+        let x = tokenizer.read_number()? as f32;
+        tokenizer.expect_separator()?;
+        let y = tokenizer.read_number()? as f32;
+
+        // after y is either the end, the next name, or the extended format amount
+        let mut amount = None;
+        let mut is_end = !tokenizer.next_separator_or_end()?;
+        if !is_end && tokenizer.peek()? != Some(b'"') {
+            let raw_amount = tokenizer.read_number()?;
+            if raw_amount < 0.0 {
+                return Err(tokenizer.error("negative amount"));
+            }
+            amount = Some(raw_amount as u32);
+            is_end = !tokenizer.next_separator_or_end()?;
+        }
+
+        match (pixel, unknown_name) {
+            (Some(pixel), _) => items.push(to_object(pixel, x, y, amount)),
+            (None, Some(name)) => *unknown_names.entry(name).or_default() += 1,
+            (None, None) => unreachable!(),
+        }
+
+        if is_end {
+            tokenizer.expect_end()?;
+            break;
+        }
     }
 
-    Ok(result)
+    Ok(ExportParsed {
+        items,
+        unknown_names,
+    })
 }
 
-#[derive(Deserialize, Debug)]
-#[serde(untagged)]
-enum ExportCompressedItem {
-    Name(String),
-    Position(f32),
+/// Minimal JSON tokenizer for only a flat array of strings and numbers
+struct ExportTokenizer<R> {
+    reader: R,
+    path: PathBuf,
+    buffer: Vec<u8>,
+    buffer_pos: usize,
+    buffer_len: usize,
+    /// file offset of buffer[0]
+    buffer_offset: u64,
+    scratch: Vec<u8>,
 }
 
-impl ExportCompressedItem {
-    fn into_pixel(self) -> Pixel {
-        match self {
-            ExportCompressedItem::Name(v) => Pixel::from_string(&v)
-                .map_err(|_| io::Error::new(ErrorKind::NotFound, v))
-                .unwrap(),
-            _ => {
-                panic!("unexpected value")
+impl<R: Read> ExportTokenizer<R> {
+    fn new(reader: R, path: &Path) -> Self {
+        ExportTokenizer {
+            reader,
+            path: path.to_path_buf(),
+            buffer: vec![0; READ_BUFFER_SIZE],
+            buffer_pos: 0,
+            buffer_len: 0,
+            buffer_offset: 0,
+            scratch: Vec::new(),
+        }
+    }
+
+    fn offset(&self) -> u64 {
+        self.buffer_offset + self.buffer_pos as u64
+    }
+
+    fn error(&self, message: &str) -> VError {
+        self.error_at(self.offset(), message)
+    }
+
+    fn error_at(&self, offset: u64, message: &str) -> VError {
+        VError::ExportParse {
+            path: self.path.to_string_lossy().to_string(),
+            offset,
+            message: message.to_string(),
+            backtrace: xbt(),
+        }
+    }
+
+    fn peek(&mut self) -> VResult<Option<u8>> {
+        if self.buffer_pos == self.buffer_len {
+            self.buffer_offset += self.buffer_len as u64;
+            self.buffer_pos = 0;
+            self.buffer_len = loop {
+                match self.reader.read(&mut self.buffer) {
+                    Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                    result => break result.convert(&self.path)?,
+                }
+            };
+            if self.buffer_len == 0 {
+                return Ok(None);
             }
         }
+        Ok(Some(self.buffer[self.buffer_pos]))
+    }
+
+    fn bump(&mut self) {
+        self.buffer_pos += 1;
+    }
+
+    fn skip_whitespace(&mut self) -> VResult<()> {
+        while let Some(b' ' | b'\n' | b'\r' | b'\t') = self.peek()? {
+            self.bump();
+        }
+        Ok(())
     }
 
-    fn as_string(&self) -> &str {
-        match self {
-            ExportCompressedItem::Name(v) => v,
-            _ => {
-                panic!("unexpected value")
+    fn expect(&mut self, expected: u8) -> VResult<()> {
+        match self.peek()? {
+            Some(actual) if actual == expected => {
+                self.bump();
+                Ok(())
             }
+            Some(actual) => Err(self.error(&format!(
+                "expected {} found {}",
+                expected as char, actual as char
+            ))),
+            None => Err(self.error(&format!("expected {} found EOF", expected as char))),
         }
     }
 
-    fn into_f32(self) -> f32 {
-        match self {
-            ExportCompressedItem::Position(v) => v,
-            _ => {
-                panic!("unexpected value")
+    fn expect_separator(&mut self) -> VResult<()> {
+        self.skip_whitespace()?;
+        self.expect(b',')?;
+        self.skip_whitespace()
+    }
+
+    /// true after a separator, false at the closing bracket
+    fn next_separator_or_end(&mut self) -> VResult<bool> {
+        self.skip_whitespace()?;
+        match self.peek()? {
+            Some(b',') => {
+                self.bump();
+                self.skip_whitespace()?;
+                Ok(true)
+            }
+            Some(b']') => {
+                self.bump();
+                Ok(false)
             }
+            Some(actual) => Err(self.error(&format!("expected , or ] found {}", actual as char))),
+            None => Err(self.error("expected , or ] found EOF")),
         }
     }
-}
 
-#[derive(Deserialize, Debug)]
-#[serde(transparent)]
-struct ExportCompressedVec {
-    inner: Vec<ExportCompressedItem>,
-}
+    fn expect_end(&mut self) -> VResult<()> {
+        self.skip_whitespace()?;
+        match self.peek()? {
+            None => Ok(()),
+            Some(actual) => Err(self.error(&format!("expected EOF found {}", actual as char))),
+        }
+    }
+
+    fn read_string(&mut self) -> VResult<&str> {
+        self.expect(b'"')?;
+        self.scratch.clear();
+        loop {
+            match self.peek()? {
+                Some(b'"') => {
+                    self.bump();
+                    break;
+                }
+                Some(b'\\') => {
+                    self.bump();
+                    match self.peek()? {
+                        Some(escaped) => {
+                            self.scratch.push(escaped);
+                            self.bump();
+                        }
+                        None => return Err(self.error("unterminated escape")),
+                    }
+                }
+                Some(byte) => {
+                    self.scratch.push(byte);
+                    self.bump();
+                }
+                None => return Err(self.error("unterminated string")),
+            }
+        }
+        std::str::from_utf8(&self.scratch).map_err(|_| self.error("name not utf8"))
+    }
 
-impl ExportCompressedVec {
-    fn item_chunks<C, V>(self, to_object: &mut C) -> impl Iterator<Item = V> + '_
-    where
-        C: Fn(String, f32, f32) -> V,
-    {
-        if !self.inner.len().is_multiple_of(3) {
-            panic!("Unexpected data");
+    fn read_number(&mut self) -> VResult<f64> {
+        let start_offset = self.offset();
+        self.scratch.clear();
+        while let Some(byte @ (b'0'..=b'9' | b'-' | b'+' | b'.' | b'e' | b'E')) = self.peek()? {
+            self.scratch.push(byte);
+            self.bump();
+        }
+        if self.scratch.is_empty() {
+            return Err(self.error("expected number"));
         }
-        self.inner
-            .into_iter()
-            .array_chunks()
-            .map(|[name_raw, x_raw, y_raw]| {
-                let name = name_raw.as_string().to_string();
-                let x = x_raw.into_f32();
-                let y = y_raw.into_f32();
-                to_object(name, x, y)
-            })
+        std::str::from_utf8(&self.scratch)
+            .ok()
+            .and_then(|raw| raw.parse::<f64>().ok())
+            .ok_or_else(|| self.error_at(start_offset, "invalid number"))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::gamedata::compressed_export::parse_exported_lua_reader;
+    use crate::surface::pixel::Pixel;
+    use crate::surfacev::err::VError;
+    use std::path::Path;
+
+    fn parse(input: &str) -> Result<Vec<(Pixel, f32, f32, Option<u32>)>, VError> {
+        parse_exported_lua_reader(input.as_bytes(), Path::new("test"), |name, x, y, amount| {
+            (name, x, y, amount)
+        })
+        .map(|v| v.items)
+    }
+
+    #[test]
+    fn test_compact_and_extended() {
+        assert_eq!(
+            parse(r#"["coal",-5.5,2.5,"water",-5,3]"#).unwrap(),
+            [
+                (Pixel::Coal, -5.5, 2.5, None),
+                (Pixel::Water, -5.0, 3.0, None)
+            ]
+        );
+        assert_eq!(
+            parse(" [ \"stone\", 1.5, 2.5, 1200 ,\"coal\",0.5,0.5,7]\n").unwrap(),
+            [
+                (Pixel::Stone, 1.5, 2.5, Some(1200)),
+                (Pixel::Coal, 0.5, 0.5, Some(7))
+            ]
+        );
+        assert!(parse("[]").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_unknown_names_counted() {
+        let parsed = parse_exported_lua_reader(
+            r#"["foo",1.5,1.5,"coal",2.5,2.5,"foo",3.5,3.5]"#.as_bytes(),
+            Path::new("test"),
+            |name, _, _, _| name,
+        )
+        .unwrap();
+        assert_eq!(parsed.items, [Pixel::Coal]);
+        assert_eq!(parsed.unknown_names.get("foo"), Some(&2));
+    }
+
+    #[test]
+    fn test_error_offset() {
+        let Err(VError::ExportParse { offset, .. }) = parse(r#"["coal",1.5,"x"]"#) else {
+            panic!("expected parse error");
+        };
+        assert_eq!(offset, 12);
+        assert!(parse(r#"["coal",1.5,2.5"#).is_err());
     }
 }
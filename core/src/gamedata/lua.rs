@@ -1,35 +1,67 @@
-use crate::gamedata::compressed_export::parse_exported_lua_data;
+use crate::gamedata::compressed_export::parse_exported_lua_file;
 use crate::surface::pixel::Pixel;
+use crate::surfacev::err::{CoreConvertPathResult, VError, VResult};
 use crate::surfacev::fast_metrics::{FastMetric, FastMetrics};
 use facto_loop_miner_common::LOCALE;
 use facto_loop_miner_common::duration::BasicWatch;
+use facto_loop_miner_common::err_utils::xbt;
 use facto_loop_miner_fac_engine::blueprint::bpfac::position::FacBpPosition;
+use facto_loop_miner_fac_engine::common::varea::VArea;
+use facto_loop_miner_fac_engine::common::vpoint::VPoint;
 use num_format::ToFormattedString;
 use serde::{Deserialize, Serialize};
-use std::fs::read;
-use std::path::Path;
-use tracing::{debug, info};
+use std::collections::HashMap;
+use std::fs::read_dir;
+use std::io;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use tracing::{debug, info, warn};
 
-pub fn read_lua_tiles(input_dir: &Path) -> Vec<LuaEntity> {
-    let read_watch = BasicWatch::start();
-    let input_path = input_dir.join("big-entities-a.json");
-    let mut raw_input = read(&input_path).unwrap();
-    debug!(
-        "-- Read Lua export JSON {} in {}",
-        input_path.display(),
-        read_watch
-    );
+const ENTITY_FILE_PREFIX: &str = "big-entities";
+const TILE_FILE_PREFIX: &str = "big-tiles";
+const OBSTACLE_FILE_PREFIX: &str = "big-obstacles";
 
-    // let data_inner: ExportCompressedVec = open_data_file(&input_path);
-    // let data_inner: ExportCompressedv2 = open_data_file(&input_path, raw_input);
-    let entities = parse_exported_lua_data(&mut raw_input, |name, x, y| LuaEntity {
+/// Every sector file of a scanner export merged
+pub struct LuaExport {
+    pub entities: Vec<LuaEntity>,
+    pub tiles: Vec<LuaTile>,
+    /// One entry per occupied tile. Older exports have none
+    pub obstacles: Vec<LuaEntity>,
+}
+
+pub fn read_lua_export(input_dir: &Path) -> VResult<LuaExport> {
+    let read_watch = BasicWatch::start();
+    let entities = read_lua_sectors(input_dir, ENTITY_FILE_PREFIX, |name, x, y, amount| {
+        LuaEntity {
+            name,
+            position: FacBpPosition { x, y },
+            amount,
+        }
+    })?;
+    let tiles = read_lua_sectors(input_dir, TILE_FILE_PREFIX, |name, x, y, _| LuaTile {
         name,
         position: FacBpPosition { x, y },
-    })
-    .unwrap();
+    })?;
+    let obstacles = read_lua_sectors(input_dir, OBSTACLE_FILE_PREFIX, |name, x, y, _| LuaEntity {
+        name,
+        position: FacBpPosition { x, y },
+        amount: None,
+    })?;
+    if entities.is_empty() {
+        return Err(VError::IoError {
+            path: input_dir.to_string_lossy().to_string(),
+            err: io::Error::new(
+                ErrorKind::NotFound,
+                format!("no {ENTITY_FILE_PREFIX}*.json entities"),
+            ),
+            backtrace: xbt(),
+        });
+    }
     info!(
-        "-- Loaded Lua {} entities in {}",
+        "-- Loaded Lua {} entities {} tiles {} obstacles in {}",
         entities.len().to_formatted_string(&LOCALE),
+        tiles.len().to_formatted_string(&LOCALE),
+        obstacles.len().to_formatted_string(&LOCALE),
         read_watch
     );
 
@@ -37,33 +69,124 @@ pub fn read_lua_tiles(input_dir: &Path) -> Vec<LuaEntity> {
     for entity in &entities {
         metric.increment(FastMetric::VSurface_Pixel(entity.name));
     }
+    for tile in &tiles {
+        metric.increment(FastMetric::VSurface_Pixel(tile.name));
+    }
+    for obstacle in &obstacles {
+        metric.increment(FastMetric::VSurface_Pixel(obstacle.name));
+    }
     metric.log_final();
 
-    // debug!("-- sample 0 {:?}", data.entities[0]);
-    // debug!("-- sample 1 {:?}", data.entities[1]);
-
-    // let mut printed: Vec<String> = Vec::new();
-    // for tile in &data.tile {
-    //     let name = tile.name().to_string();
-    //     if !printed.contains(&name) {
-    //         tracing::debug!("type {}", &name);
-    //         printed.push(name);
-    //     }
-    // }
-    // for tile in &data.resource {
-    //     let name = tile.lua_type.to_string();
-    //     if !printed.contains(&name) {
-    //         tracing::debug!("type {}", &name);
-    //         printed.push(name);
-    //     }
-    // }
-
-    entities
+    Ok(LuaExport {
+        entities,
+        tiles,
+        obstacles,
+    })
+}
+
+/// Scanner splits large exports into sectors, eg `big-tiles1.json` ... `big-tiles4.json`
+fn find_sector_files(input_dir: &Path, prefix: &str) -> VResult<Vec<PathBuf>> {
+    let mut paths = Vec::new();
+    for entry in read_dir(input_dir).convert(input_dir)? {
+        let path = entry.convert(input_dir)?.path();
+        let file_name = path.file_name().unwrap().to_string_lossy();
+        if file_name.starts_with(prefix) && file_name.ends_with(".json") {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+struct LuaSector<T> {
+    path: PathBuf,
+    things: Vec<T>,
+}
+
+fn read_lua_sectors<T, C>(input_dir: &Path, prefix: &str, to_object: C) -> VResult<Vec<T>>
+where
+    T: LuaThing,
+    C: Fn(Pixel, f32, f32, Option<u32>) -> T,
+{
+    let mut sectors = Vec::new();
+    for path in find_sector_files(input_dir, prefix)? {
+        let read_watch = BasicWatch::start();
+        let parsed = parse_exported_lua_file(&path, &to_object)?;
+        if !parsed.unknown_names.is_empty() {
+            warn!(
+                "Skipped unknown names in {} {:?}",
+                path.display(),
+                parsed.unknown_names
+            );
+        }
+        let things = parsed.items;
+        let area = if things.is_empty() {
+            None
+        } else {
+            Some(VArea::from_arbitrary_points(
+                things.iter().map(LuaThing::point),
+            ))
+        };
+        debug!(
+            "-- Read Lua export sector {} with {} in {} area {}",
+            path.display(),
+            things.len().to_formatted_string(&LOCALE),
+            read_watch,
+            area.as_ref().map(VArea::to_string).unwrap_or_default(),
+        );
+        sectors.push(LuaSector { path, things });
+    }
+    remove_sector_duplicates(&mut sectors)?;
+    Ok(sectors.into_iter().flat_map(|v| v.things).collect())
+}
+
+/// Sector boundaries may be inclusive on both sides, keep the first copy of every point
+fn remove_sector_duplicates<T: LuaThing>(sectors: &mut [LuaSector<T>]) -> VResult<()> {
+    let mut seen: HashMap<VPoint, Pixel> = HashMap::new();
+    for sector in sectors {
+        let mut conflict = None;
+        let total_before = sector.things.len();
+        sector.things.retain(|thing| {
+            let point = thing.point();
+            match seen.get(&point) {
+                Some(existing) => {
+                    if existing != thing.name() && conflict.is_none() {
+                        conflict = Some((point, *existing, *thing.name()));
+                    }
+                    false
+                }
+                None => {
+                    seen.insert(point, *thing.name());
+                    true
+                }
+            }
+        });
+        if let Some((pos, existing, new)) = conflict {
+            return Err(VError::ImportConflict {
+                path: sector.path.to_string_lossy().to_string(),
+                pos,
+                existing,
+                new,
+                backtrace: xbt(),
+            });
+        }
+
+        let total_duplicate = total_before - sector.things.len();
+        if total_duplicate != 0 {
+            warn!(
+                "-- sector {} removed {} duplicate points",
+                sector.path.display(),
+                total_duplicate.to_formatted_string(&LOCALE),
+            );
+        }
+    }
+    Ok(())
 }
 
 pub trait LuaThing {
     fn name(&self) -> &Pixel;
     fn position(&self) -> &FacBpPosition;
+    fn point(&self) -> VPoint;
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -73,6 +196,9 @@ pub struct LuaEntity {
     pub name: Pixel,
     #[serde(rename = "pos")]
     pub position: FacBpPosition,
+    /// Only in the extended export format
+    #[serde(default)]
+    pub amount: Option<u32>,
 }
 
 impl LuaThing for LuaEntity {
@@ -82,9 +208,13 @@ impl LuaThing for LuaEntity {
     fn position(&self) -> &FacBpPosition {
         &self.position
     }
+    /// Resource entity position is the tile center
+    fn point(&self) -> VPoint {
+        self.position.to_vpoint_with_offset(0.5, 0.5)
+    }
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Debug)]
 pub struct LuaTile {
     pub name: Pixel,
     pub position: FacBpPosition,
@@ -97,4 +227,122 @@ impl LuaThing for LuaTile {
     fn position(&self) -> &FacBpPosition {
         &self.position
     }
+    /// Tile position is the top left corner, shifted to match the entity center conversion
+    fn point(&self) -> VPoint {
+        self.position.to_vpoint_with_offset(1.0, 1.0)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::gamedata::lua::{
+        LuaSector, LuaThing, LuaTile, find_sector_files, remove_sector_duplicates,
+    };
+    use crate::surface::pixel::Pixel;
+    use crate::surfacev::err::VError;
+    use facto_loop_miner_fac_engine::blueprint::bpfac::position::FacBpPosition;
+    use facto_loop_miner_fac_engine::common::vpoint::VPoint;
+    use itertools::Itertools;
+    use std::env::temp_dir;
+    use std::fs::{create_dir_all, remove_dir_all, write};
+    use std::path::PathBuf;
+
+    fn sector(path: &str, tiles: &[(i32, i32, Pixel)]) -> LuaSector<LuaTile> {
+        let things = tiles
+            .iter()
+            .map(|(x, y, name)| LuaTile {
+                name: *name,
+                // see LuaTile::point
+                position: FacBpPosition::new(*x as f32 - 1.0, *y as f32 - 1.0),
+            })
+            .collect_vec();
+        LuaSector {
+            path: PathBuf::from(path),
+            things,
+        }
+    }
+
+    fn points(sector: &LuaSector<LuaTile>) -> Vec<(i32, i32)> {
+        sector
+            .things
+            .iter()
+            .map(|v| (v.point().x(), v.point().y()))
+            .collect()
+    }
+
+    #[test]
+    fn test_remove_sector_duplicates() {
+        let water = Pixel::Water;
+        let mut sectors = [
+            sector(
+                "big-tiles1.json",
+                &[(0, 0, water), (4, 0, water), (4, 2, water)],
+            ),
+            // shares the x = 4 boundary, (4, 1) is only in this sector
+            sector(
+                "big-tiles2.json",
+                &[(4, 0, water), (4, 1, water), (8, 2, water)],
+            ),
+            sector("big-tiles3.json", &[(8, 2, water), (20, 20, water)]),
+            // duplicated within the sector
+            sector("big-tiles4.json", &[(30, 30, water), (30, 30, water)]),
+        ];
+        remove_sector_duplicates(&mut sectors).unwrap();
+
+        assert_eq!(points(&sectors[0]), [(0, 0), (4, 0), (4, 2)]);
+        assert_eq!(points(&sectors[1]), [(4, 1), (8, 2)]);
+        assert_eq!(points(&sectors[2]), [(20, 20)]);
+        assert_eq!(points(&sectors[3]), [(30, 30)]);
+    }
+
+    #[test]
+    fn test_remove_sector_duplicates_conflict() {
+        let mut sectors = [
+            sector(
+                "big-tiles1.json",
+                &[(0, 0, Pixel::Water), (4, 4, Pixel::Water)],
+            ),
+            sector(
+                "big-tiles2.json",
+                &[(4, 4, Pixel::Empty), (8, 8, Pixel::Empty)],
+            ),
+        ];
+        match remove_sector_duplicates(&mut sectors) {
+            Err(VError::ImportConflict {
+                path,
+                pos,
+                existing,
+                new,
+                ..
+            }) => {
+                assert_eq!(path, "big-tiles2.json");
+                assert_eq!(pos, VPoint::new(4, 4));
+                assert_eq!(existing, Pixel::Water);
+                assert_eq!(new, Pixel::Empty);
+            }
+            other => panic!("expected ImportConflict got {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn test_find_sector_files() {
+        let dir = temp_dir().join(format!("lua-sectors-{}", std::process::id()));
+        create_dir_all(&dir).unwrap();
+        for name in [
+            "big-tiles2.json",
+            "big-tiles1.json",
+            "big-tiles-notes.txt",
+            "big-entities1.json",
+        ] {
+            write(dir.join(name), []).unwrap();
+        }
+
+        let found = find_sector_files(&dir, "big-tiles").unwrap();
+        assert_eq!(
+            found,
+            [dir.join("big-tiles1.json"), dir.join("big-tiles2.json")]
+        );
+
+        remove_dir_all(&dir).unwrap();
+    }
 }
@@ -1,2 +1,3 @@
 mod compressed_export;
 pub mod lua;
+pub mod rcon_scan;
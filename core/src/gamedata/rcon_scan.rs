@@ -0,0 +1,319 @@
+use crate::TILES_PER_CHUNK;
+use crate::gamedata::compressed_export::parse_exported_lua_reader;
+use crate::gamedata::lua::{LuaEntity, LuaExport, LuaTile};
+use crate::surface::pixel::Pixel;
+use crate::surfacev::err::{VError, VResult};
+use facto_loop_miner_common::LOCALE;
+use facto_loop_miner_common::duration::BasicWatch;
+use facto_loop_miner_common::err_utils::xbt;
+use facto_loop_miner_fac_engine::admiral::executor::LuaCompiler;
+use facto_loop_miner_fac_engine::admiral::lua_command::LuaCommand;
+use facto_loop_miner_fac_engine::admiral::lua_command::scanner::{
+    BaseScanner, FacScanChunk, FacScanKind, FacScanPage,
+};
+use facto_loop_miner_fac_engine::blueprint::bpfac::position::FacBpPosition;
+use num_format::ToFormattedString;
+use std::collections::HashMap;
+use std::path::Path;
+use tracing::{debug, info, warn};
+
+/// Same result as the scanner mod files, but queried from a running server page by page.
+/// Only one page of JSON is held in memory at a time
+pub fn read_rcon_export(
+    client: &mut impl LuaCompiler,
+    radius_chunks: u32,
+    page_chunks: u32,
+    response_bytes: u32,
+    trees: bool,
+) -> VResult<LuaExport> {
+    let read_watch = BasicWatch::start();
+    let radius_tiles = radius_chunks * TILES_PER_CHUNK as u32;
+    client.execute_checked_command(BaseScanner::new_radius(radius_tiles).into_boxed())?;
+
+    let pages = page_areas(radius_chunks, page_chunks);
+    let response_bytes = response_bytes as usize;
+    let entities = scan_pages(
+        client,
+        &pages,
+        response_bytes,
+        FacScanKind::Entities,
+        |name, x, y, amount| LuaEntity {
+            name,
+            position: FacBpPosition { x, y },
+            amount,
+        },
+    )?;
+    let tiles = scan_pages(
+        client,
+        &pages,
+        response_bytes,
+        FacScanKind::Tiles,
+        |name, x, y, _| LuaTile {
+            name,
+            position: FacBpPosition { x, y },
+        },
+    )?;
+    let obstacles = scan_pages(
+        client,
+        &pages,
+        response_bytes,
+        FacScanKind::Obstacles { trees },
+        |name, x, y, _| LuaEntity {
+            name,
+            position: FacBpPosition { x, y },
+            amount: None,
+        },
+    )?;
+    info!(
+        "-- Scanned RCON {} pages {} entities {} tiles {} obstacles in {}",
+        pages.len(),
+        entities.len().to_formatted_string(&LOCALE),
+        tiles.len().to_formatted_string(&LOCALE),
+        obstacles.len().to_formatted_string(&LOCALE),
+        read_watch
+    );
+
+    Ok(LuaExport {
+        entities,
+        tiles,
+        obstacles,
+    })
+}
+
+fn scan_pages<T, C>(
+    client: &mut impl LuaCompiler,
+    pages: &[([i32; 2], [i32; 2])],
+    response_bytes: usize,
+    kind: FacScanKind,
+    mut to_object: C,
+) -> VResult<Vec<T>>
+where
+    C: FnMut(Pixel, f32, f32, Option<u32>) -> T,
+{
+    let mut things = Vec::new();
+    let mut unknown_names: HashMap<String, usize> = HashMap::new();
+    for (top_left, bottom_right) in pages {
+        let page = FacScanPage::new(kind, *top_left, *bottom_right);
+        // errors point at the page instead of a file
+        let page_name = format!("rcon {kind:?} {top_left:?} {bottom_right:?}");
+        let page_json = read_page(client, page, response_bytes, &page_name)?;
+        let parsed =
+            parse_exported_lua_reader(page_json.as_bytes(), Path::new(&page_name), &mut to_object)?;
+        debug!("-- {page_name} found {}", parsed.items.len());
+        things.extend(parsed.items);
+        for (name, count) in parsed.unknown_names {
+            *unknown_names.entry(name).or_default() += count;
+        }
+    }
+    if !unknown_names.is_empty() {
+        warn!("Skipped unknown names in rcon {kind:?} {unknown_names:?}");
+    }
+    Ok(things)
+}
+
+/// Reassemble the page JSON from bounded [`FacScanChunk`] responses
+fn read_page(
+    client: &mut impl LuaCompiler,
+    page: FacScanPage,
+    response_bytes: usize,
+    page_name: &str,
+) -> VResult<String> {
+    let response = client._execute_statement(page)?;
+    let Ok(total_bytes) = response.body.trim().parse::<usize>() else {
+        return Err(chunk_error(
+            page_name,
+            0,
+            format!("expected page size got {}", response.body.trim()),
+        ));
+    };
+
+    let mut page_json = String::with_capacity(total_bytes);
+    for index in 0..total_bytes.div_ceil(response_bytes) {
+        let response = client._execute_statement(FacScanChunk::new(index, response_bytes))?;
+        // rcon.print appends a newline
+        let body = response.body.strip_suffix('\n').unwrap_or(&response.body);
+        let Some(chunk) = body.strip_prefix(&format!("{index}:")) else {
+            let start: String = body.chars().take(20).collect();
+            return Err(chunk_error(
+                page_name,
+                page_json.len(),
+                format!("expected chunk {index} got {start}"),
+            ));
+        };
+        page_json.push_str(chunk);
+    }
+    if page_json.len() != total_bytes {
+        return Err(chunk_error(
+            page_name,
+            page_json.len(),
+            format!("expected {total_bytes} bytes, responses truncated?"),
+        ));
+    }
+    Ok(page_json)
+}
+
+fn chunk_error(page_name: &str, offset: usize, message: String) -> VError {
+    VError::ExportParse {
+        path: page_name.to_string(),
+        offset: offset as u64,
+        message,
+        backtrace: xbt(),
+    }
+}
+
+/// Square pages in tiles covering the radius, the last row and column may be smaller
+fn page_areas(radius_chunks: u32, page_chunks: u32) -> Vec<([i32; 2], [i32; 2])> {
+    let radius = radius_chunks as i32;
+    let starts: Vec<i32> = (-radius..radius).step_by(page_chunks as usize).collect();
+    let tiles = TILES_PER_CHUNK as i32;
+
+    let mut pages = Vec::new();
+    for &y in &starts {
+        for &x in &starts {
+            let end_x = (x + page_chunks as i32).min(radius);
+            let end_y = (y + page_chunks as i32).min(radius);
+            pages.push(([x * tiles, y * tiles], [end_x * tiles, end_y * tiles]));
+        }
+    }
+    pages
+}
+
+#[cfg(test)]
+mod test {
+    use crate::gamedata::rcon_scan::{page_areas, read_rcon_export};
+    use crate::surface::pixel::Pixel;
+    use crate::surfacev::err::VError;
+    use facto_loop_miner_fac_engine::admiral::err::AdmiralResult;
+    use facto_loop_miner_fac_engine::admiral::executor::{ExecuteResponse, LuaCompiler};
+    use facto_loop_miner_fac_engine::admiral::lua_command::LuaCommand;
+    use std::collections::VecDeque;
+
+    /// Answers scan pages from memory instead of a Factorio server.
+    ///
+    /// Responses over `max_response_bytes` are split like RCON packets,
+    /// each request only reads the next packet
+    struct FakeServer {
+        entities: Vec<(&'static str, f32, f32, u32)>,
+        tiles: Vec<(&'static str, f32, f32)>,
+        max_response_bytes: usize,
+        page_json: String,
+        packets: VecDeque<String>,
+    }
+
+    impl FakeServer {
+        fn new(max_response_bytes: usize) -> Self {
+            FakeServer {
+                entities: vec![
+                    ("coal", -40.5, -40.5, 300),
+                    ("coal", -41.5, -40.5, 250),
+                    ("iron-ore", 31.5, 0.5, 5),
+                    ("steel-chest", 0.5, 0.5, 0),
+                    ("tree-01", 3.5, 3.5, 0),
+                ],
+                tiles: vec![("water", 32.0, 32.0), ("water", -1.0, -1.0)],
+                max_response_bytes,
+                page_json: String::new(),
+                packets: VecDeque::new(),
+            }
+        }
+
+        fn page_area(lua: &str) -> [f32; 4] {
+            let start = lua.find("local area = ").unwrap() + "local area = ".len();
+            let end = start + lua[start..].find("} }").unwrap();
+            let numbers: Vec<f32> = lua[start..end]
+                .split(|c: char| !(c.is_ascii_digit() || c == '-'))
+                .filter(|v| !v.is_empty())
+                .map(|v| v.parse().unwrap())
+                .collect();
+            numbers.try_into().unwrap()
+        }
+
+        fn respond(&mut self, lua_text: &str) -> String {
+            if let Some(checked) = lua_text.split("rcon.print('").nth(1) {
+                // the checked command id
+                return checked.trim_end_matches("')").to_string();
+            }
+            if let Some(range) = lua_text.split("string.sub(facscan_page, ").nth(1) {
+                let index = lua_text.split('"').nth(1).unwrap().trim_end_matches(':');
+                let (start, end) = range.trim_end_matches("))").split_once(", ").unwrap();
+                let start: usize = start.parse().unwrap();
+                let end = end.parse::<usize>().unwrap().min(self.page_json.len());
+                return format!("{index}:{}\n", &self.page_json[(start - 1)..end]);
+            }
+
+            let [left, top, right, bottom] = Self::page_area(lua_text);
+            let in_page = |x: f32, y: f32| x >= left && x < right && y >= top && y < bottom;
+            let mut output = Vec::new();
+            if lua_text.contains("find_tiles_filtered") {
+                for (name, x, y) in &self.tiles {
+                    if in_page(*x, *y) {
+                        output.push(format!("\"{name}\",{x},{y}"));
+                    }
+                }
+            } else if lua_text.contains("entity.amount") {
+                for (name, x, y, amount) in &self.entities {
+                    if in_page(*x, *y) {
+                        output.push(format!("\"{name}\",{x},{y},{amount}"));
+                    }
+                }
+            }
+            self.page_json = format!("[{}]", output.join(","));
+            format!("{}\n", self.page_json.len())
+        }
+    }
+
+    impl LuaCompiler for FakeServer {
+        fn _execute_statement(&mut self, lua: impl LuaCommand) -> AdmiralResult<ExecuteResponse> {
+            let lua_text = lua.make_lua();
+            let response = self.respond(&lua_text);
+            for packet in response.as_bytes().chunks(self.max_response_bytes) {
+                self.packets
+                    .push_back(String::from_utf8(packet.to_vec()).unwrap());
+            }
+            let body = self.packets.pop_front().unwrap();
+            Ok(ExecuteResponse { body, lua_text })
+        }
+    }
+
+    #[test]
+    fn test_pages_cover_radius() {
+        let pages = page_areas(5, 4);
+        assert_eq!(pages.len(), 9);
+        assert_eq!(pages[0], ([-160, -160], [-32, -32]));
+        assert_eq!(pages[8], ([96, 96], [160, 160]));
+    }
+
+    #[test]
+    fn test_fake_server_scan() {
+        // coal page is 47 bytes, read in 3 chunks
+        let mut server = FakeServer::new(24);
+        let export = read_rcon_export(&mut server, 2, 1, 20, false).unwrap();
+
+        let mut entities: Vec<(Pixel, Option<u32>)> =
+            export.entities.iter().map(|v| (v.name, v.amount)).collect();
+        entities.sort();
+        assert_eq!(
+            entities,
+            [
+                (Pixel::IronOre, Some(5)),
+                (Pixel::Coal, Some(250)),
+                (Pixel::Coal, Some(300)),
+                (Pixel::SteelChest, Some(0)),
+            ]
+        );
+        assert_eq!(export.tiles.len(), 2);
+        assert!(export.obstacles.is_empty());
+        assert!(server.packets.is_empty());
+    }
+
+    #[test]
+    fn test_split_response_detected() {
+        // chunks larger than the server sends in one packet
+        let mut server = FakeServer::new(24);
+        let result = read_rcon_export(&mut server, 2, 1, 100, false);
+        assert!(
+            matches!(result, Err(VError::ExportParse { .. })),
+            "expected ExportParse"
+        );
+    }
+}
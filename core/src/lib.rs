@@ -26,18 +26,21 @@
 // TODO #![deny(let-underscore)]
 // TODO #![deny(nonstandard-style)]
 
-use crate::state::machine_v1::new_v1_machine;
+use crate::cli::{CliArgs, CliCommand, run_diff, run_overlay, run_tiles, run_validate};
 use crate::surface::pixel::generate_lookup_image;
+use clap::Parser;
 use facto_loop_miner_common::duration::BasicWatch;
+use facto_loop_miner_common::err_bt::pretty_print_error;
 use facto_loop_miner_common::log_init_trace;
 use kiddo::float;
-use std::path::Path;
 use tracing::info;
 
 pub use facto_loop_miner_common::util::always_true_test;
 
+mod cli;
 mod gamedata;
 mod navigator;
+#[cfg(feature = "opencv_re")]
 mod opencv;
 // mod simd_diff;
 mod state;
@@ -56,13 +59,53 @@ pub fn inner_main() {
     // let mut data = String::new();
     // stdin().read_line(&mut data).expect("asd");
 
-    let root_dir = Path::new("work");
+    let args = CliArgs::parse();
 
     let watch = BasicWatch::start();
-    match 1 {
-        1 => new_v1_machine().start(root_dir),
-        3 => generate_lookup_image(),
-        _ => panic!("wtf"),
+    match args.command {
+        CliCommand::Run {
+            machine,
+            steps,
+            force_from,
+            stop_at,
+            tunables,
+        } => match tunables.load(&machine.work_dir) {
+            Ok(tunables) => machine.pipeline.new_machine().start(
+                &machine.work_dir,
+                CliCommand::run_options(steps, force_from, stop_at),
+                tunables,
+            ),
+            Err(e) => pretty_print_error(e),
+        },
+        CliCommand::Steps { machine } => machine.pipeline.new_machine().list(&machine.work_dir),
+        CliCommand::LookupImage => generate_lookup_image(),
+        CliCommand::Diff {
+            before,
+            after,
+            image_dir,
+        } => {
+            if let Err(e) = run_diff(&before, &after, image_dir.as_deref()) {
+                pretty_print_error(e)
+            }
+        }
+        CliCommand::Tiles { step_dir, out_dir } => {
+            if let Err(e) = run_tiles(&step_dir, out_dir.as_deref()) {
+                pretty_print_error(e)
+            }
+        }
+        CliCommand::Overlay { step_dir, out_dir } => {
+            if let Err(e) = run_overlay(&step_dir, out_dir.as_deref()) {
+                pretty_print_error(e)
+            }
+        }
+        CliCommand::Validate {
+            step_dir,
+            report_dir,
+        } => {
+            if let Err(e) = run_validate(&step_dir, report_dir.as_deref()) {
+                pretty_print_error(e)
+            }
+        }
     }
     info!("Total time {watch}")
 }
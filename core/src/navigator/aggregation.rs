@@ -0,0 +1,432 @@
+use crate::navigator::base_source::BaseSourceEighth;
+use crate::navigator::mine_executor::{
+    ExecuteFlags, ExecutionRoute, ExecutionSequence, ExecutorResult, execute_route_batch_clone_prep,
+};
+use crate::navigator::mine_selector::group_nearby_patches;
+use crate::navigator::planners::PathingTunables;
+use crate::state::tuneables::AggregationTunables;
+use crate::surface::pixel::Pixel;
+use crate::surfacev::mine::{MineLocation, MinePathKind};
+use crate::surfacev::vsurface::{
+    VSurfaceNavMut, VSurfacePatchAsVs, VSurfacePixel, VSurfacePixelAsVs, VSurfacePixelAsVsMut,
+    VSurfaceRail, VSurfaceRailAsVsMut,
+};
+use facto_loop_miner_fac_engine::common::varea::VArea;
+use facto_loop_miner_fac_engine::common::vpoint::{VPOINT_ONE, VPOINT_ZERO, VPoint};
+use facto_loop_miner_fac_engine::common::vpoint_direction::VPointDirectionQ;
+use facto_loop_miner_fac_engine::game_blocks::rail_hope_single::SECTION_POINTS_I32;
+use facto_loop_miner_fac_engine::game_entities::direction::FacDirectionQuarter;
+use itertools::Itertools;
+use tracing::{info, warn};
+
+/// Station side in sodas, room for a long train
+const STATION_SODAS: i32 = 4;
+/// How far towards the base a station may move when the cluster center is taken
+const STATION_PLACEMENT_STEPS: i32 = 8;
+/// Feeder loops may wander this far outside the cluster
+const FEEDER_LIMITER_MARGIN: i32 = SECTION_POINTS_I32 * 6;
+
+/// Small remote mines sharing one aggregation outpost
+struct AggregationSite {
+    station: MineLocation,
+    feeders: Vec<MineLocation>,
+    /// Feeder loops leave the station here, away from the base
+    feeder_origin: VPointDirectionQ,
+}
+
+/// Route clusters of small remote mines to a new aggregation outpost station.
+///
+/// Runs before the planner, which routes each station instead of its feeders.
+/// See [mines_to_base]
+pub fn plan_aggregation_outposts(tunables: &PathingTunables, surface: &mut VSurfaceNavMut) {
+    if !tunables.aggregation().enabled {
+        return;
+    }
+    let mut mines = group_nearby_patches(surface.patches());
+    let sites = select_sites(tunables.aggregation(), surface.pixels(), &mut mines);
+    info!("selected {} aggregation outposts", sites.len());
+
+    for mine in mines.iter().chain(sites.iter().flat_map(|v| &v.feeders)) {
+        mine.draw_area_buffered(&mut surface.pixels_mut());
+    }
+    let mut total_feeders = 0;
+    for site in sites {
+        total_feeders += route_feeders(tunables, surface, site);
+    }
+    surface.pixels_mut().commit();
+    info!("routed {total_feeders} mines to aggregation outposts");
+}
+
+/// Swap mines feeding an aggregation outpost for the outpost station
+pub fn mines_to_base(rails: VSurfaceRail, mut mines: Vec<MineLocation>) -> Vec<MineLocation> {
+    let mut stations: Vec<&MineLocation> = Vec::new();
+    for mine_path in rails.get_mine_paths() {
+        if let MinePathKind::MineToAggregator { station } = &mine_path.kind {
+            mines.retain(|mine| *mine != mine_path.location);
+            if !stations.contains(&station) {
+                stations.push(station);
+            }
+        }
+    }
+    mines.extend(stations.into_iter().cloned());
+    mines
+}
+
+fn select_sites(
+    tunables: &AggregationTunables,
+    surface: VSurfacePixel,
+    mines: &mut Vec<MineLocation>,
+) -> Vec<AggregationSite> {
+    let areas = mines.iter().map(|v| v.area_min()).collect_vec();
+    let clusters = find_clusters(tunables, &areas);
+
+    let mut sites: Vec<AggregationSite> = Vec::new();
+    let mut claimed = vec![false; mines.len()];
+    for cluster in clusters {
+        let feeders = cluster.iter().map(|i| &mines[*i]).collect_vec();
+        let Some((station, feeder_origin)) = place_station(surface, &feeders, mines) else {
+            warn!(
+                "no room for aggregation station near {}",
+                feeders[0].area_min().point_center()
+            );
+            continue;
+        };
+        let station_area = station.area_buffered();
+        if sites
+            .iter()
+            .any(|site| site.station.area_buffered().overlaps(station_area))
+        {
+            warn!(
+                "aggregation station {} overlaps another",
+                station.area_min()
+            );
+            continue;
+        }
+        for i in &cluster {
+            claimed[*i] = true;
+        }
+        sites.push(AggregationSite {
+            station,
+            feeders: feeders.into_iter().cloned().collect(),
+            feeder_origin,
+        });
+    }
+
+    let mut claimed = claimed.into_iter();
+    mines.retain(|_| !claimed.next().unwrap());
+    sites
+}
+
+/// Indexes of small remote mines near each other, clusters farthest from the base first
+fn find_clusters(tunables: &AggregationTunables, areas: &[&VArea]) -> Vec<Vec<usize>> {
+    let base_distance = |i: &usize| areas[*i].point_center().distance_bird(&VPOINT_ZERO);
+    let mut candidates = (0..areas.len())
+        .filter(|i| {
+            let size = areas[*i].as_size() + VPOINT_ONE;
+            let tiles = size.x() as u32 * size.y() as u32;
+            tiles <= tunables.max_mine_tiles
+                && base_distance(i) >= tunables.min_base_distance as f32
+        })
+        .collect_vec();
+    candidates.sort_by(|a, b| base_distance(b).total_cmp(&base_distance(a)));
+
+    let mut claimed = vec![false; areas.len()];
+    let mut clusters = Vec::new();
+    for seed in &candidates {
+        if claimed[*seed] {
+            continue;
+        }
+        let seed_center = areas[*seed].point_center();
+        let seed_distance = |i: &usize| areas[*i].point_center().distance_bird(&seed_center);
+        let mut members = candidates
+            .iter()
+            .copied()
+            .filter(|i| !claimed[*i] && seed_distance(i) <= tunables.cluster_radius as f32)
+            .collect_vec();
+        members.sort_by(|a, b| seed_distance(a).total_cmp(&seed_distance(b)));
+        members.truncate(tunables.max_cluster_mines);
+        if members.len() < tunables.min_cluster_mines {
+            continue;
+        }
+        for member in &members {
+            claimed[*member] = true;
+        }
+        clusters.push(members);
+    }
+    clusters
+}
+
+/// Station in empty space at the cluster center, or moved towards the base
+fn place_station(
+    surface: VSurfacePixel,
+    feeders: &[&MineLocation],
+    mines: &[MineLocation],
+) -> Option<(MineLocation, VPointDirectionQ)> {
+    let cluster_center = VArea::from_arbitrary_points(
+        feeders
+            .iter()
+            .flat_map(|v| v.area_min().get_corner_points()),
+    )
+    .point_center()
+    .move_round_rail_down();
+    let towards_base = direction_towards_base(cluster_center);
+    let half_size = STATION_SODAS * SECTION_POINTS_I32 / 2;
+
+    for step in 0..STATION_PLACEMENT_STEPS {
+        let center = cluster_center.move_direction_int(towards_base, step * SECTION_POINTS_I32);
+        let area_min = VArea::from_arbitrary_points_pair(
+            center - VPoint::new(half_size, half_size),
+            center + VPoint::new(half_size, half_size),
+        );
+        let Some(station) = MineLocation::new_aggregation_station(surface, area_min) else {
+            continue;
+        };
+
+        // leave a soda of room for the feeder sources and the station endpoints
+        let room = station.area_buffered().expand_margin(SECTION_POINTS_I32);
+        if room
+            .get_corner_points()
+            .iter()
+            .any(|v| surface.is_point_out_of_bounds(v))
+            || mines.iter().any(|v| v.area_buffered().overlaps(&room))
+            || room
+                .get_points()
+                .into_iter()
+                .any(|v| surface.get_pixel(v) != Pixel::Empty)
+        {
+            continue;
+        }
+
+        let away_from_base = towards_base.rotate_flip();
+        let feeder_origin = VPointDirectionQ(
+            center.move_direction_int(away_from_base, half_size + SECTION_POINTS_I32 * 2),
+            away_from_base,
+        );
+        return Some((station, feeder_origin));
+    }
+    None
+}
+
+fn direction_towards_base(point: VPoint) -> FacDirectionQuarter {
+    if point.x().abs() >= point.y().abs() {
+        if point.x() > 0 {
+            FacDirectionQuarter::West
+        } else {
+            FacDirectionQuarter::East
+        }
+    } else if point.y() > 0 {
+        FacDirectionQuarter::North
+    } else {
+        FacDirectionQuarter::South
+    }
+}
+
+/// Route each feeder to the station, returning the number routed.
+/// Feeders that fail are left for the planner to route to the base
+fn route_feeders(
+    tunables: &PathingTunables,
+    surface: &mut VSurfaceNavMut,
+    site: AggregationSite,
+) -> usize {
+    let AggregationSite {
+        station,
+        feeders,
+        feeder_origin,
+    } = site;
+    let checkpoint = surface.rails_mut().begin();
+    station.draw_area_buffered(&mut surface.pixels_mut());
+
+    let finding_limiter = VArea::from_arbitrary_points(
+        feeders
+            .iter()
+            .chain([&station])
+            .flat_map(|v| v.area_buffered().get_corner_points()),
+    )
+    .expand_margin(FEEDER_LIMITER_MARGIN)
+    .normalize_within_radius(surface.pixels().get_radius_i32() - 1);
+
+    let mut feeder_sources = BaseSourceEighth::new(feeder_origin, 1);
+    let mut routed = 0;
+    for feeder in feeders {
+        let source = feeder_sources.peek_single();
+        let sequences = feeder
+            .destinations()
+            .map(|destination| ExecutionSequence {
+                routes: vec![ExecutionRoute {
+                    location: feeder.clone(),
+                    segment: source.segment_for_mine(&destination),
+                    finding_limiter: finding_limiter.clone(),
+                }],
+            })
+            .collect_vec();
+        match execute_route_batch_clone_prep(
+            tunables.mori(),
+            &mut surface.pixels_mut(),
+            sequences,
+            &[ExecuteFlags::ShrinkBases],
+        ) {
+            ExecutorResult::Success { mut paths, .. } => {
+                let mut path = paths.remove(0);
+                path.kind = MinePathKind::MineToAggregator {
+                    station: station.clone(),
+                };
+                surface.rails_mut().add_mine_path(path);
+                feeder_sources.advance_by(1).unwrap();
+                routed += 1;
+            }
+            ExecutorResult::Failure { .. } => {
+                warn!(
+                    "no feeder route from {} to aggregation station {}",
+                    feeder.area_min(),
+                    station.area_min()
+                );
+            }
+        }
+    }
+
+    if routed == 0 {
+        // unused station is only in the way
+        surface.rails_mut().rollback_to(checkpoint);
+    }
+    routed
+}
+
+#[cfg(test)]
+mod test {
+    use crate::navigator::aggregation::{
+        direction_towards_base, find_clusters, place_station, select_sites,
+    };
+    use crate::state::tuneables::AggregationTunables;
+    use crate::surface::pixel::Pixel;
+    use crate::surfacev::mine::MineLocation;
+    use crate::surfacev::vpatch::VPatch;
+    use crate::surfacev::vsurface::{
+        VSurface, VSurfacePatchAsVs, VSurfacePatchAsVsMut, VSurfacePixelAsVs, VSurfacePixelAsVsMut,
+    };
+    use facto_loop_miner_fac_engine::common::varea::VArea;
+    use facto_loop_miner_fac_engine::common::vpoint::VPoint;
+    use facto_loop_miner_fac_engine::game_entities::direction::FacDirectionQuarter;
+    use itertools::Itertools;
+
+    /// One mine per 20x20 patch with the top left at each corner
+    fn add_mines(surface: &mut VSurface, corners: &[(i32, i32)]) -> Vec<MineLocation> {
+        surface
+            .patches_mut()
+            .add_patches(corners.iter().map(|(x, y)| {
+                let area = VArea::from_arbitrary_points_pair(
+                    VPoint::new(*x, *y),
+                    VPoint::new(x + 19, y + 19),
+                );
+                VPatch::new(area.clone(), Pixel::IronOre, area.get_points())
+            }));
+        (0..corners.len())
+            .map(|i| MineLocation::from_patch_indexes(surface.patches(), vec![i]).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_find_clusters() {
+        let tunables = AggregationTunables {
+            max_mine_tiles: 50 * 50,
+            min_base_distance: 1000,
+            cluster_radius: 300,
+            min_cluster_mines: 2,
+            max_cluster_mines: 3,
+            ..AggregationTunables::default()
+        };
+        let mine = |x: i32, y: i32, size: i32| {
+            VArea::from_arbitrary_points_pair(VPoint::new(x, y), VPoint::new(x + size, y + size))
+        };
+        let areas = [
+            // near the base
+            mine(100, 0, 20),
+            mine(200, 0, 20),
+            // far cluster, one too big
+            mine(2000, 0, 20),
+            mine(2100, 120, 20),
+            mine(2000, 200, 200),
+            mine(2200, -100, 20),
+            mine(1900, 100, 20),
+            // far alone
+            mine(-1500, 0, 20),
+        ];
+        let areas = areas.iter().collect_vec();
+
+        let clusters = find_clusters(&tunables, &areas);
+        assert_eq!(clusters, [vec![5, 2, 3]]);
+    }
+
+    #[test]
+    fn test_direction_towards_base() {
+        for (point, direction) in [
+            (VPoint::new(2000, 100), FacDirectionQuarter::West),
+            (VPoint::new(-2000, 100), FacDirectionQuarter::East),
+            (VPoint::new(100, 2000), FacDirectionQuarter::North),
+            (VPoint::new(100, -2000), FacDirectionQuarter::South),
+        ] {
+            assert_eq!(direction_towards_base(point), direction);
+        }
+    }
+
+    #[test]
+    fn test_place_station() {
+        let mut surface = VSurface::new(1500);
+        let mines = add_mines(&mut surface, &[(1000, -100), (1000, 100)]);
+        let feeders = mines.iter().collect_vec();
+
+        let (station, feeder_origin) = place_station(surface.pixels(), &feeders, &mines).unwrap();
+        assert!(station.is_aggregation_station());
+        for mine in &mines {
+            assert!(!mine.area_buffered().overlaps(station.area_buffered()));
+        }
+        // the cluster center is taken, moved towards the base
+        let station_center = station.area_min().point_center();
+        assert!(station_center.x() < 1000);
+        assert!(station_center.y().abs() < 30, "{station_center}");
+        assert_eq!(feeder_origin.1, FacDirectionQuarter::East);
+        assert!(feeder_origin.0.x() > station.area_buffered().point_bottom_right().x());
+
+        // no empty space on the way to the base
+        let water =
+            VArea::from_arbitrary_points_pair(VPoint::new(500, -300), VPoint::new(999, 300));
+        surface
+            .pixels_mut()
+            .change_pixels(water.get_points())
+            .stomp(Pixel::Water);
+        assert!(place_station(surface.pixels(), &feeders, &mines).is_none());
+    }
+
+    #[test]
+    fn test_select_sites() {
+        let tunables = AggregationTunables {
+            min_base_distance: 700,
+            ..AggregationTunables::default()
+        };
+        let mut surface = VSurface::new(1500);
+        let mut mines = add_mines(
+            &mut surface,
+            &[
+                // near the base
+                (200, 0),
+                // cluster
+                (1000, -100),
+                (1000, 100),
+                // far alone
+                (-1000, 0),
+            ],
+        );
+        let all_mines = mines.clone();
+
+        let sites = select_sites(&tunables, surface.pixels(), &mut mines);
+        assert_eq!(sites.len(), 1);
+        let site = &sites[0];
+        assert!(site.station.is_aggregation_station());
+        let mut feeders = site.feeders.clone();
+        feeders.sort();
+        let mut expected_feeders = vec![all_mines[1].clone(), all_mines[2].clone()];
+        expected_feeders.sort();
+        assert_eq!(feeders, expected_feeders);
+        assert_eq!(site.feeder_origin.1, FacDirectionQuarter::East);
+        assert_eq!(mines, [all_mines[0].clone(), all_mines[3].clone()]);
+    }
+}
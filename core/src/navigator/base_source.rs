@@ -1,67 +1,63 @@
 use crate::navigator::planners::PathingTunables;
-use facto_loop_miner_fac_engine::common::vpoint::VPoint;
+use facto_loop_miner_fac_engine::common::varea::VArea;
+use facto_loop_miner_fac_engine::common::vpoint::{VPOINT_ZERO, VPoint};
 use facto_loop_miner_fac_engine::common::vpoint_direction::{VPointDirectionQ, VSegment};
 use facto_loop_miner_fac_engine::game_blocks::rail_hope_single::SECTION_POINTS_I32;
 use facto_loop_miner_fac_engine::game_entities::direction::FacDirectionQuarter;
 use itertools::Itertools;
 use std::cell::RefCell;
 use std::rc::Rc;
+use strum::VariantArray;
 
+/// One positive eighth on every face of the central base.
+///
+/// Each eighth runs clockwise along its face, a pinwheel around the base.
+/// So each face routes into its own area and never crosses another face's sources
 pub struct BaseSource {
-    positive: BaseSourceEighth,
-    negative: BaseSourceEighth,
+    faces: Vec<BaseSourceEighth>,
 }
 
 impl BaseSource {
     pub fn from_central_base(tunables: &PathingTunables) -> Self {
-        let mut offset_x_from_base = tunables.base_chunks().as_tiles_i32();
-        offset_x_from_base -= offset_x_from_base % SECTION_POINTS_I32;
-        BaseSource::new(VPointDirectionQ(
-            VPoint::new(offset_x_from_base, 0),
-            FacDirectionQuarter::East,
-        ))
+        let mut offset_from_base = tunables.base_chunks().as_tiles_i32();
+        offset_from_base -= offset_from_base % SECTION_POINTS_I32;
+        let faces = FacDirectionQuarter::VARIANTS
+            .iter()
+            .map(|direction| {
+                let origin = VPOINT_ZERO.move_direction_int(direction, offset_from_base);
+                origin.assert_even_position();
+                BaseSourceEighth::new(VPointDirectionQ(origin, *direction), 1)
+            })
+            .collect();
+        Self { faces }
     }
 
-    fn new(origin: VPointDirectionQ) -> Self {
-        origin.point().assert_even_position();
-        Self {
-            positive: BaseSourceEighth::new(origin, 1),
-            negative: BaseSourceEighth::new(origin, -1),
-        }
-    }
-
-    // pub fn positive(&mut self) -> &mut BaseSourceEighth {
-    //     &mut self.positive
-    // }
-    //
-    // pub fn negative(&mut self) -> &mut BaseSourceEighth {
-    //     &mut self.negative
-    // }
-
     pub fn into_refcells(self) -> BaseSourceRefs {
         BaseSourceRefs {
-            positive: self.positive.into_rc_refcell(),
-            negative: self.negative.into_rc_refcell(),
+            faces: self
+                .faces
+                .into_iter()
+                .map(BaseSourceEighth::into_rc_refcell)
+                .collect(),
         }
     }
 }
 
 pub struct BaseSourceRefs {
-    positive: Rc<RefCell<BaseSourceEighth>>,
-    negative: Rc<RefCell<BaseSourceEighth>>,
+    faces: Vec<Rc<RefCell<BaseSourceEighth>>>,
 }
 
 impl BaseSourceRefs {
-    pub fn positive_rc(&self) -> Rc<RefCell<BaseSourceEighth>> {
-        self.positive.clone()
-    }
-
-    pub fn negative_rc(&self) -> Rc<RefCell<BaseSourceEighth>> {
-        self.negative.clone()
+    /// North, East, South, West
+    pub fn faces(&self) -> &[Rc<RefCell<BaseSourceEighth>>] {
+        &self.faces
     }
 }
 
 const INTRA_OFFSET: i32 = 6;
+const TOTAL_INTRA_RAILS: i32 = 4;
+/// Half a soda, so route links never overlap a start link of the neighbouring face
+const AREA_MARGIN: i32 = SECTION_POINTS_I32 / 2;
 
 /// From a source point,
 #[derive(Debug, Eq, PartialEq)]
@@ -90,8 +86,6 @@ impl BaseSourceEighth {
     }
 
     fn get_for_index(&self, index: i32) -> BaseSourceEntry {
-        const TOTAL_INTRA_RAILS: i32 = 4;
-
         // non-zero to move outside of no-touch area
         let stay_outside_offset = 1;
         let applied_infra_offset_pos =
@@ -107,7 +101,7 @@ impl BaseSourceEighth {
 
         // calculate the applied offset
         let applied_intra_offset = pos
-            .move_direction_sideways_axis_int(self.origin.direction(), applied_infra_offset_pos)
+            .move_direction_sideways_int(self.origin.direction(), applied_infra_offset_pos)
             - pos;
 
         BaseSourceEntry {
@@ -116,6 +110,12 @@ impl BaseSourceEighth {
         }
     }
 
+    /// Largest [BaseSourceEntry::applied_intra_offset], mine destinations must fit it too
+    pub fn max_intra_offset(&self) -> VPoint {
+        self.get_for_index(TOTAL_INTRA_RAILS - 1)
+            .applied_intra_offset
+    }
+
     pub fn peek_single(&self) -> BaseSourceEntry {
         self.get_for_index(self.next)
     }
@@ -148,6 +148,69 @@ impl BaseSourceEighth {
     pub fn into_rc_refcell(self) -> Rc<RefCell<Self>> {
         Rc::new(RefCell::new(self))
     }
+
+    pub fn direction(&self) -> FacDirectionQuarter {
+        *self.origin.direction()
+    }
+
+    fn offset_from_base(&self) -> i32 {
+        let origin = self.origin.point();
+        origin.x().abs() + origin.y().abs()
+    }
+
+    /// Corner of [Self::finding_limiter] nearest the base
+    fn area_corner(&self) -> VPoint {
+        let direction = self.origin.direction();
+        self.origin
+            .point()
+            .move_direction_int(direction, -AREA_MARGIN)
+            .move_direction_sideways_int(
+                direction,
+                self.sign * (AREA_MARGIN - self.offset_from_base()),
+            )
+    }
+
+    /// In front of the face and beside the base on the eighth's side, out to `radius`
+    pub fn finding_limiter(&self, radius: i32) -> VArea {
+        let direction = self.origin.direction();
+        let far_corner = VPOINT_ZERO
+            .move_direction_int(direction, radius)
+            .move_direction_sideways_int(direction, self.sign * radius);
+        VArea::from_arbitrary_points_pair(self.area_corner(), far_corner)
+    }
+
+    /// Strip `index` of [Self::finding_limiter], `width` wide and reaching `reach` in front
+    /// of the base. None once it starts past `reach`
+    pub fn area_strip(&self, index: i32, width: i32, reach: i32) -> Option<VArea> {
+        let sideways = index * width;
+        if sideways + AREA_MARGIN - self.offset_from_base() > reach {
+            return None;
+        }
+        let direction = self.origin.direction();
+        let start = self
+            .area_corner()
+            .move_direction_sideways_int(direction, self.sign * sideways);
+        let end = start
+            .move_direction_sideways_int(direction, self.sign * width)
+            .move_direction_int(direction, reach - self.offset_from_base() + AREA_MARGIN);
+        Some(VArea::from_arbitrary_points_pair(start, end))
+    }
+
+    /// Whether one of the [Self::area_strip] scans contains the point
+    pub fn is_in_area_strips(&self, point: &VPoint, width: i32, reach: i32) -> bool {
+        (0..)
+            .map_while(|index| self.area_strip(index, width, reach))
+            .any(|strip| strip.contains_point(point))
+    }
+
+    /// Line right behind the face, stopping routes from going around behind the sources
+    pub fn backside_points(&self, radius: i32) -> Vec<VPoint> {
+        let direction = self.origin.direction();
+        let behind = self.origin.point().move_direction_int(direction, -1);
+        ((AREA_MARGIN + 1 - self.offset_from_base())..radius)
+            .map(|i| behind.move_direction_sideways_int(direction, self.sign * i))
+            .collect()
+    }
 }
 
 impl Iterator for BaseSourceEighth {
@@ -203,6 +266,8 @@ mod test {
     use facto_loop_miner_fac_engine::common::vpoint_direction::VPointDirectionQ;
     use facto_loop_miner_fac_engine::game_blocks::rail_hope_single::SECTION_POINTS_I32;
     use facto_loop_miner_fac_engine::game_entities::direction::FacDirectionQuarter;
+    use itertools::Itertools;
+    use strum::VariantArray;
 
     #[test]
     fn test_nexts() {
@@ -265,4 +330,46 @@ mod test {
         test_next(2, 0);
         test_next(2, 1);
     }
+
+    #[test]
+    fn test_nexts_west() {
+        let mut source =
+            BaseSourceEighth::new(VPointDirectionQ(VPOINT_ZERO, FacDirectionQuarter::West), 1);
+        // first index is 1, plus the stay outside offset
+        let intra = ((1 % 4) + 1) * INTRA_OFFSET;
+        assert_eq!(
+            source.next().unwrap(),
+            BaseSourceEntry {
+                origin: VPointDirectionQ(VPoint::new(0, -intra), FacDirectionQuarter::West),
+                applied_intra_offset: VPoint::new(0, -intra)
+            }
+        );
+    }
+
+    #[test]
+    fn test_faces_own_their_sources() {
+        let offset = SECTION_POINTS_I32 * 8;
+        let radius = SECTION_POINTS_I32 * 40;
+        let faces = FacDirectionQuarter::VARIANTS
+            .iter()
+            .map(|direction| {
+                let origin = VPOINT_ZERO.move_direction_int(direction, offset);
+                BaseSourceEighth::new(VPointDirectionQ(origin, *direction), 1)
+            })
+            .collect_vec();
+        for face in &faces {
+            for entry in face.regenerate().take(12) {
+                let start = entry.origin.point();
+                for other in &faces {
+                    assert_eq!(
+                        other.finding_limiter(radius).contains_point(start),
+                        face.direction() == other.direction(),
+                        "{} source {start} in {} limiter",
+                        face.direction(),
+                        other.direction()
+                    );
+                }
+            }
+        }
+    }
 }
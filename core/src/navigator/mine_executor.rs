@@ -1,6 +1,6 @@
 use crate::navigator::mori::{MoriResult, mori2_start};
 use crate::state::tuneables::MoriTunables;
-use crate::surfacev::mine::{MineLocation, MinePath};
+use crate::surfacev::mine::{MineLocation, MinePath, MinePathKind};
 use crate::surfacev::vsurface::{
     VSurfacePixel, VSurfacePixelAsVs, VSurfacePixelAsVsMut, VSurfacePixelMut, VSurfaceRail,
     VSurfaceRailAsVsMut,
@@ -353,13 +353,18 @@ fn execute_route_combination(
             MoriResult::Route { path, sodas, cost } => {
                 // path.extend(extended_entry_rails);
 
-                let path = MinePath {
+                let mut path = MinePath {
                     links: path,
                     sodas,
                     cost,
                     location: route.location.clone(),
                     segment: route.segment.clone(),
+                    kind: MinePathKind::to_base(&route.location),
+                    landfill: Vec::new(),
+                    replaced_obstacles: Vec::new(),
                 };
+                // before the rail pixels replace the water
+                path.landfill = path.find_landfill(surface.pixels());
                 surface.add_mine_path(path);
             }
             MoriResult::FailingDebug { err } => {
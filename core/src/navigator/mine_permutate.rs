@@ -4,7 +4,6 @@ use crate::navigator::mine_selector::MineSelectBatch;
 use crate::surfacev::mine::MineLocation;
 use crate::surfacev::vsurface::VSurfacePixel;
 use facto_loop_miner_fac_engine::common::varea::VArea;
-use facto_loop_miner_fac_engine::common::vpoint::VPoint;
 use facto_loop_miner_fac_engine::common::vpoint_direction::VPointDirectionQ;
 use itertools::Itertools;
 use std::cell::RefCell;
@@ -47,15 +46,12 @@ pub fn get_possible_routes_for_batch(
     //     total_combinations_permut
     // );
 
-    // Limit pathing to the entire right half of the map
-    // todo: autogen this somewhere
-    let fixed_radius = surface.get_radius_i32();
-    let fixed_finding_limiter = VArea::from_arbitrary_points_pair(
-        VPoint::new(0, -fixed_radius),
-        // Must give spacing from Edge, because hope_link.area() can extend past it.
-        // range checks are disabled for theoretical performance
-        VPoint::new(fixed_radius, fixed_radius),
-    );
+    // Limit pathing to the area in front of this face of the base.
+    // Must give spacing from Edge, because hope_link.area() can extend past it.
+    // range checks are disabled for theoretical performance
+    let fixed_finding_limiter = base_sources
+        .borrow()
+        .finding_limiter(surface.get_radius_i32());
 
     // Did we actually generate unique steps?
     // let mut dedupe_test = mine_combinations.iter().collect_vec();
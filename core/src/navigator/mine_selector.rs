@@ -3,12 +3,9 @@ use crate::navigator::base_source::{BaseSource, BaseSourceEighth, BaseSourceRefs
 use crate::navigator::planners::PathingTunables;
 use crate::surface::pixel::Pixel;
 use crate::surfacev::mine::MineLocation;
-use crate::surfacev::vpatch::VPatch;
 use crate::surfacev::vsurface::VSurfacePatch;
 use facto_loop_miner_fac_engine::common::varea::VArea;
 use facto_loop_miner_fac_engine::common::vpoint::VPoint;
-use facto_loop_miner_fac_engine::common::vpoint_direction::VPointDirectionQ;
-use facto_loop_miner_fac_engine::game_entities::direction::FacDirectionQuarter;
 use itertools::Itertools;
 use std::cell::RefCell;
 use std::rc::Rc;
@@ -50,7 +47,7 @@ impl MineSelectBatch {
 pub const PERPENDICULAR_SCAN_WIDTH: i32 = 120;
 
 /// Input:
-///  - Raw patch list
+///  - Mines from grouped patches
 ///
 /// Output:
 ///  - Group nearby patches
@@ -59,12 +56,11 @@ pub const PERPENDICULAR_SCAN_WIDTH: i32 = 120;
 ///  - Split groups if needed because too huge creates too many possibilities later
 pub fn select_mines_and_sources(
     tunables: &PathingTunables,
-    surface: VSurfacePatch,
+    patch_groups: Vec<MineLocation>,
     maximum_mine_count_per_batch: usize,
 ) -> MineSelectBatchResult {
     let base_source = BaseSource::from_central_base(tunables).into_refcells();
 
-    let patch_groups = group_nearby_patches(surface);
     let total_patches: usize = patch_groups
         .iter()
         .map(VSurfacePatch::mine_patches_len)
@@ -81,7 +77,7 @@ pub fn select_mines_and_sources(
     // };
     // ordered_patches
 
-    let mine_batches = patches_by_cross_sign_expanding(patch_groups, &base_source, tunables);
+    let mine_batches = patches_by_cross_sign_expanding(patch_groups, &base_source);
     if mine_batches.is_empty() {
         return MineSelectBatchResult::EmptyBatch;
     }
@@ -128,58 +124,25 @@ pub fn group_nearby_patches(surface: VSurfacePatch) -> Vec<MineLocation> {
         Pixel::CrudeOil,
     ];
 
-    let patches: Vec<&VPatch> = surface
-        .get_patches()
-        .iter()
-        .filter(|patch| resources.contains(&patch.resource))
-        .collect();
-
     // group patches by nearby
-    let mut groups: Vec<Vec<&VPatch>> = Vec::new();
-    for patch in &patches {
-        // todo: this was a performance boost. Needs to re-benchmark
-        let processed_patches = groups.iter().flatten().cloned().collect_vec();
-        if processed_patches.contains(patch) {
-            // already in a group
+    let patches = surface.get_patches();
+    let mut grouped = vec![false; patches.len()];
+    let mut result = Vec::new();
+    for (patch_index, patch) in patches.iter().enumerate() {
+        if grouped[patch_index] || !resources.contains(&patch.resource) {
             continue;
         }
-        let remaining_patches = patches
-            .iter()
-            .filter(|p| !processed_patches.contains(p))
-            .cloned()
-            .collect_vec();
-
-        let mut new_group = Vec::new();
-        new_group.push(*patch);
-        recursive_near_patches(patch, &remaining_patches, &mut new_group);
-        groups.push(new_group);
-    }
-
-    // {
-    //     let mut dedupe_check = groups.iter().flatten().cloned().collect_vec();
-    //     let old = dedupe_check.len();
-    //     dedupe_check.sort();
-    //     dedupe_check.dedup();
-    //     assert_eq!(old, dedupe_check.len(), "dedupe found stuff!");
-    // }
-
-    // Merge groups
-    let mut result = Vec::new();
-    for patch_group in groups {
-        let patch_group_indexes = if patch_group.len() != 1 {
-            // trace!("Merging patch group of {:?}", patch_group);
-
-            // Externally we use the index in the VSurface Patches slice
-            patch_group
-                .iter()
-                .map(|patch| surface.get_patch_index(patch))
-                .collect()
-        } else {
-            let patch = patch_group[0];
-            // trace!("Single patch group {:?}", patch);
-            vec![surface.get_patch_index(patch)]
-        };
+        grouped[patch_index] = true;
+        let mut patch_group_indexes = vec![patch_index];
+        recursive_near_patches(
+            surface,
+            &resources,
+            patch_index,
+            &mut grouped,
+            &mut patch_group_indexes,
+        );
 
+        // Externally we use the index in the VSurface Patches slice
         if let Some(mine) = MineLocation::from_patch_indexes(surface, patch_group_indexes) {
             result.push(mine);
         }
@@ -187,23 +150,25 @@ pub fn group_nearby_patches(surface: VSurfacePatch) -> Vec<MineLocation> {
     result
 }
 
-fn recursive_near_patches<'a>(
-    needle: &VPatch,
-    patches: &[&'a VPatch],
-    total: &mut Vec<&'a VPatch>,
+fn recursive_near_patches(
+    surface: VSurfacePatch,
+    resources: &[Pixel],
+    needle_index: usize,
+    grouped: &mut [bool],
+    total: &mut Vec<usize>,
 ) {
-    for other in patches {
-        if *other == needle || total.contains(other) {
+    let patches = surface.get_patches();
+    let max_distance = TILES_PER_CHUNK as f32 * 5.0;
+    let needle_center = patches[needle_index].area.point_center();
+    for other_index in surface.patch_indexes_within(needle_center, max_distance) {
+        let other = &patches[other_index];
+        if grouped[other_index] || !resources.contains(&other.resource) {
             continue;
         }
-        if needle
-            .area
-            .point_center()
-            .distance_bird(&other.area.point_center())
-            < TILES_PER_CHUNK as f32 * 5.0
-        {
-            total.push(*other);
-            recursive_near_patches(other, patches, total);
+        if needle_center.distance_bird(&other.area.point_center()) < max_distance {
+            grouped[other_index] = true;
+            total.push(other_index);
+            recursive_near_patches(surface, resources, other_index, grouped, total);
         }
     }
 }
@@ -211,64 +176,30 @@ fn recursive_near_patches<'a>(
 fn patches_by_cross_sign_expanding(
     mut mines: Vec<MineLocation>,
     base_source: &BaseSourceRefs,
-    base_tunables: &PathingTunables,
 ) -> Vec<MineSelectBatch> {
     let bounding_area =
         VArea::from_arbitrary_points(mines.iter().flat_map(|v| v.area_min().get_corner_points()));
+    let reach = bounding_area
+        .get_corner_points()
+        .iter()
+        .map(|v| v.x().abs().max(v.y().abs()))
+        .max()
+        .unwrap();
 
-    let cross_sides: [VPointDirectionQ; 1] = [
-        // Rail::new_straight(
-        //     VPoint::new(REMOVE_RESOURCE_BASE_TILES, 0),
-        //     RailDirection::Right,
-        // )
-        VPointDirectionQ(
-            // todo: this assumes dream of both east and west building
-            VPoint::new(base_tunables.base_chunks().as_tiles_i32(), 0),
-            FacDirectionQuarter::East,
-        ),
-    ];
     let mut batches = Vec::new();
-    for cross_side in cross_sides {
-        for scan_index in (1i32..).flat_map(|i| [i, -i]) {
-            let scan_start = cross_side
-                .point()
-                // first corner
-                .move_direction_sideways_int(
-                    cross_side.direction(),
-                    scan_index * PERPENDICULAR_SCAN_WIDTH,
-                );
-            if !bounding_area.contains_point(&scan_start) {
+    // Expand all faces together, so batches stay ordered from the base outwards
+    for scan_index in 0i32.. {
+        let mut any_face_scanned = false;
+        for face in base_source.faces() {
+            let Some(search_area) =
+                face.borrow()
+                    .area_strip(scan_index, PERPENDICULAR_SCAN_WIDTH, reach)
+            else {
                 // extended past edge of surface
-                break;
-            }
-
-            let scan_end = {
-                let mut pos = scan_start;
-                // move up again to complete box height
-                // this is the only way to be generic. not a hot path though
-                for _ in 0..PERPENDICULAR_SCAN_WIDTH {
-                    let next = pos.move_direction_sideways_int(cross_side.direction(), 1);
-                    if bounding_area.contains_point(&next) {
-                        pos = next;
-                    } else {
-                        break;
-                    }
-                }
-
-                // move left to edge of surface
-                // again trying to be generic
-                loop {
-                    let next = pos.move_direction_int(cross_side.direction(), 1);
-                    if bounding_area.contains_point(&next) {
-                        pos = next;
-                    } else {
-                        break;
-                    }
-                }
-                pos
+                continue;
             };
+            any_face_scanned = true;
 
-            let search_area = VArea::from_arbitrary_points_pair(scan_start, scan_end);
             let mut found_mines: Vec<MineLocation> = mines
                 .extract_if(.., |mine| {
                     search_area.contains_point(&mine.area_min().point_center())
@@ -278,9 +209,10 @@ fn patches_by_cross_sign_expanding(
                 // might just be unlucky with small scan areas
                 continue;
             }
+            let direction = face.borrow().direction();
             found_mines.sort_by(|left, right| {
                 VPoint::sort_by_direction(
-                    *cross_side.direction(),
+                    direction,
                     left.area_min().point_top_left(),
                     right.area_min().point_top_left(),
                 )
@@ -289,18 +221,17 @@ fn patches_by_cross_sign_expanding(
             //     trace!("batch for mine {:?}", mine);
             // }
 
-            // TODO: Support multiple sides
-            let base_source_eighth = if scan_index > 0 {
-                base_source.positive_rc()
-            } else {
-                base_source.negative_rc()
-            };
-
             batches.push(MineSelectBatch {
                 mines: found_mines,
-                base_sources: base_source_eighth,
+                base_sources: face.clone(),
             });
         }
+        if !any_face_scanned {
+            break;
+        }
+    }
+    if !mines.is_empty() {
+        warn!("{} mines are not in front of any base face", mines.len());
     }
     batches
 }
@@ -1,4 +1,5 @@
 // pub mod basic;
+mod aggregation;
 mod base_source;
 mod mine_executor;
 mod mine_permutate;
@@ -7,8 +8,10 @@ mod mori;
 mod mori_cost;
 // pub mod resource_cloud;
 // pub mod shinri;
+#[cfg(feature = "opencv_re")]
 mod circleify;
 pub mod planners;
 // mod threaded_search;
 
+pub use aggregation::plan_aggregation_outposts;
 pub use mori_cost::MoriCostMode;
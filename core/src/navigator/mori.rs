@@ -1,5 +1,7 @@
 use crate::navigator::mine_executor::FailingMeta;
-use crate::navigator::mori_cost::calculate_cost_for_link;
+use crate::navigator::mori_cost::{
+    calculate_cost_for_link, obstacle_clearing_cost, soda_heuristic,
+};
 use crate::state::tuneables::MoriTunables;
 use crate::surfacev::vsurface::VSurfacePixel;
 use facto_loop_miner_common::LOCALE;
@@ -18,9 +20,11 @@ use tracing::{info, warn};
 
 /// Pathfinder v1.2, Mori Calliope💀
 ///
-/// astar powered pathfinding, now powered by fac-engine
+/// astar powered pathfinding, now powered by fac-engine.
+/// Link costs don't include the remaining distance, that's only in the heuristic
 ///
-/// Makes a dual rail + spacing, +6 straight or 90 degree turning, path of rail from start to end.
+/// Makes a dual rail + spacing, +6 straight, 90 degree turning or 45 degree shifting, path of
+/// rail from start to end.
 /// Without collisions into any point on the Surface.
 pub fn mori2_start(
     tunables: &MoriTunables,
@@ -34,11 +38,14 @@ pub fn mori2_start(
     let start_link = new_straight_link_from_vd(&endpoints.start);
     let end_link = new_straight_link_from_vd(&endpoints.end);
 
-    if !surface.is_points_free_unchecked(&end_link.area_vec()) {
+    let end_area = end_link.area_vec();
+    if !surface.is_points_free_unchecked(&end_area)
+        && obstacle_clearing_cost(surface, &end_area, &tunables.obstacles).is_none()
+    {
         // // todo: lock?
         // error!("endpoint {}", endpoints.end);
         // let new_surface = crude_dump_on_failure(surface, end_link, endpoints);
-        // new_surface.paint_pixel_colored_entire().save_to_oculante();
+        // new_surface.paint_pixel_colored_entire().save_to_sink();
         // // new_surface
         // //     .paint_pixel_colored_entire()
         // //     .save_to_file(Path::new("work/out0"))
@@ -48,7 +55,7 @@ pub fn mori2_start(
         // panic!("waste of time")
 
         let mut founds = HashSet::new();
-        for pos in end_link.area_vec() {
+        for pos in end_area {
             founds.insert(surface.get_pixel(pos));
         }
         // let founds_txt = founds.iter().map(|v| v.as_ref()).join(",");
@@ -72,7 +79,6 @@ pub fn mori2_start(
             let watch = BasicWatch::start();
             let res = successors(
                 surface,
-                &endpoints,
                 head,
                 // processor,
                 finding_limiter,
@@ -82,7 +88,7 @@ pub fn mori2_start(
             successor_sum += watch.duration();
             res
         },
-        |_p| 0,
+        |p| soda_heuristic(p, &end_link, tunables),
         |p| {
             // let watch = BasicWatch::start();
             let res = p == &end_link;
@@ -91,11 +97,12 @@ pub fn mori2_start(
             // p.start.distance_bird(&end_link.start) < 5.0
         },
         |path| {
-            // sequential compare
-            path.sort_by_key(|v| v.pos_start());
+            // sequential compare. Shifts also pass through sodas besides their own
+            let mut cells: Vec<VPoint> = path.iter().flat_map(|v| v.cells()).collect();
+            cells.sort();
             let mut i = 0;
-            while i + 1 < path.len() {
-                if path[i].pos_start() == path[i + 1].pos_start() {
+            while i + 1 < cells.len() {
+                if cells[i] == cells[i + 1] {
                     return false;
                 }
                 i += 1;
@@ -128,7 +135,7 @@ pub fn mori2_start(
     //     new_surface
     //         //.paint_pixel_graduated(watch_data.was_unfree_check)
     //         .paint_pixel_graduated(count_link_origins(&err.seen))
-    //         .save_to_oculante();
+    //         .save_to_sink();
     //     std::process::exit(0)
     // }
 
@@ -230,7 +237,6 @@ fn new_straight_link_from_vd(start: &VPointDirectionQ) -> HopeSodaLink {
 
 fn successors(
     surface: VSurfacePixel,
-    segment_points: &VSegment,
     head: &HopeSodaLink,
     finding_limiter: &VArea,
     tune: &MoriTunables,
@@ -239,34 +245,16 @@ fn successors(
     watch_data.executions += 1;
 
     let watch = BasicWatch::start();
-    let nexts = [
-        into_buildable_link(
-            surface,
-            finding_limiter,
-            head.add_straight_section(),
-            // watch_data,
-        ),
-        into_buildable_link(
-            //
-            surface,
-            finding_limiter,
-            head.add_turn90(false),
-            // watch_data,
-        ),
-        into_buildable_link(
-            //
-            surface,
-            finding_limiter,
-            head.add_turn90(true),
-            // watch_data,
-        ),
-    ];
+    let nexts: Vec<_> = next_links(head, tune)
+        .into_iter()
+        .map(|next| into_buildable_link(surface, finding_limiter, next, tune))
+        .collect();
     watch_data.nexts += watch.duration();
 
     let watch = BasicWatch::start();
-    let mut successors = Vec::with_capacity(3);
-    for next in nexts.into_iter().flatten() {
-        let cost = calculate_cost_for_link(&next, segment_points, tune);
+    let mut successors = Vec::with_capacity(nexts.len());
+    for (next, clearing_cost) in nexts.into_iter().flatten() {
+        let cost = calculate_cost_for_link(&next, tune) + clearing_cost;
         successors.push((next, cost));
     }
     watch_data.cost += watch.duration();
@@ -276,12 +264,26 @@ fn successors(
     successors
 }
 
+/// Straight, both turns, then both shifts of every allowed width
+fn next_links(head: &HopeSodaLink, tune: &MoriTunables) -> Vec<HopeSodaLink> {
+    let mut nexts = Vec::with_capacity(3 + 2 * tune.shift_max_sodas);
+    nexts.push(head.add_straight_section());
+    nexts.push(head.add_turn90(false));
+    nexts.push(head.add_turn90(true));
+    for length in 1..=tune.shift_max_sodas {
+        nexts.push(head.add_shift45(false, length));
+        nexts.push(head.add_shift45(true, length));
+    }
+    nexts
+}
+
 fn into_buildable_link(
     surface: VSurfacePixel,
     finding_limiter: &VArea,
     new_link: HopeSodaLink,
+    tune: &MoriTunables,
     // watch_data: &mut WatchData,
-) -> Option<HopeSodaLink> {
+) -> Option<(HopeSodaLink, u32)> {
     // todo: fix the limiter and just check center
     if !new_link
         .corners()
@@ -292,9 +294,11 @@ fn into_buildable_link(
     }
     // new_link.pos_start().assert_step_rail();
     let area = new_link.area_vec();
-    assert_eq!(area.len(), 104);
+    assert_eq!(area.len(), 104 * new_link.length_sodas());
     if surface.is_points_free_unchecked(&area) {
-        Some(new_link)
+        Some((new_link, 0))
+    } else if let Some(clearing_cost) = obstacle_clearing_cost(surface, &area, &tune.obstacles) {
+        Some((new_link, clearing_cost))
     } else {
         // for point in area {
         //     watch_data
@@ -316,3 +320,161 @@ pub fn count_link_origins(links: &[HopeSodaLink]) -> HashMap<VPoint, u32> {
     }
     compressed
 }
+
+#[cfg(test)]
+mod test {
+    use crate::navigator::mori::{MoriResult, mori2_start, next_links};
+    use crate::navigator::mori_cost::{calculate_cost_for_link, soda_heuristic};
+    use crate::state::tuneables::{MoriTunables, ObstaclePolicy};
+    use crate::surface::pixel::Pixel;
+    use crate::surfacev::vsurface::{VSurface, VSurfacePixelAsVs, VSurfacePixelAsVsMut};
+    use facto_loop_miner_fac_engine::common::varea::VArea;
+    use facto_loop_miner_fac_engine::common::vpoint::{VPOINT_ZERO, VPoint};
+    use facto_loop_miner_fac_engine::common::vpoint_direction::VSegment;
+    use facto_loop_miner_fac_engine::game_blocks::rail_hope_single::SECTION_POINTS_I32;
+    use facto_loop_miner_fac_engine::game_blocks::rail_hope_soda::HopeSodaLink;
+    use facto_loop_miner_fac_engine::game_entities::direction::FacDirectionQuarter;
+    use pathfinding::prelude::{astar, dijkstra};
+    use std::cell::Cell;
+    use strum::VariantArray;
+
+    /// Cheapest route without obstacles
+    fn optimal_cost(start: &HopeSodaLink, end: &HopeSodaLink, tune: &MoriTunables) -> u32 {
+        let successors = |head: &HopeSodaLink| {
+            next_links(head, tune).into_iter().map(|next| {
+                let cost = calculate_cost_for_link(&next, tune);
+                (next, cost)
+            })
+        };
+        dijkstra(start, successors, |head| head == end).unwrap().1
+    }
+
+    fn soda_at(x: i32, y: i32, direction: FacDirectionQuarter) -> HopeSodaLink {
+        let center = VPoint::new(x * SECTION_POINTS_I32, y * SECTION_POINTS_I32);
+        HopeSodaLink::new_soda_straight(center, direction)
+    }
+
+    #[test]
+    fn test_heuristic_admissible() {
+        let cheap_turns = MoriTunables {
+            straight_cost_unit: 3,
+            turn_cost_unit: 1,
+            ..MoriTunables::default()
+        };
+        let cheap_shifts = MoriTunables {
+            shift_cost_unit: 1,
+            shift_max_sodas: 3,
+            ..MoriTunables::default()
+        };
+        let no_shifts = MoriTunables {
+            shift_max_sodas: 0,
+            ..MoriTunables::default()
+        };
+        for tune in [
+            MoriTunables::default(),
+            cheap_turns,
+            cheap_shifts,
+            no_shifts,
+        ] {
+            let end = soda_at(0, 0, FacDirectionQuarter::East);
+            for x in -3..=3 {
+                for y in -3..=3 {
+                    for direction in FacDirectionQuarter::VARIANTS {
+                        let start = soda_at(x, y, *direction);
+                        let estimate = soda_heuristic(&start, &end, &tune);
+                        let optimal = optimal_cost(&start, &end, &tune);
+                        assert!(estimate <= optimal, "{start:?} {estimate} > {optimal}");
+                    }
+                }
+            }
+        }
+
+        // straight ahead is exact
+        let tune = MoriTunables::default();
+        let start = soda_at(-3, 0, FacDirectionQuarter::East);
+        let end = soda_at(0, 0, FacDirectionQuarter::East);
+        assert_eq!(
+            soda_heuristic(&start, &end, &tune),
+            optimal_cost(&start, &end, &tune)
+        );
+    }
+
+    #[test]
+    fn test_heuristic_expands_less() {
+        let tune = MoriTunables::default();
+        let start = soda_at(-4, 2, FacDirectionQuarter::East);
+        let end = soda_at(4, -3, FacDirectionQuarter::North);
+        let search = |heuristic: &dyn Fn(&HopeSodaLink) -> u32| {
+            let expanded = Cell::new(0);
+            let successors = |head: &HopeSodaLink| {
+                expanded.set(expanded.get() + 1);
+                next_links(head, &tune).into_iter().map(|next| {
+                    let cost = calculate_cost_for_link(&next, &tune);
+                    (next, cost)
+                })
+            };
+            let (_, cost) = astar(&start, successors, heuristic, |head| head == &end).unwrap();
+            (cost, expanded.get())
+        };
+
+        let (blind_cost, blind_expanded) = search(&|_p| 0);
+        let (cost, expanded) = search(&|p| soda_heuristic(p, &end, &tune));
+        assert_eq!(cost, blind_cost);
+        assert!(
+            expanded < blind_expanded,
+            "expanded {expanded} vs {blind_expanded} without heuristic"
+        );
+    }
+
+    #[test]
+    fn test_route_is_optimal() {
+        let tune = MoriTunables::default();
+        let surface = VSurface::new(400);
+        let start = soda_at(-4, 2, FacDirectionQuarter::East);
+        let end = soda_at(4, -3, FacDirectionQuarter::North);
+        let endpoints = VSegment {
+            start: start.my_q(),
+            end: end.my_q(),
+        };
+        let limiter = VArea::from_radius(VPOINT_ZERO, 350);
+
+        let MoriResult::Route { cost, .. } =
+            mori2_start(&tune, surface.pixels(), endpoints, &limiter)
+        else {
+            panic!("no route");
+        };
+        assert_eq!(cost, optimal_cost(&start, &end, &tune));
+    }
+
+    #[test]
+    fn test_water_landfill() {
+        let mut surface = VSurface::new(400);
+        let lake = VArea::from_arbitrary_points_pair(VPoint::new(0, -360), VPoint::new(9, 360));
+        surface
+            .pixels_mut()
+            .change_pixels(lake.get_points())
+            .stomp(Pixel::Water);
+        let start = soda_at(-4, 0, FacDirectionQuarter::East);
+        let end = soda_at(4, 0, FacDirectionQuarter::East);
+        let endpoints = VSegment {
+            start: start.my_q(),
+            end: end.my_q(),
+        };
+        let limiter = VArea::from_radius(VPOINT_ZERO, 350);
+
+        let blocked = MoriTunables::default();
+        assert!(matches!(
+            mori2_start(&blocked, surface.pixels(), endpoints.clone(), &limiter),
+            MoriResult::FailingDebug { .. }
+        ));
+
+        let mut landfill = MoriTunables::default();
+        landfill.obstacles.water = ObstaclePolicy::Landfill { cost: 30 };
+        let MoriResult::Route { cost, .. } =
+            mori2_start(&landfill, surface.pixels(), endpoints, &limiter)
+        else {
+            panic!("no route over landfill");
+        };
+        assert!(cost > optimal_cost(&start, &end, &landfill));
+    }
+}
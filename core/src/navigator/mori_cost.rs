@@ -1,41 +1,39 @@
-use crate::state::tuneables::MoriTunables;
-use facto_loop_miner_fac_engine::common::vpoint_direction::{VPointDirectionQ, VSegment};
+use crate::state::tuneables::{MoriTunables, ObstaclePolicy, ObstacleTunables};
+use crate::surface::pixel::Pixel;
+use crate::surfacev::vsurface::VSurfacePixel;
+use facto_loop_miner_fac_engine::common::vpoint::VPoint;
+use facto_loop_miner_fac_engine::common::vpoint_direction::VPointDirectionQ;
 use facto_loop_miner_fac_engine::game_blocks::rail_hope::RailHopeLink;
-use facto_loop_miner_fac_engine::game_blocks::rail_hope_single::HopeLinkType;
+use facto_loop_miner_fac_engine::game_blocks::rail_hope_single::{
+    HopeLinkType, SECTION_POINTS_I32,
+};
+use facto_loop_miner_fac_engine::game_blocks::rail_hope_soda::HopeSodaLink;
+use facto_loop_miner_fac_engine::game_entities::direction::FacDirectionQuarter;
 use serde::{Deserialize, Serialize};
 // const ANTI_WRONG_BIAS_EFFECT: f32 = 10f32;
 // const RESOURCE_BIAS_EFFECT: f32 = 20f32;
 
+/// Enough to cover every direction from any heading and still end at any heading
+const MAX_HEURISTIC_TURNS: u32 = 5;
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum MoriCostMode {
+    /// Every link costs the same
     Dummy,
+    /// Link length only
     DistanceManhattanOnly,
+    /// Link length weighted by type, plus the axis penalty
     Complete,
 }
 
-pub fn calculate_cost_for_link(
-    next: &impl RailHopeLink,
-    segment_points: &VSegment,
-    tune: &MoriTunables,
-) -> u32 {
-    let result = match tune.cost_mode {
-        MoriCostMode::Dummy => 5,
-        MoriCostMode::DistanceManhattanOnly => {
-            distance_by_basic_manhattan(next, &segment_points.end)
-        }
-        MoriCostMode::Complete => {
-            let cost = distance_by_punish_turns(next, &segment_points.end, tune);
-            let bias = axis_bias(next, tune);
-            //cost
-            cost + ((cost as f32 * bias) as u32)
-        } // MoriCostMode::Complete => into_end_landing_bias(
-          //     next,
-          //     start,
-          //     end,
-          //     distance_by_punish_turns(parents_compare, next, end),
-          // ),
-    };
-    result
+/// Cost of adding one link. Only depends on the link, the remaining distance is left
+/// to `soda_heuristic`
+pub fn calculate_cost_for_link(next: &impl RailHopeLink, tune: &MoriTunables) -> u32 {
+    let base = link_base_cost(&next.link_type(), tune);
+    match tune.cost_mode {
+        MoriCostMode::Dummy | MoriCostMode::DistanceManhattanOnly => base,
+        MoriCostMode::Complete => base + axis_penalty(next, base, tune),
+    }
 
     // // block it closer to base
     // let anti_wrong = if distance < 400.0 {
@@ -63,22 +61,46 @@ pub fn calculate_cost_for_link(
     //
 }
 
-fn distance_by_basic_manhattan(next: &impl RailHopeLink, end: &VPointDirectionQ) -> u32 {
-    next.pos_next().distance_to(&end.0)
+/// Extra cost to clear obstacles from an occupied area, None if anything is blocking
+pub fn obstacle_clearing_cost(
+    surface: VSurfacePixel,
+    area: &[VPoint],
+    obstacles: &ObstacleTunables,
+) -> Option<u32> {
+    let mut total = 0;
+    for point in area {
+        let pixel = surface.get_pixel(point);
+        if pixel == Pixel::Empty {
+            continue;
+        }
+        match obstacles.policy(pixel)? {
+            ObstaclePolicy::Blocked => return None,
+            ObstaclePolicy::Deconstruct { cost } | ObstaclePolicy::Landfill { cost } => {
+                total += cost
+            }
+            ObstaclePolicy::Ignore => {}
+        }
+    }
+    Some(total)
 }
 
-fn distance_by_punish_turns(
-    next: &impl RailHopeLink,
-    end: &VPointDirectionQ,
-    tune: &MoriTunables,
-) -> u32 {
-    let base_distance = distance_by_basic_manhattan(next, end);
-
-    let link_cost: u32 = match next.link_type() {
-        HopeLinkType::Straight { .. } => tune.straight_cost_unit,
-        HopeLinkType::Turn90 { .. } => tune.turn_cost_unit,
-        HopeLinkType::Shift45 { .. } => todo!("shift45"),
+/// Lower bound of `calculate_cost_for_link`, penalties and clearing are never negative
+fn link_base_cost(link_type: &HopeLinkType, tune: &MoriTunables) -> u32 {
+    let length = SECTION_POINTS_I32 as u32;
+    // soda shift length is sideways, forward is one more
+    let sodas = match link_type {
+        HopeLinkType::Shift45 { length, .. } => *length as u32 + 1,
+        HopeLinkType::Straight { .. } | HopeLinkType::Turn90 { .. } => 1,
     };
+    match tune.cost_mode {
+        MoriCostMode::Dummy => 5,
+        MoriCostMode::DistanceManhattanOnly => sodas * length,
+        MoriCostMode::Complete => match link_type {
+            HopeLinkType::Straight { .. } => length * tune.straight_cost_unit,
+            HopeLinkType::Turn90 { .. } => length * tune.turn_cost_unit,
+            HopeLinkType::Shift45 { .. } => sodas * length * tune.shift_cost_unit,
+        },
+    }
 
     // let num_recent_turns: u32 = parents
     //     .iter()
@@ -88,15 +110,159 @@ fn distance_by_punish_turns(
     //     })
     //     .sum();
     // let turn_punish = num_recent_turns * tune.multi_turn_cost_unit;
+}
+
+/// `axis_cost_unit` percent of the link cost at `crop_radius` from y = 0
+fn axis_penalty(next: &impl RailHopeLink, base: u32, tune: &MoriTunables) -> u32 {
+    let y_abs = next.pos_next().y().unsigned_abs() as u64;
+    let scaled = base as u64 * tune.axis_cost_unit as u64 * y_abs;
+    (scaled / (100 * tune.crop_radius as u64)) as u32
+}
+
+/// Admissible A* estimate on the soda grid.
+///
+/// Straights and turns move one soda in their source direction and the route must arrive
+/// at `end` with a straight. So every heading on the way costs at least one link, and
+/// headings against the remaining distance cost a link there and back
+pub fn soda_heuristic(head: &HopeSodaLink, end: &HopeSodaLink, tune: &MoriTunables) -> u32 {
+    if head == end {
+        return 0;
+    }
+    let VPointDirectionQ(head_center, heading) = head.my_q();
+    let VPointDirectionQ(end_center, end_direction) = end.my_q();
+    let delta = end_center - head_center;
+    let delta_x = delta.x() / SECTION_POINTS_I32;
+    let delta_y = delta.y() / SECTION_POINTS_I32;
 
-    base_distance * link_cost //+ turn_punish
+    let straight = link_base_cost(&HopeLinkType::Straight { length: 0 }, tune);
+    let turn = link_base_cost(&HopeLinkType::Turn90 { clockwise: true }, tune);
+    let mut best = u32::MAX;
+    let mut fewest_links = u32::MAX;
+    walk_headings(
+        heading,
+        end_direction,
+        direction_bit(heading),
+        0,
+        &mut |visited, turns| {
+            let Some(links) = links_lower_bound(visited, delta_x, delta_y) else {
+                return;
+            };
+            fewest_links = fewest_links.min(links);
+            let straights = links.saturating_sub(turns).max(1);
+            best = best.min(turns * turn + straights * straight);
+        },
+    );
+    if turn < straight {
+        // extra turns would be cheaper than the straights they replace
+        best = best.min((fewest_links - 1) * turn + straight);
+    }
+    if tune.shift_max_sodas > 0 {
+        let sodas = delta_x.unsigned_abs() + delta_y.unsigned_abs();
+        best = best.min(shift_route_lower_bound(heading, end_direction, sodas, tune));
+    }
+    best
+}
+
+/// Shifts move sideways without turning, so the walks above don't bound routes using them.
+/// Those still need the turns to `end_direction`, one shift, and the rest of the sodas at
+/// the cheapest cost per soda of any link
+fn shift_route_lower_bound(
+    heading: FacDirectionQuarter,
+    end_direction: FacDirectionQuarter,
+    sodas: u32,
+    tune: &MoriTunables,
+) -> u32 {
+    let turns = if heading == end_direction {
+        0
+    } else if heading.rotate_flip() == end_direction {
+        2
+    } else {
+        1
+    };
+    let straight = link_base_cost(&HopeLinkType::Straight { length: 0 }, tune);
+    let turn = link_base_cost(&HopeLinkType::Turn90 { clockwise: true }, tune);
+    let shift_cost = |length: usize| {
+        let shift = HopeLinkType::Shift45 {
+            clockwise: true,
+            length,
+        };
+        link_base_cost(&shift, tune)
+    };
+    let shift_sodas = |length: usize| 2 * length as u32 + 1;
+
+    // (cost, sodas moved)
+    let mut rate = (straight.min(turn), 1);
+    for length in 1..=tune.shift_max_sodas {
+        let (cost, moved) = (shift_cost(length), shift_sodas(length));
+        if cost * rate.1 < rate.0 * moved {
+            rate = (cost, moved);
+        }
+    }
+
+    let remaining = sodas.saturating_sub(turns);
+    let cheapest_shift = (1..=tune.shift_max_sodas)
+        .map(|length| {
+            let rest = remaining.saturating_sub(shift_sodas(length));
+            shift_cost(length) + rest * rate.0 / rate.1
+        })
+        .min()
+        .unwrap();
+    turns * turn + cheapest_shift
+}
+
+/// Every sequence of 90 degree heading changes that ends at `end_direction`
+fn walk_headings(
+    heading: FacDirectionQuarter,
+    end_direction: FacDirectionQuarter,
+    visited: u8,
+    turns: u32,
+    found: &mut impl FnMut(u8, u32),
+) {
+    if heading == end_direction {
+        found(visited, turns);
+    }
+    if turns == MAX_HEURISTIC_TURNS {
+        return;
+    }
+    for clockwise in [true, false] {
+        let next = heading.rotate_clockwise(clockwise);
+        walk_headings(
+            next,
+            end_direction,
+            visited | direction_bit(next),
+            turns + 1,
+            found,
+        );
+    }
+}
+
+/// Fewest links to move the delta sodas using every `visited` heading at least once.
+/// None if a needed heading isn't visited
+fn links_lower_bound(visited: u8, delta_x: i32, delta_y: i32) -> Option<u32> {
+    let axis = |delta: i32, positive: FacDirectionQuarter, negative: FacDirectionQuarter| {
+        let has_positive = visited & direction_bit(positive) != 0;
+        let has_negative = visited & direction_bit(negative) != 0;
+        match delta.signum() {
+            1 => has_positive.then(|| delta.unsigned_abs() + 2 * has_negative as u32),
+            -1 => has_negative.then(|| delta.unsigned_abs() + 2 * has_positive as u32),
+            _ => Some(if has_positive || has_negative { 2 } else { 0 }),
+        }
+    };
+    Some(
+        axis(
+            delta_x,
+            FacDirectionQuarter::East,
+            FacDirectionQuarter::West,
+        )? + axis(
+            delta_y,
+            FacDirectionQuarter::South,
+            FacDirectionQuarter::North,
+        )?,
+    )
 }
 
-fn axis_bias(next: &impl RailHopeLink, tune: &MoriTunables) -> f32 {
-    let y_abs = next.pos_next().y().abs();
-    let total = tune.crop_radius;
-    let percent = y_abs as f32 / total as f32;
-    percent
+fn direction_bit(direction: FacDirectionQuarter) -> u8 {
+    1 << direction as u8
 }
 
 // fn into_end_landing_bias(next: &Rail, start: &Rail, end: &VPoint, base_distance: f32) -> f32 {
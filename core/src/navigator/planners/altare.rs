@@ -1,5 +1,5 @@
-use crate::navigator::base_source::{BaseSource, BaseSourceEighth};
-use crate::navigator::circleify::draw_circle_around;
+use crate::navigator::aggregation::mines_to_base;
+use crate::navigator::base_source::{BaseSource, BaseSourceEighth, BaseSourceRefs};
 use crate::navigator::mine_executor::{
     ExecuteFlags, ExecutorResult, execute_route_batch_clone_prep,
 };
@@ -12,40 +12,69 @@ use crate::navigator::planners::PathingTunables;
 use crate::navigator::planners::common::{
     debug_draw_failing_mines, debug_failing, draw_prep_mines,
 };
+use crate::navigator::planners::planner::{Planner, PlannerOutcome};
 use crate::surface::pixel::Pixel;
-use crate::surfacev::mine::MineLocation;
+use crate::surfacev::mine::{MineLocation, MinePathKind};
 use std::cell::RefCell;
 
 use crate::surfacev::vsurface::{
-    VSurfaceNavMut, VSurfacePatchAsVs, VSurfacePixel, VSurfacePixelAsVs, VSurfacePixelAsVsMut,
-    VSurfaceRail, VSurfaceRailAsVs, VSurfaceRailAsVsMut, VSurfaceRailMut,
+    RailCheckpoint, VSurfaceNavMut, VSurfacePatchAsVs, VSurfacePixel, VSurfacePixelAsVs,
+    VSurfacePixelAsVsMut, VSurfaceRail, VSurfaceRailAsVs, VSurfaceRailAsVsMut, VSurfaceRailMut,
 };
-use facto_loop_miner_fac_engine::common::varea::VArea;
 use facto_loop_miner_fac_engine::common::vpoint::VPoint;
 use facto_loop_miner_fac_engine::common::vpoint_direction::{VPointDirectionQ, VSegment};
-use facto_loop_miner_fac_engine::game_blocks::rail_hope::RailHopeLink;
 use facto_loop_miner_fac_engine::game_blocks::rail_hope_single::SECTION_POINTS_I32;
 use facto_loop_miner_fac_engine::game_entities::direction::FacDirectionQuarter;
 use itertools::Itertools;
 use simd_json::prelude::ArrayTrait;
-use std::collections::HashSet;
 use std::ops::ControlFlow;
 use std::rc::Rc;
 use tracing::{error, info, trace, warn};
 
 const BATCH_SIZE_MAX: usize = 3;
+/// Older rails are final, bounding the pixel journal on large maps
+const ROLLBACK_MAX_RAILS: usize = 64;
+
+pub struct AltarePlanner;
+
+impl Planner for AltarePlanner {
+    fn name(&self) -> &'static str {
+        "altare"
+    }
+
+    fn plan(&self, tunables: &PathingTunables, surface: &mut VSurfaceNavMut) -> PlannerOutcome {
+        start_altare_planner(tunables, surface)
+    }
+}
 
 /// Planner v2 "Regis Altare 🎇"
 ///
 /// Pathfinding with medium-difficulty backtracking.
 /// because v0 Mori and v1 Ruze Planner can mask valid routes
-pub fn start_altare_planner(tunables: &PathingTunables, surface: &mut VSurfaceNavMut) {
+pub fn start_altare_planner(
+    tunables: &PathingTunables,
+    surface: &mut VSurfaceNavMut,
+) -> PlannerOutcome {
     Quester::init(tunables, surface).start()
 }
 
-fn remove_bad_mines(surface: VSurfacePixel, all_mine_locations: &mut Vec<MineLocation>) {
+fn remove_bad_mines(
+    surface: VSurfacePixel,
+    base_source_faces: &[Rc<RefCell<BaseSourceEighth>>],
+    all_mine_locations: &mut Vec<MineLocation>,
+) {
+    let radius = surface.get_radius_i32();
     all_mine_locations.retain_mut(|mine| {
-        mine.revalidate_endpoints_after_no_touch(surface);
+        // the face that will scan this mine
+        let center = mine.area_min().point_center();
+        let Some(face) = base_source_faces.iter().find(|face| {
+            face.borrow()
+                .is_in_area_strips(&center, PERPENDICULAR_SCAN_WIDTH, radius)
+        }) else {
+            trace!("removing mine outside every face {mine:?}");
+            return false;
+        };
+        mine.revalidate_endpoints_after_no_touch(surface, &face.borrow());
         // !mine.endpoints().is_empty()
         if mine.destinations().next().is_none() {
             trace!("removing empty mine {mine:?}");
@@ -60,33 +89,42 @@ fn remove_bad_mines(surface: VSurfacePixel, all_mine_locations: &mut Vec<MineLoc
 struct Quester<'t, 'sr, 's> {
     surface: &'sr mut VSurfaceNavMut<'s>,
     mines_remain: Vec<MineLocation>,
-    base_source_positive: Rc<RefCell<BaseSourceEighth>>,
-    origin_base: VPointDirectionQ,
+    /// Journal position before each rail from `first_rollback_rail` was added
+    rail_checkpoints: Vec<RailCheckpoint>,
+    /// Rails before this are final, from previous faces, too old or planned before Altare
+    first_rollback_rail: usize,
+    base_source_faces: BaseSourceRefs,
+    face_index: usize,
+    /// Face currently being scanned
+    base_source: Rc<RefCell<BaseSourceEighth>>,
     origin_index: i32,
-    origin_sign_pos: bool,
     is_prev_retry: bool,
     tunables: &'t PathingTunables,
 }
 
 impl<'t, 'sr, 's> Quester<'t, 'sr, 's> {
     fn init(tunables: &'t PathingTunables, surface: &'sr mut VSurfaceNavMut<'s>) -> Self {
-        let base_source = BaseSource::from_central_base(tunables).into_refcells();
-        let base_source_positive = base_source.positive_rc();
+        let base_source_faces = BaseSource::from_central_base(tunables).into_refcells();
+        let base_source = base_source_faces.faces()[0].clone();
 
-        let mines_remain = group_nearby_patches(surface.patches());
+        let mines_remain = mines_to_base(surface.rails(), group_nearby_patches(surface.patches()));
         draw_prep_mines(
             &mut surface.pixels_mut(),
             &mines_remain,
-            &base_source_positive,
+            base_source_faces.faces(),
         );
 
+        // aggregation feeders are planned before and can't be rolled back
+        let first_rollback_rail = surface.rails().get_mine_paths().len();
         Quester {
             surface,
             mines_remain,
-            base_source_positive,
-            origin_base: VPointDirectionQ(VPoint::new(0, 0), FacDirectionQuarter::East),
+            rail_checkpoints: Vec::new(),
+            first_rollback_rail,
+            base_source_faces,
+            face_index: 0,
+            base_source,
             origin_index: 0,
-            origin_sign_pos: true,
             is_prev_retry: false,
             tunables,
         }
@@ -101,13 +139,15 @@ impl<'t, 'sr, 's> Quester<'t, 'sr, 's> {
     //     if let ControlFlow::Break(()) = self.new_patches_in_scan_area(patches) {}
     // }
 
-    fn start(&mut self) {
+    fn start(&mut self) -> PlannerOutcome {
         let mut limiter_counter = 0;
-        loop {
+        let outcome = loop {
             match self.scan_patches() {
-                QuesterScanResult::YAxisEnding => {
-                    info!("end of processing");
-                    break;
+                QuesterScanResult::FaceEnding => {
+                    if !self.next_face() {
+                        info!("end of processing");
+                        break PlannerOutcome::Complete;
+                    }
                 }
                 QuesterScanResult::NoPatchesInScan => {
                     self.origin_index += 1;
@@ -116,50 +156,79 @@ impl<'t, 'sr, 's> Quester<'t, 'sr, 's> {
                 QuesterScanResult::NewPatchesInScanArea { selected_mines } => {
                     if limiter_counter >= 99999 {
                         self.debug_iteration(limiter_counter);
-                        break;
+                        break PlannerOutcome::Stopped {
+                            reason: format!("limiter {limiter_counter}"),
+                        };
                     }
                     limiter_counter += 1;
                     if let ControlFlow::Break(()) = self.new_patches_in_scan_area(selected_mines) {
-                        break;
+                        break PlannerOutcome::Stopped {
+                            reason: format!(
+                                "failed at face {} scan index {}",
+                                self.base_source.borrow().direction(),
+                                self.origin_index
+                            ),
+                        };
                     }
                 }
             }
-        }
+        };
+        // rails are final, drop the undo history
+        self.rail_checkpoints.clear();
+        self.surface.pixels_mut().commit();
 
         info!("post save to gif buffering");
         for _ in 0..4 {
             self.surface
                 .pixels()
                 .paint_pixel_colored_zoomed()
-                .save_to_oculante();
+                .save_to_sink(self.tunables.paint_sink());
         }
+        outcome
     }
 
-    fn scan_patches(&mut self) -> QuesterScanResult {
-        let scan_sign = if self.origin_sign_pos { 1 } else { -1 };
-        let scan_start = self.origin_base.point().move_direction_sideways_int(
-            self.origin_base.direction(),
-            PERPENDICULAR_SCAN_WIDTH * self.origin_index * scan_sign,
+    /// Continue on the next face of the base, false when all are done
+    fn next_face(&mut self) -> bool {
+        self.face_index += 1;
+        let Some(face) = self.base_source_faces.faces().get(self.face_index) else {
+            return false;
+        };
+        self.base_source = face.clone();
+        self.origin_index = 0;
+        // previous faces sources can't be undone from this face
+        self.first_rollback_rail += self.rail_checkpoints.len();
+        self.rail_checkpoints.clear();
+        self.surface.pixels_mut().commit();
+        info!(
+            "continuing on {} face after {} rails",
+            self.base_source.borrow().direction(),
+            self.first_rollback_rail
         );
-        let scan_end = {
-            let mut pos = scan_start.move_direction_sideways_int(
-                self.origin_base.direction(),
-                PERPENDICULAR_SCAN_WIDTH,
-            );
-            if !pos.is_within_center_radius(self.surface.pixels().get_radius()) {
-                return QuesterScanResult::YAxisEnding;
-            }
-            loop {
-                let next = pos.move_direction_int(self.origin_base.direction(), 1);
-                if pos.is_within_center_radius(self.surface.pixels().get_radius()) {
-                    pos = next;
-                } else {
-                    break;
-                }
-            }
-            pos
+        true
+    }
+
+    fn push_rail_checkpoint(&mut self, checkpoint: RailCheckpoint) {
+        self.rail_checkpoints.push(checkpoint);
+        let excess = self
+            .rail_checkpoints
+            .len()
+            .saturating_sub(ROLLBACK_MAX_RAILS);
+        if excess > 0 {
+            let oldest_kept = self.rail_checkpoints[excess];
+            self.surface.rails_mut().commit_before(oldest_kept);
+            self.rail_checkpoints.drain(..excess);
+            self.first_rollback_rail += excess;
+        }
+    }
+
+    fn scan_patches(&mut self) -> QuesterScanResult {
+        let Some(scan_area) = self.base_source.borrow().area_strip(
+            self.origin_index,
+            PERPENDICULAR_SCAN_WIDTH,
+            self.surface.pixels().get_radius_i32(),
+        ) else {
+            return QuesterScanResult::FaceEnding;
         };
-        let scan_area = VArea::from_arbitrary_points_pair(&scan_start, &scan_end);
 
         let already_pathed_mines: Vec<&MineLocation> = self
             .surface
@@ -206,7 +275,7 @@ impl<'t, 'sr, 's> Quester<'t, 'sr, 's> {
         // better = 28, 30, 32
         info!("limiter {limiter_counter}");
         // break;
-        let base_source = self.base_source_positive.borrow_mut().next().unwrap();
+        let base_source = self.base_source.borrow_mut().next().unwrap();
         let start = base_source.origin;
         let end = VPointDirectionQ(
             VPoint::new(SECTION_POINTS_I32 * 100, SECTION_POINTS_I32 * 100),
@@ -214,13 +283,10 @@ impl<'t, 'sr, 's> Quester<'t, 'sr, 's> {
         );
         let surface = self.surface.pixels();
 
-        let fixed_radius = surface.get_radius_i32();
-        let fixed_finding_limiter = VArea::from_arbitrary_points_pair(
-            VPoint::new(0, -fixed_radius),
-            // Must give spacing from Edge, because hope_link.area() can extend past it.
-            // range checks are disabled for theoretical performance
-            VPoint::new(fixed_radius, fixed_radius),
-        );
+        let fixed_finding_limiter = self
+            .base_source
+            .borrow()
+            .finding_limiter(surface.get_radius_i32());
 
         let result = mori2_start(
             self.tunables.mori(),
@@ -233,7 +299,7 @@ impl<'t, 'sr, 's> Quester<'t, 'sr, 's> {
         };
         surface
             .paint_pixel_graduated(count_link_origins(&err.seen))
-            .save_to_oculante();
+            .save_to_sink(self.tunables.paint_sink());
     }
 
     fn new_patches_in_scan_area(&mut self, selected_mines: Vec<usize>) -> ControlFlow<()> {
@@ -245,7 +311,7 @@ impl<'t, 'sr, 's> Quester<'t, 'sr, 's> {
         let possible_routes = get_possible_routes_for_batch(
             self.surface.pixels(),
             MineSelectBatch {
-                base_sources: self.base_source_positive.clone(),
+                base_sources: self.base_source.clone(),
                 mines,
             },
         );
@@ -257,16 +323,14 @@ impl<'t, 'sr, 's> Quester<'t, 'sr, 's> {
     fn fill_queue(&mut self, mut selected_mines: Vec<usize>) -> Vec<MineLocation> {
         let mut mines: Vec<MineLocation> = Vec::new();
         for _ in 0..BATCH_SIZE_MAX.saturating_sub(1) {
-            if let Some((mine, removed_points)) = self.surface.rails_mut().remove_mine_path_pop() {
-                trace!("batch pop from mine {BATCH_SIZE_MAX}");
-                MineLocation::restore_area_buffered(
-                    &[&mine.location],
-                    &mut self.surface.pixels_mut(),
-                    removed_points,
-                );
-                mines.push(mine.location);
-                self.base_source_positive.borrow_mut().undo_one();
-            }
+            let Some(checkpoint) = self.rail_checkpoints.pop() else {
+                break;
+            };
+            trace!("batch pop from mine {BATCH_SIZE_MAX}");
+            let mut removed = self.surface.rails_mut().rollback_to(checkpoint);
+            assert_eq!(removed.len(), 1);
+            mines.push(removed.remove(0).location);
+            self.base_source.borrow_mut().undo_one();
         }
         selected_mines.sort();
         while mines.len() != BATCH_SIZE_MAX {
@@ -287,23 +351,25 @@ impl<'t, 'sr, 's> Quester<'t, 'sr, 's> {
         ) {
             ExecutorResult::Success { paths, routes } => {
                 self.is_prev_retry = false;
-                self.base_source_positive
+                self.base_source
                     .borrow_mut()
                     .advance_by(paths.len())
                     .unwrap();
                 // routes.last().unwrap().location.draw_area_buffered(surface);
                 for path in paths {
+                    let checkpoint = self.surface.rails_mut().begin();
+                    self.push_rail_checkpoint(checkpoint);
                     self.surface.rails_mut().add_mine_path(path);
                 }
                 self.surface
                     .pixels()
                     .paint_pixel_colored_zoomed()
-                    .save_to_oculante();
+                    .save_to_sink(self.tunables.paint_sink());
                 ControlFlow::Continue(())
             }
             ExecutorResult::Failure { meta, seen_mines } => {
                 // || is_prev_retry todo
-                if self.surface.rails().get_mine_paths().is_empty() {
+                if self.rail_checkpoints.is_empty() {
                     error!("failed to pathfind! but no rollback after another rollback");
                     debug_failing(&mut self.surface.rails_mut(), meta);
                     ControlFlow::Break(())
@@ -321,7 +387,7 @@ impl<'t, 'sr, 's> Quester<'t, 'sr, 's> {
                         self.surface
                             .pixels()
                             .paint_pixel_colored_zoomed()
-                            .save_to_oculante();
+                            .save_to_sink(self.tunables.paint_sink());
                         error!("combination of {} mines cannot be found", seen_mines.len());
                         return ControlFlow::Break(());
                     }
@@ -353,28 +419,29 @@ impl<'t, 'sr, 's> Quester<'t, 'sr, 's> {
 
                     let nearest_rail =
                         detect_nearby_rails_as_index(self.surface.rails(), &never_mined);
-                    rollback_and_reapply(
+                    let Some(nearest_checkpoint) =
+                        nearest_rail.checked_sub(self.first_rollback_rail)
+                    else {
+                        error!("nearest rail {nearest_rail} is final");
+                        return ControlFlow::Break(());
+                    };
+                    let new_checkpoint = rollback_and_reapply(
                         &mut self.surface.rails_mut(),
+                        &mut self.rail_checkpoints,
                         self.tunables,
-                        nearest_rail,
+                        nearest_checkpoint,
                         never_mined,
-                        &mut self.base_source_positive.borrow_mut(),
-                        &self.mines_remain,
+                        &mut self.base_source.borrow_mut(),
                     );
+                    self.push_rail_checkpoint(new_checkpoint);
 
                     self.surface
                         .pixels()
                         .paint_pixel_colored_zoomed()
-                        .save_to_oculante();
+                        .save_to_sink(self.tunables.paint_sink());
                     // we may took another attempt
 
-                    let scan_sign = if self.origin_sign_pos { 1 } else { -1 };
-                    self.origin_index -= match self.origin_index.unsigned_abs() {
-                        3.. => scan_sign * 3,
-                        2 => scan_sign * 2,
-                        1 => scan_sign,
-                        0 => 0,
-                    };
+                    self.origin_index -= self.origin_index.min(3);
 
                     if self.origin_index > 1 {
                         self.origin_index -= 1;
@@ -389,84 +456,39 @@ impl<'t, 'sr, 's> Quester<'t, 'sr, 's> {
     }
 }
 
+/// Nearest rail from a base source, feeders into aggregation outposts are never the cause
 fn detect_nearby_rails_as_index(surface: VSurfaceRail, mine_location: &MineLocation) -> usize {
-    let origin = mine_location
-        .area_min()
-        .point_center()
-        .move_round_even_down();
-    origin.assert_even_position();
-
-    let mut closest_rail = None;
-    let mut seen_points = HashSet::new();
-    for depth in 2.. {
-        let mut stop_after = false;
-        trace!("circling origin {origin} depth {depth}");
-        for cursor in draw_circle_around(&origin, depth * 200) {
-            if !cursor.is_even() || surface.pixels().is_point_out_of_bounds(&cursor) {
-                continue;
-            }
-
-            if seen_points.contains(&cursor) {
-                continue;
-            }
-            seen_points.insert(cursor);
-
-            let distance = origin.distance_bird(&cursor).abs();
-            match surface.pixels().get_pixel(cursor) {
-                Pixel::Empty | Pixel::MineNoTouch | Pixel::Highlighter => {
-                    // the vast expanse...
-                }
-                Pixel::Rail => {
-                    closest_rail = match closest_rail {
-                        None => {
-                            trace!("found rail at {distance}");
-                            Some((cursor, distance))
-                        }
-                        Some((prev_cursor, prev_distance)) if distance < prev_distance => {
-                            trace!("found rail at {distance} better than {prev_distance}");
-                            Some((cursor, distance))
-                        }
-                        Some(good) => Some(good),
-                    };
-                    stop_after = true;
-                }
-                pixel if Pixel::is_resource(&pixel) => {
-                    // ignore resources
-                }
-                pixel => {
-                    // resource buffer area probably
-                    trace!("hit limit at depth {depth} at {pixel:?}");
-                    stop_after = true;
-                }
-            }
-        }
-        if stop_after {
-            break;
-        }
-    }
-    let (closest_rail, _) = closest_rail.unwrap();
-
+    let origin = mine_location.area_min().point_center();
     surface
-        .get_mine_paths()
-        .iter()
-        .position(|p| {
-            p.links
-                .iter()
-                .any(|link| link.area_vec().contains(&closest_rail))
+        .nearest_mine_path_index_where(origin, |mine_path| {
+            !matches!(mine_path.kind, MinePathKind::MineToAggregator { .. })
         })
-        .unwrap_or_else(|| panic!("No rail found at {closest_rail}"))
+        .unwrap_or_else(|| panic!("No rail found near {origin}"))
 }
 
+/// Replace the rail at `rail_checkpoints[checkpoint_index]` and all after it with `new_mine`,
+/// returning the checkpoint before the new rail
 fn rollback_and_reapply(
     surface: &mut VSurfaceRailMut,
+    rail_checkpoints: &mut Vec<RailCheckpoint>,
     tunables: &PathingTunables,
-    old_rail_index: usize,
+    checkpoint_index: usize,
     new_mine: MineLocation,
     base_source: &mut BaseSourceEighth,
-    all_mines: &[MineLocation],
-) {
-    // remove old rail
-    let (old_path, _) = surface.remove_mine_path_at(old_rail_index).unwrap();
+) -> RailCheckpoint {
+    // remove old rail and everything after, restoring the exact pixels before it
+    let checkpoint = rail_checkpoints[checkpoint_index];
+    rail_checkpoints.truncate(checkpoint_index);
+    let mut removed = surface.rollback_to(checkpoint);
+    let old_path = removed.remove(0);
+    info!(
+        "rollback to mine checkpoint {checkpoint_index} removed {} after",
+        removed.len()
+    );
+    // old rail's base source is reused by the new path
+    for _ in &removed {
+        base_source.undo_one();
+    }
 
     // re-pathfind with restricted barriers. this SHOULD succeed
     let mut base_source_dummy = base_source.regenerate();
@@ -502,7 +524,7 @@ fn rollback_and_reapply(
             surface
                 .pixels()
                 .paint_pixel_colored_zoomed()
-                .save_to_oculante();
+                .save_to_sink(tunables.paint_sink());
             panic!("uhh")
         }
         ExecutorResult::Success { mut paths, routes } => {
@@ -511,25 +533,107 @@ fn rollback_and_reapply(
         }
     };
 
-    // re-pathfind everything after
-    while surface.rails().get_mine_paths().len() != old_rail_index {
-        info!(
-            "mines len {} or {old_rail_index}",
-            surface.rails().get_mine_paths().len()
-        );
-        let (_, removed_points) = surface.remove_mine_path_pop().unwrap();
-        MineLocation::restore_area_buffered(all_mines, &mut surface.pixels_mut(), removed_points);
-        base_source.undo_one();
-    }
     surface
         .pixels()
         .paint_pixel_colored_zoomed()
-        .save_to_oculante();
+        .save_to_sink(tunables.paint_sink());
+    let new_checkpoint = surface.begin();
     surface.add_mine_path(new_path);
+    new_checkpoint
 }
 
 enum QuesterScanResult {
-    YAxisEnding,
+    FaceEnding,
     NoPatchesInScan,
     NewPatchesInScanArea { selected_mines: Vec<usize> },
 }
+
+#[cfg(test)]
+mod test {
+    use crate::navigator::base_source::BaseSource;
+    use crate::navigator::mine_selector::PERPENDICULAR_SCAN_WIDTH;
+    use crate::navigator::planners::altare::{detect_nearby_rails_as_index, start_altare_planner};
+    use crate::navigator::planners::{PathingTunables, PlannerOutcome};
+    use crate::state::tuneables::Tunables;
+    use crate::surface::pixel::Pixel;
+    use crate::surfacev::mine::{MineLocation, MinePath, MinePathKind};
+    use crate::surfacev::vpatch::VPatch;
+    use crate::surfacev::vsurface::{
+        VSurface, VSurfaceNavAsVsMut, VSurfacePatchAsVsMut, VSurfacePixelAsVs,
+        VSurfacePixelAsVsMut, VSurfaceRailAsVs, VSurfaceRailAsVsMut,
+    };
+    use facto_loop_miner_fac_engine::common::varea::VArea;
+    use facto_loop_miner_fac_engine::common::vpoint::VPoint;
+    use facto_loop_miner_fac_engine::common::vpoint_direction::VPointDirectionQ;
+    use facto_loop_miner_fac_engine::game_blocks::rail_hope_single::SECTION_POINTS_I32;
+    use facto_loop_miner_fac_engine::game_entities::direction::FacDirectionQuarter;
+    use std::rc::Rc;
+
+    #[test]
+    fn test_existing_feeders() {
+        let tunables = PathingTunables::from_tunables(&Tunables::new(), Rc::default());
+        let mut surface = VSurface::new(800);
+        let radius = surface.pixels().get_radius_i32();
+
+        // two mines and the station in the first scanned strip
+        let faces = BaseSource::from_central_base(&tunables).into_refcells();
+        let first_face = faces.faces()[0].borrow();
+        let strip = first_face
+            .area_strip(0, PERPENDICULAR_SCAN_WIDTH, radius)
+            .unwrap();
+        let along_strip = |distance: i32| {
+            strip
+                .point_center()
+                .move_direction_int(first_face.direction(), distance)
+        };
+        for distance in [-250, 250] {
+            let corner = along_strip(distance);
+            let area = VArea::from_arbitrary_points_pair(corner, corner + VPoint::new(9, 9));
+            surface
+                .pixels_mut()
+                .change_pixels(area.get_points())
+                .stomp(Pixel::IronOre);
+            surface.patches_mut().add_patches([VPatch::new(
+                area.clone(),
+                Pixel::IronOre,
+                area.get_points(),
+            )]);
+        }
+        let station_center = along_strip(0);
+        let station = MineLocation::new_aggregation_station(
+            surface.pixels(),
+            VArea::from_arbitrary_points_pair(
+                station_center - VPoint::new(SECTION_POINTS_I32, SECTION_POINTS_I32),
+                station_center + VPoint::new(SECTION_POINTS_I32, SECTION_POINTS_I32),
+            ),
+        )
+        .unwrap();
+
+        // feeder on the other side of the base
+        let mut feeder = MinePath::new_test_straight(
+            surface.pixels(),
+            VPointDirectionQ(
+                VPoint::new(-4 * SECTION_POINTS_I32, 16 * SECTION_POINTS_I32),
+                FacDirectionQuarter::East,
+            ),
+            4,
+        );
+        feeder.kind = MinePathKind::MineToAggregator { station };
+        surface.rails_mut().add_mine_path(feeder.clone());
+
+        let outcome = start_altare_planner(&tunables, &mut surface.nav_mut());
+        assert!(matches!(outcome, PlannerOutcome::Complete), "{outcome:?}");
+
+        let rails = surface.rails();
+        let paths = rails.get_mine_paths();
+        assert_eq!(paths.len(), 4);
+        assert_eq!(paths[0], feeder);
+        let to_station = paths[1..]
+            .iter()
+            .filter(|v| v.kind == MinePathKind::AggregatorToBase)
+            .count();
+        assert_eq!(to_station, 1);
+        // rollbacks never start at the final feeder
+        assert_ne!(detect_nearby_rails_as_index(rails, &feeder.location), 0);
+    }
+}
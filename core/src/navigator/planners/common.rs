@@ -2,14 +2,14 @@ use crate::navigator::base_source::BaseSourceEighth;
 use crate::navigator::mine_executor::{ExecutionRoute, FailingMeta};
 use crate::navigator::mine_permutate::CompletePlan;
 use crate::navigator::mine_selector::MineSelectBatch;
-use crate::state::tuneables::{ChunkValue, MoriTunables, Tunables};
+use crate::state::tuneables::{AggregationTunables, ChunkValue, MoriTunables, Tunables};
 use crate::surface::pixel::Pixel;
 use crate::surfacev::mine::MineLocation;
 use crate::surfacev::vsurface::{
-    VSurfacePixelAsVs, VSurfacePixelAsVsMut, VSurfacePixelMut, VSurfaceRailMut,
+    SurfacePaintSink, VSurfacePixelAsVs, VSurfacePixelAsVsMut, VSurfacePixelMut, VSurfaceRailMut,
 };
 use facto_loop_miner_fac_engine::common::varea::VArea;
-use facto_loop_miner_fac_engine::common::vpoint::{VPOINT_THREE, VPoint};
+use facto_loop_miner_fac_engine::common::vpoint::VPOINT_THREE;
 use facto_loop_miner_fac_engine::common::vpoint_direction::VSegment;
 use facto_loop_miner_fac_engine::game_blocks::rail_hope::RailHopeLink;
 use facto_loop_miner_fac_engine::game_blocks::rail_hope_soda::HopeSodaLink;
@@ -22,13 +22,17 @@ use tracing::{error, warn};
 pub struct PathingTunables {
     base_chunks: ChunkValue,
     mori: MoriTunables,
+    aggregation: AggregationTunables,
+    paint_sink: Rc<SurfacePaintSink>,
 }
 
 impl PathingTunables {
-    pub fn from_tunables(tunables: &Tunables) -> Self {
+    pub fn from_tunables(tunables: &Tunables, paint_sink: Rc<SurfacePaintSink>) -> Self {
         Self {
             base_chunks: tunables.base.base_chunks,
             mori: tunables.mori.clone(),
+            aggregation: tunables.nav.aggregation.clone(),
+            paint_sink,
         }
     }
 
@@ -39,6 +43,15 @@ impl PathingTunables {
     pub fn mori(&self) -> &MoriTunables {
         &self.mori
     }
+
+    pub fn aggregation(&self) -> &AggregationTunables {
+        &self.aggregation
+    }
+
+    /// Progress frames while planning
+    pub fn paint_sink(&self) -> &SurfacePaintSink {
+        &self.paint_sink
+    }
 }
 
 /*
@@ -179,21 +192,27 @@ pub fn debug_failing(
         route
             .location
             .draw_area_buffered_highlight_pixel(&mut surface.pixels_mut(), Pixel::SteelChest);
+        surface.record_failed_mine(route.location);
     }
 }
 
 pub(super) fn draw_prep(surface: &mut VSurfacePixelMut, batches: &[MineSelectBatch]) {
+    let base_sources = batches
+        .iter()
+        .map(|v| v.base_sources.clone())
+        .unique_by(Rc::as_ptr)
+        .collect_vec();
     draw_prep_mines(
         surface,
         batches.iter().flat_map(|v| &v.mines),
-        &batches[0].base_sources,
+        &base_sources,
     )
 }
 
 pub(super) fn draw_prep_mines(
     surface: &mut VSurfacePixelMut,
     mines: impl IntoIterator<Item = impl Borrow<MineLocation>>,
-    base_sources: &Rc<RefCell<BaseSourceEighth>>,
+    base_sources: &[Rc<RefCell<BaseSourceEighth>>],
 ) {
     for mine in mines {
         mine.borrow().draw_area_buffered(surface);
@@ -203,10 +222,9 @@ pub(super) fn draw_prep_mines(
     // stop routes going backwards right behind the start
     let radius = surface.pixels().get_radius_i32();
 
-    let base_sources = base_sources.as_ref().borrow();
-    let anti_backside_x = base_sources.peek_single().origin.point().x() - 1;
-    let anti_backside_points = (-(radius - 1)..radius)
-        .map(|i| VPoint::new(anti_backside_x, i))
+    let anti_backside_points = base_sources
+        .iter()
+        .flat_map(|base_source| base_source.borrow().backside_points(radius))
         .collect_vec();
     surface
         .change_pixels(anti_backside_points)
@@ -1,18 +1,39 @@
 use crate::navigator::mine_permutate::get_possible_routes_for_batch;
-use crate::navigator::mine_selector::{MineSelectBatch, select_mines_and_sources};
+use crate::navigator::mine_selector::{
+    MineSelectBatch, group_nearby_patches, select_mines_and_sources,
+};
 use crate::navigator::planners::PathingTunables;
 use crate::navigator::planners::common::{debug_draw_complete_plan, draw_prep};
+use crate::navigator::planners::planner::{Planner, PlannerOutcome};
 use crate::surfacev::vsurface::{
-    VSurfacePatch, VSurfacePatchAsVs, VSurfacePatchMut, VSurfacePixelAsVs, VSurfacePixelAsVsMut,
-    VSurfacePixelMut,
+    SurfacePaintSink, VSurfaceNavMut, VSurfacePatch, VSurfacePatchAsVs, VSurfacePatchAsVsMut,
+    VSurfacePatchMut, VSurfacePixelAsVs, VSurfacePixelAsVsMut, VSurfacePixelMut,
 };
 use facto_loop_miner_fac_engine::common::varea::VArea;
 use simd_json::prelude::ArrayTrait;
 use tracing::{info, trace};
 
+/// Only draws the selected mines and possible routes
+pub struct DebugPlanner;
+
+impl Planner for DebugPlanner {
+    fn name(&self) -> &'static str {
+        "debug"
+    }
+
+    fn plan(&self, tunables: &PathingTunables, surface: &mut VSurfaceNavMut) -> PlannerOutcome {
+        start_debug_planner(tunables, &mut surface.patches_mut());
+        PlannerOutcome::Complete
+    }
+}
+
 pub fn start_debug_planner(tunables: &PathingTunables, surface_mut: &mut VSurfacePatchMut) {
     let select_batches = get_batches(tunables, surface_mut.patches());
-    paint_result(&mut surface_mut.pixels_mut(), select_batches);
+    paint_result(
+        &mut surface_mut.pixels_mut(),
+        select_batches,
+        tunables.paint_sink(),
+    );
     // if let Err(()) = debug_conflict_no_touching(surface, &select_batches) {
     //     error!("no touching");
     //     return;
@@ -22,7 +43,7 @@ pub fn start_debug_planner(tunables: &PathingTunables, surface_mut: &mut VSurfac
 }
 
 fn get_batches(tunables: &PathingTunables, surface: VSurfacePatch) -> Vec<MineSelectBatch> {
-    let select_batches = select_mines_and_sources(tunables, surface, 5)
+    let select_batches = select_mines_and_sources(tunables, group_nearby_patches(surface), 5)
         .into_success()
         .unwrap();
     let mines: usize = select_batches
@@ -51,7 +72,11 @@ fn get_batches(tunables: &PathingTunables, surface: VSurfacePatch) -> Vec<MineSe
     select_batches
 }
 
-fn paint_result(surface_mut: &mut VSurfacePixelMut, select_batches: Vec<MineSelectBatch>) {
+fn paint_result(
+    surface_mut: &mut VSurfacePixelMut,
+    select_batches: Vec<MineSelectBatch>,
+    paint_sink: &SurfacePaintSink,
+) {
     draw_prep(surface_mut, &select_batches);
     for (i, batch) in select_batches.into_iter().enumerate() {
         trace!("batch {i}");
@@ -61,5 +86,5 @@ fn paint_result(surface_mut: &mut VSurfacePixelMut, select_batches: Vec<MineSele
     surface_mut
         .pixels()
         .paint_pixel_colored_zoomed()
-        .save_to_oculante();
+        .save_to_sink(paint_sink);
 }
@@ -1,8 +1,10 @@
 pub mod altare;
 mod common;
 pub mod debugplan;
+mod planner;
 pub mod ruze;
 
 pub use common::{
     PathingTunables, debug_draw_mine_index_labels, debug_draw_mine_links, debug_draw_segment,
 };
+pub use planner::{Planner, PlannerOutcome, PlannerReport, all_planners, planner_by_name};
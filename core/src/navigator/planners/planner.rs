@@ -0,0 +1,112 @@
+use crate::navigator::planners::PathingTunables;
+use crate::navigator::planners::altare::AltarePlanner;
+use crate::navigator::planners::debugplan::DebugPlanner;
+use crate::navigator::planners::ruze::RuzePlanner;
+use crate::surfacev::err::{CoreConvertPathResult, VError, VResult};
+use crate::surfacev::vsurface::{VSurfaceNavMut, VSurfacePatchAsVs, VSurfaceRailAsVs};
+use facto_loop_miner_common::duration::{BasicWatch, BasicWatchResult};
+use facto_loop_miner_common::err_utils::xbt;
+use serde::Serialize;
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+use std::time::Duration;
+use tracing::info;
+
+/// Routes mines to the base, adding `MinePath`s to the surface
+pub trait Planner {
+    /// Selected by `Tunables.nav.planner`
+    fn name(&self) -> &'static str;
+
+    fn plan(&self, tunables: &PathingTunables, surface: &mut VSurfaceNavMut) -> PlannerOutcome;
+}
+
+pub fn all_planners() -> Vec<Box<dyn Planner>> {
+    vec![
+        Box::new(RuzePlanner),
+        Box::new(AltarePlanner),
+        Box::new(DebugPlanner),
+    ]
+}
+
+pub fn planner_by_name(name: &str) -> VResult<Box<dyn Planner>> {
+    all_planners()
+        .into_iter()
+        .find(|planner| planner.name() == name)
+        .ok_or_else(|| VError::UnknownName {
+            name: name.to_string(),
+            backtrace: xbt(),
+        })
+}
+
+#[derive(Debug, Serialize)]
+pub enum PlannerOutcome {
+    /// Every reachable mine was attempted
+    Complete,
+    /// Planner gave up early, surface contains partial and debug output
+    Stopped { reason: String },
+}
+
+/// Planner independent summary, for comparing planners on the same surface
+#[derive(Debug, Serialize)]
+pub struct PlannerReport {
+    pub planner: &'static str,
+    pub outcome: PlannerOutcome,
+    pub total_patches: usize,
+    pub mine_paths_before: usize,
+    pub mine_paths_after: usize,
+    pub total_cost: u64,
+    pub total_links: usize,
+    pub duration: Duration,
+}
+
+impl PlannerReport {
+    pub fn run(
+        planner: &dyn Planner,
+        tunables: &PathingTunables,
+        surface: &mut VSurfaceNavMut,
+    ) -> Self {
+        info!("Planner {} starting", planner.name());
+        let mine_paths_before = surface.rails().get_mine_paths().len();
+        let watch = BasicWatch::start();
+
+        let outcome = planner.plan(tunables, surface);
+
+        let mine_paths = surface.rails().get_mine_paths();
+        let report = PlannerReport {
+            planner: planner.name(),
+            outcome,
+            total_patches: surface.patches().get_patches().len(),
+            mine_paths_before,
+            mine_paths_after: mine_paths.len(),
+            total_cost: mine_paths.iter().map(|v| v.cost as u64).sum(),
+            total_links: mine_paths.iter().map(|v| v.links.len()).sum(),
+            duration: watch.duration(),
+        };
+        info!("{report}");
+        report
+    }
+
+    pub fn save(&self, out_dir: &Path) -> VResult<()> {
+        let report_path = out_dir.join("planner-report.json");
+        let output = simd_json::to_vec_pretty(self).convert(&report_path)?;
+        std::fs::write(&report_path, &output).convert(&report_path)?;
+        Ok(())
+    }
+}
+
+impl Display for PlannerReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Planner {} {:?} paths {} (was {}) of {} patches cost {} links {} in {}",
+            self.planner,
+            self.outcome,
+            self.mine_paths_after,
+            self.mine_paths_before,
+            self.total_patches,
+            self.total_cost,
+            self.total_links,
+            BasicWatchResult(self.duration),
+        )
+    }
+}
@@ -1,29 +1,47 @@
 use crate::always_true_test;
+use crate::navigator::aggregation::mines_to_base;
 use crate::navigator::mine_executor::{ExecutorResult, FailingMeta, execute_route_batch};
 use crate::navigator::mine_permutate::get_possible_routes_for_batch;
-use crate::navigator::mine_selector::{MineSelectBatch, select_mines_and_sources};
+use crate::navigator::mine_selector::{
+    MineSelectBatch, group_nearby_patches, select_mines_and_sources,
+};
 use crate::navigator::planners::common::{PathingTunables, debug_failing, draw_prep};
+use crate::navigator::planners::planner::{Planner, PlannerOutcome};
 use crate::state::tuneables::MoriTunables;
 use crate::surface::metric::Metrics;
 use crate::surface::pixel::Pixel;
 use crate::surfacev::vsurface::{
-    VSurfaceNavMut, VSurfacePatchAsVs, VSurfacePixelAsVs, VSurfacePixelAsVsMut, VSurfaceRailAsVsMut,
+    VSurfaceNavMut, VSurfacePatchAsVs, VSurfacePixelAsVs, VSurfacePixelAsVsMut, VSurfaceRailAsVs,
+    VSurfaceRailAsVsMut,
 };
 use tracing::{error, info, trace, warn};
 
 const RUZE_MAXIMUM_MINE_COUNT_PER_BATCH: usize = 5;
 
+pub struct RuzePlanner;
+
+impl Planner for RuzePlanner {
+    fn name(&self) -> &'static str {
+        "ruze"
+    }
+
+    fn plan(&self, tunables: &PathingTunables, surface: &mut VSurfaceNavMut) -> PlannerOutcome {
+        start_ruze_planner(tunables, surface)
+    }
+}
+
 /// Planner v1 "Crimzon Ruze 💢"
 ///
 /// Super parallel batch based planner
-pub fn start_ruze_planner(tunables: &PathingTunables, surface: &mut VSurfaceNavMut) {
-    let select_batches = select_mines_and_sources(
-        tunables,
-        surface.patches(),
-        RUZE_MAXIMUM_MINE_COUNT_PER_BATCH,
-    )
-    .into_success()
-    .unwrap();
+pub fn start_ruze_planner(
+    tunables: &PathingTunables,
+    surface: &mut VSurfaceNavMut,
+) -> PlannerOutcome {
+    let mines = mines_to_base(surface.rails(), group_nearby_patches(surface.patches()));
+    let select_batches =
+        select_mines_and_sources(tunables, mines, RUZE_MAXIMUM_MINE_COUNT_PER_BATCH)
+            .into_success()
+            .unwrap();
 
     let mut num_mines_metrics = Metrics::new("mine_batch_size");
     for batch in &select_batches {
@@ -39,14 +57,19 @@ pub fn start_ruze_planner(tunables: &PathingTunables, surface: &mut VSurfaceNavM
         let found = process_batch(tunables.mori(), surface, batch, batch_index);
         if !found {
             error!("KILLING EARLY index {batch_index}");
-            break;
+            return PlannerOutcome::Stopped {
+                reason: format!("batch {batch_index} failed"),
+            };
         }
 
         // if 1 + 1 == 2 {
         if batch_index > 20 {
-            break;
+            return PlannerOutcome::Stopped {
+                reason: format!("batch limit {batch_index}"),
+            };
         }
     }
+    PlannerOutcome::Complete
 }
 
 fn process_batch(
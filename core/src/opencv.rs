@@ -1,14 +1,19 @@
 use crate::surface::pixel::Pixel;
-use facto_loop_miner_fac_engine::common::vpoint::VPoint;
+use crate::surfacev::vsurface::{VSurface, VSurfacePatchAsVs, VSurfacePixelAsVs};
+use facto_loop_miner_fac_engine::common::varea::VArea;
+use facto_loop_miner_fac_engine::common::vpoint::{VPOINT_ONE, VPoint};
 use facto_loop_miner_fac_engine::opencv_re::boxed_ref::BoxedRefMut;
 use facto_loop_miner_fac_engine::opencv_re::core::{
-    Mat, MatTraitConstManual, Point, ROTATE_90_COUNTERCLOCKWISE, Rect, Scalar, Vector, rotate,
+    Mat, MatTraitConstManual, Point, ROTATE_90_COUNTERCLOCKWISE, Rect, Scalar, ToInputArray,
+    Vector, rotate,
 };
+use facto_loop_miner_fac_engine::opencv_re::imgcodecs::imwrite;
 use facto_loop_miner_fac_engine::opencv_re::imgproc::{
-    FONT_HERSHEY_SIMPLEX, LINE_8, bounding_rect, get_font_scale_from_height, get_text_size,
-    put_text,
+    CHAIN_APPROX_NONE, FONT_HERSHEY_SIMPLEX, LINE_8, RETR_EXTERNAL, bounding_rect, find_contours,
+    get_font_scale_from_height, get_text_size, put_text, rectangle,
 };
-use tracing::trace;
+use std::path::Path;
+use tracing::{debug, trace};
 // pub fn load_raw_image_with_surface(
 //     path: &Path,
 //     surface_meta: &VSurface,
@@ -79,6 +84,64 @@ use tracing::trace;
 //     )
 // }
 
+/// Bounding areas of the outer contours, in image coordinates.
+/// `find_contours` ignores the 1 pixel image border,
+/// same as [crate::surfacev::patch_labeller::label_component_areas]
+pub fn detect_contour_areas(base: &impl ToInputArray) -> Vec<VArea> {
+    let mut contours: Vector<Vector<Point>> = Vector::default();
+    let offset = Point { x: 0, y: 0 };
+    // RETR_LIST - May make rectangles inside rectangles, other multiple rectangles at whisps
+    // RETR_CCOMP - Same???
+    //
+    // CHAIN_APPROX_NONE - Stores all points. unnecessary
+    // CHAIN_APPROX_SIMPLE - Only store corners, which are the relevant points
+    // CHAIN_APPROX_TC89_L1 - Didn't do anything????
+    find_contours(
+        base,
+        &mut contours,
+        RETR_EXTERNAL,
+        // RETR_LIST,
+        CHAIN_APPROX_NONE,
+        offset,
+    )
+    .unwrap();
+    debug!("found contours {}", contours.len());
+
+    contours
+        .into_iter()
+        .map(|contour| rect_to_area(&bounding_rect(&contour).unwrap()))
+        .collect()
+}
+
+fn rect_to_area(rect: &Rect) -> VArea {
+    VArea::from_arbitrary_points_pair(
+        VPoint::from_rect_start(rect),
+        VPoint::new(rect.x + rect.width - 1, rect.y + rect.height - 1),
+    )
+}
+
+/// `cv-{resource}.png` of each resource with a border around its patches
+pub fn write_patch_debug_images(surface: &VSurface, out_dir: &Path) {
+    let radius = surface.pixels().get_radius_i32();
+    for pixel in Pixel::iter_resource() {
+        let mut img_gen = surface.pixels().get_pixel_cv_image(Some(pixel));
+        let mut img = img_gen.as_mat();
+        for patch in surface.patches().get_patches() {
+            if patch.resource != pixel {
+                continue;
+            }
+            let size = patch.area.as_size() + VPOINT_ONE;
+            let start = patch.area.point_top_left() + VPoint::new(radius, radius);
+            let rect = Rect::new(start.x(), start.y(), size.x(), size.y());
+            rectangle(&mut img, rect, Pixel::Highlighter.scalar_cv(), 2, LINE_8, 0).unwrap();
+        }
+
+        let path = out_dir.join(format!("cv-{}.png", pixel.as_ref()));
+        debug!("Wrote debug image {}", path.display());
+        imwrite(path.to_str().unwrap(), &img, &Vector::new()).unwrap();
+    }
+}
+
 pub fn get_cv_bounding_rect(points: Vec<Point>) -> Rect {
     bounding_rect(&Vector::from_slice(&points)).unwrap()
 }
@@ -185,3 +248,42 @@ impl GeneratedMat {
             .unwrap()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::opencv::{GeneratedMat, detect_contour_areas};
+    use crate::surface::pixel::Pixel;
+    use crate::surfacev::patch_labeller::{Connectivity, label_component_areas};
+
+    #[test]
+    fn test_contours_match_labeller() {
+        let size = 200;
+        // deterministic speckle with blobs, holes and diagonal joins
+        let mut state: u32 = 12345;
+        let mask: Vec<bool> = (0..size * size)
+            .map(|index| {
+                state = state.wrapping_mul(1103515245).wrapping_add(12345);
+                let (x, y) = (index % size, index / size);
+                // both detectors ignore the border
+                let is_border = x == 0 || y == 0 || x == size - 1 || y == size - 1;
+                !is_border && (state >> 16) % 3 == 0
+            })
+            .collect();
+
+        let mut img_gen = GeneratedMat {
+            rows: size,
+            cols: size,
+            data: mask
+                .iter()
+                .map(|v| if *v { Pixel::Highlighter.into_id() } else { 0 })
+                .collect(),
+        };
+        let mut cv_areas = detect_contour_areas(&img_gen.as_mat());
+        let mut labeller_areas = label_component_areas(&mask, size, Connectivity::Eight);
+        assert_ne!(cv_areas.len(), 0);
+
+        cv_areas.sort();
+        labeller_areas.sort();
+        assert_eq!(cv_areas, labeller_areas);
+    }
+}
@@ -1,23 +1,23 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::fs;
 use std::fs::{read_to_string, File};
 use std::io::{BufWriter, ErrorKind};
 use std::path::{Path, PathBuf};
-use std::time::UNIX_EPOCH;
 
 #[derive(Serialize, Deserialize)]
 pub struct State {
     #[serde(skip)]
     path: PathBuf,
-    files_to_last_modified: HashMap<PathBuf, u128>,
+    /// Step name to [`crate::state::fingerprint::StepFingerprint`] of its last successful run
+    #[serde(default)]
+    step_fingerprints: HashMap<String, u64>,
 }
 
 impl Default for State {
     fn default() -> Self {
         State {
             path: PathBuf::new(),
-            files_to_last_modified: HashMap::new(),
+            step_fingerprints: HashMap::new(),
         }
     }
 }
@@ -54,37 +54,35 @@ impl State {
         serde_json::to_writer(BufWriter::new(file), self).unwrap();
     }
 
-    pub fn update_modified(&mut self, path: &Path) -> bool {
-        !path.exists()
+    pub fn is_fingerprint_changed(&self, step_name: &str, fingerprint: u64) -> bool {
+        match self.step_fingerprints.get(step_name) {
+            Some(existing) if *existing == fingerprint => false,
+            Some(existing) => {
+                tracing::debug!(
+                    "[State] found change on {} old {:016x} new {:016x}",
+                    step_name,
+                    existing,
+                    fingerprint,
+                );
+                true
+            }
+            None => {
+                tracing::debug!("[State] no fingerprint for {}", step_name);
+                true
+            }
+        }
     }
 
-    pub fn update_modified_old(&mut self, path: &Path) -> bool {
-        let metadata = match fs::metadata(path) {
-            Ok(v) => v,
-            Err(e) => panic!("Path {} e {}", path.display(), e),
-        };
-        let mut force_rebuild = false;
-        let modified_new = metadata.modified().unwrap();
-        let modified_new_milli = modified_new.duration_since(UNIX_EPOCH).unwrap().as_millis();
-        self.files_to_last_modified
-            .entry(PathBuf::from(path))
-            .and_modify(|modified_old| {
-                if &modified_new_milli != modified_old {
-                    force_rebuild = true;
-                    tracing::debug!(
-                        "[State] found change on {} old {} new {}",
-                        path.display(),
-                        modified_old,
-                        modified_new_milli,
-                    );
-                    *modified_old = modified_new_milli;
-                }
-            })
-            .or_insert_with(|| {
-                force_rebuild = true;
-                modified_new_milli
-            });
+    pub fn fingerprint(&self, step_name: &str) -> Option<u64> {
+        self.step_fingerprints.get(step_name).copied()
+    }
+
+    pub fn update_fingerprint(&mut self, step_name: &str, fingerprint: u64) {
+        self.step_fingerprints
+            .insert(step_name.to_string(), fingerprint);
+    }
 
-        force_rebuild
+    pub fn remove_fingerprint(&mut self, step_name: &str) {
+        self.step_fingerprints.remove(step_name);
     }
 }
@@ -9,12 +9,19 @@ pub type XMachineResult<R> = Result<R, XMachineError>;
 pub enum XMachineError {
     #[error("SurfaceFailure {}", e)]
     SurfaceFailure { e: VError },
+    #[error("UnknownStep {name} expected one of {known}")]
+    UnknownStep {
+        name: String,
+        known: String,
+        backtrace: Backtrace,
+    },
 }
 
 impl MyBacktrace for XMachineError {
     fn my_backtrace(&self) -> &Backtrace {
         match self {
             XMachineError::SurfaceFailure { e } => e.my_backtrace(),
+            XMachineError::UnknownStep { backtrace, .. } => backtrace,
         }
     }
 }
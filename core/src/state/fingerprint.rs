@@ -0,0 +1,204 @@
+use crate::state::tuneables::Tunables;
+use crate::surfacev::err::{CoreConvertPathResult, VError, VResult};
+use facto_loop_miner_common::err_utils::xbt;
+use std::fs::read_dir;
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// Hash of everything a step consumes.
+///
+/// The previous step's output is represented by that step's own fingerprint plus the metadata
+/// of its output dir, so edits to that dir are noticed without re-reading it. Files from
+/// outside the step history also only contribute their metadata.
+///
+/// FNV-1a instead of DefaultHasher because state.json must compare equal across toolchain updates
+pub struct StepFingerprint {
+    hash: u64,
+}
+
+impl StepFingerprint {
+    pub fn compute(
+        step_version: u32,
+        tunables: &Tunables,
+        tunable_sections: &[&str],
+        previous_fingerprint: Option<u64>,
+        previous_out_dir: Option<&Path>,
+        external_input: Option<&Path>,
+    ) -> VResult<u64> {
+        let mut fingerprint = Self {
+            hash: FNV_OFFSET_BASIS,
+        };
+        fingerprint.write(&step_version.to_le_bytes());
+
+        let tunables = serde_json::to_value(tunables).map_err(invalid_tunables)?;
+        for section in tunable_sections {
+            let Some(section_value) = tunables.get(section) else {
+                return Err(VError::InvalidTunables {
+                    reason: format!("unknown tunables section {section}"),
+                    backtrace: xbt(),
+                });
+            };
+            fingerprint.write(section.as_bytes());
+            fingerprint.write(&serde_json::to_vec(section_value).map_err(invalid_tunables)?);
+        }
+
+        match previous_fingerprint {
+            Some(previous) => fingerprint.write(&previous.to_le_bytes()),
+            None => fingerprint.write(&[0]),
+        }
+        match previous_out_dir {
+            Some(previous_out_dir) if previous_out_dir.exists() => {
+                fingerprint.write_dir_metadata(previous_out_dir)?
+            }
+            _ => fingerprint.write(&[0]),
+        }
+        if let Some(external_input) = external_input {
+            fingerprint.write_dir_metadata(external_input)?;
+        }
+        Ok(fingerprint.hash)
+    }
+
+    /// Names, sizes and modification times. Contents can be gigabytes
+    fn write_dir_metadata(&mut self, dir: &Path) -> VResult<()> {
+        let mut entries = Vec::new();
+        for entry in read_dir(dir).convert(dir)? {
+            entries.push(entry.convert(dir)?.path());
+        }
+        // read_dir order is filesystem dependent
+        entries.sort();
+
+        for path in entries {
+            self.write(path.file_name().unwrap().as_encoded_bytes());
+            if path.is_dir() {
+                self.write_dir_metadata(&path)?;
+                continue;
+            }
+            let metadata = path.metadata().convert(&path)?;
+            self.write(&metadata.len().to_le_bytes());
+            let modified = metadata
+                .modified()
+                .convert(&path)?
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default();
+            self.write(&modified.as_nanos().to_le_bytes());
+        }
+        Ok(())
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.hash ^= *byte as u64;
+            self.hash = self.hash.wrapping_mul(FNV_PRIME);
+        }
+    }
+}
+
+fn invalid_tunables(err: serde_json::Error) -> VError {
+    VError::InvalidTunables {
+        reason: format!("serialize for fingerprint {err}"),
+        backtrace: xbt(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::state::fingerprint::StepFingerprint;
+    use crate::state::machine_v1::new_v1_machine;
+    use crate::state::tuneables::Tunables;
+    use crate::surfacev::err::VError;
+    use std::env::temp_dir;
+    use std::fs::{create_dir_all, remove_dir_all, write};
+
+    #[test]
+    fn test_version_changes_fingerprint() {
+        let tunables = Tunables::new();
+        let first = StepFingerprint::compute(1, &tunables, &["crop"], None, None, None).unwrap();
+        assert_eq!(
+            first,
+            StepFingerprint::compute(1, &tunables, &["crop"], None, None, None).unwrap()
+        );
+        assert_ne!(
+            first,
+            StepFingerprint::compute(2, &tunables, &["crop"], None, None, None).unwrap()
+        );
+        assert_ne!(
+            first,
+            StepFingerprint::compute(1, &tunables, &["crop"], Some(first), None, None).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_unrelated_tunable_changed() {
+        let tunables = Tunables::new();
+        let first = StepFingerprint::compute(1, &tunables, &["crop"], None, None, None).unwrap();
+
+        let mut unrelated = tunables.clone();
+        unrelated.save.validate = !unrelated.save.validate;
+        unrelated.save.json_state = !unrelated.save.json_state;
+        unrelated.mori.turn_cost_unit += 1;
+        assert_eq!(
+            first,
+            StepFingerprint::compute(1, &unrelated, &["crop"], None, None, None).unwrap()
+        );
+
+        let mut related = tunables.clone();
+        related.crop.radius += 1;
+        assert_ne!(
+            first,
+            StepFingerprint::compute(1, &related, &["crop"], None, None, None).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_input_dir_changed() {
+        let dir = temp_dir().join(format!("fingerprint-input-{}", std::process::id()));
+        create_dir_all(dir.join("sub")).unwrap();
+        write(dir.join("sub/a.dat"), [1, 2, 3]).unwrap();
+        let tunables = Tunables::new();
+        let compute =
+            || StepFingerprint::compute(1, &tunables, &[], None, None, Some(&dir)).unwrap();
+
+        let first = compute();
+        assert_eq!(first, compute());
+        write(dir.join("sub/a.dat"), [1, 2, 3, 4]).unwrap();
+        let grown = compute();
+        assert_ne!(first, grown);
+        write(dir.join("b.dat"), []).unwrap();
+        assert_ne!(grown, compute());
+
+        remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_previous_out_dir_changed() {
+        let dir = temp_dir().join(format!("fingerprint-previous-{}", std::process::id()));
+        create_dir_all(&dir).unwrap();
+        write(dir.join("a.dat"), [1, 2, 3]).unwrap();
+        let tunables = Tunables::new();
+        let compute =
+            || StepFingerprint::compute(1, &tunables, &[], Some(5), Some(&dir), None).unwrap();
+
+        let first = compute();
+        assert_eq!(first, compute());
+        write(dir.join("a.dat"), [1, 2]).unwrap();
+        assert_ne!(first, compute());
+
+        remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_step_sections_exist() {
+        let tunables = Tunables::new();
+        for step in new_v1_machine().steps {
+            StepFingerprint::compute(1, &tunables, step.tunable_sections(), None, None, None)
+                .unwrap();
+        }
+        assert!(matches!(
+            StepFingerprint::compute(1, &tunables, &["cropp"], None, None, None),
+            Err(VError::InvalidTunables { .. })
+        ));
+    }
+}
@@ -1,8 +1,12 @@
 use crate::state::disk::State;
-use crate::state::err::XMachineResult;
+use crate::state::err::{XMachineError, XMachineResult};
+use crate::state::fingerprint::StepFingerprint;
+use crate::state::tuneables::Tunables;
 use crate::surface::metric::Metrics;
+use crate::surfacev::vsurface::SurfacePaintSink;
 use facto_loop_miner_common::duration::BasicWatch;
 use facto_loop_miner_common::err_bt::MyBacktrace;
+use std::backtrace::Backtrace;
 use std::cell::RefCell;
 use std::fs::{create_dir, read_dir, remove_dir, remove_dir_all};
 use std::path::{Path, PathBuf};
@@ -16,30 +20,88 @@ pub struct Machine {
     pub(crate) steps: Vec<StepBox>,
 }
 
+/// Which steps [`Machine::start`] may run. Names are [`Step::name`]
+#[derive(Default)]
+pub struct MachineRunOptions {
+    /// Earlier steps are skipped, their existing output is reused
+    pub start_at: Option<String>,
+    /// Later steps are skipped
+    pub stop_at: Option<String>,
+    /// Output of this and later steps is deleted first
+    pub force_from: Option<String>,
+}
+
 pub struct StepParams {
+    work_dir: PathBuf,
     pub step_out_dir: PathBuf,
     pub metrics: Rc<RefCell<Metrics>>,
     step_history_out_dirs: Vec<PathBuf>,
     pub state: Rc<RefCell<State>>,
+    pub tunables: Tunables,
+    /// Shared by every step so frame numbers keep counting up
+    pub paint_sink: Rc<SurfacePaintSink>,
 }
 
 impl StepParams {
     pub fn previous_step_dir(&self) -> &Path {
         self.step_history_out_dirs.last().unwrap()
     }
+
+    pub fn work_dir(&self) -> &Path {
+        &self.work_dir
+    }
 }
 
 pub trait Step {
     fn name(&self) -> &'static str;
+    /// Bump when the transformer output changes, forcing this and later steps to re-run
+    fn version(&self) -> u32 {
+        1
+    }
+    /// Top level [`Tunables`] sections the transformer reads
+    fn tunable_sections(&self) -> &'static [&'static str] {
+        &[]
+    }
+    /// Input from outside the step history, like the exported game data
+    fn external_input(&self, _work_dir: &Path, _tunables: &Tunables) -> Option<PathBuf> {
+        None
+    }
     fn transformer(&self, params: StepParams) -> XMachineResult<()>;
 }
 
 impl Machine {
-    pub fn start(self, work_dir: &Path) {
+    pub fn start(self, work_dir: &Path, options: MachineRunOptions, tunables: Tunables) {
+        if let Err(e) = self.start_checked(work_dir, options, tunables) {
+            error!("Machine failed! {}\n{}", e, e.my_backtrace());
+        }
+    }
+
+    fn start_checked(
+        self,
+        work_dir: &Path,
+        options: MachineRunOptions,
+        tunables: Tunables,
+    ) -> XMachineResult<()> {
         let output_dir = work_dir.join("out0");
         if !output_dir.is_dir() {
             create_dir(&output_dir).unwrap();
         }
+        let paint_sink = Rc::new(SurfacePaintSink::new(work_dir, &tunables.save.paint_sink)?);
+
+        let start_index = self.find_step_index(options.start_at.as_deref())?;
+        let stop_index = self.find_step_index(options.stop_at.as_deref())?;
+        if let Some(force_index) = self.find_step_index(options.force_from.as_deref())? {
+            for step in &self.steps[force_index..] {
+                let step_out_dir = output_dir.join(step.name());
+                if step_out_dir.exists() {
+                    info!(
+                        "[Machine] forced re-run, removing {}",
+                        step_out_dir.display()
+                    );
+                    remove_dir_all(&step_out_dir).unwrap();
+                }
+            }
+        }
 
         let state = Rc::new(RefCell::new(State::new(&work_dir.join("state.json"))));
 
@@ -48,7 +110,7 @@ impl Machine {
 
         let mut step_history_out_dirs = Vec::new();
         let mut enabled = false;
-        for step in &self.steps {
+        for (step_index, step) in self.steps.iter().enumerate() {
             let header_prefix = format!("=== {}", step.name());
             if step.name() == DEATH_STEP_NAME {
                 warn!("{} - RIP", header_prefix);
@@ -56,9 +118,29 @@ impl Machine {
             }
 
             let step_out_dir = output_dir.join(step.name());
+            if start_index.is_some_and(|start_index| step_index < start_index) {
+                info!("{} - Skipped before start", header_prefix);
+                step_history_out_dirs.push(step_out_dir);
+                continue;
+            }
+
+            // previous output is represented by the fingerprint that produced it
+            let previous_fingerprint = step_index
+                .checked_sub(1)
+                .and_then(|i| state.borrow().fingerprint(self.steps[i].name()));
+            let fingerprint = StepFingerprint::compute(
+                step.version(),
+                &tunables,
+                step.tunable_sections(),
+                previous_fingerprint,
+                step_history_out_dirs.last().map(PathBuf::as_path),
+                step.external_input(work_dir, &tunables).as_deref(),
+            )?;
             if !enabled {
-                // let changed = state.borrow_mut().update_modified(&step_out_dir);
-                enabled = !step_out_dir.exists();
+                enabled = !step_out_dir.exists()
+                    || state
+                        .borrow()
+                        .is_fingerprint_changed(step.name(), fingerprint);
             }
 
             if enabled {
@@ -71,15 +153,20 @@ impl Machine {
 
                 let mut step_watch = BasicWatch::start();
 
+                // partial output from a failed run must not look up to date
+                state.borrow_mut().remove_fingerprint(step.name());
                 let transformer_result = step.transformer(StepParams {
+                    work_dir: work_dir.to_path_buf(),
                     step_out_dir: step_out_dir.clone(),
                     metrics: metrics.clone(),
                     step_history_out_dirs: step_history_out_dirs.clone(),
                     state: state.clone(),
+                    tunables: tunables.clone(),
+                    paint_sink: paint_sink.clone(),
                 });
                 if let Err(e) = transformer_result {
-                    error!("Machine failed! {}\n{}", e, e.my_backtrace());
-                    return;
+                    RefCell::into_inner(Rc::into_inner(state).unwrap()).disk_write();
+                    return Err(e);
                 }
                 step_watch.stop();
 
@@ -88,9 +175,11 @@ impl Machine {
                 if read_dir(&step_out_dir).unwrap().count() == 0 {
                     debug!("=== detected empty dir, removing for future re-processing");
                     remove_dir(&step_out_dir).unwrap();
+                    state.borrow_mut().remove_fingerprint(step.name());
                 } else {
-                    // dir modify date updated after writing to folder
-                    state.borrow_mut().update_modified(&step_out_dir);
+                    state
+                        .borrow_mut()
+                        .update_fingerprint(step.name(), fingerprint);
                 }
 
                 info!("Step Completed in {}", step_watch,);
@@ -98,9 +187,49 @@ impl Machine {
                 info!("{} - No Changes Found", header_prefix)
             }
             step_history_out_dirs.push(step_out_dir.clone());
+
+            if stop_index == Some(step_index) {
+                warn!("{} - Stopping as requested", header_prefix);
+                break;
+            }
         }
 
         RefCell::into_inner(Rc::into_inner(state).unwrap()).disk_write();
+        Ok(())
+    }
+
+    /// Print each step and if its output dir exists
+    pub fn list(&self, work_dir: &Path) {
+        let output_dir = work_dir.join("out0");
+        for step in &self.steps {
+            let status = if step.name() == DEATH_STEP_NAME {
+                "end"
+            } else if output_dir.join(step.name()).is_dir() {
+                "done"
+            } else {
+                "pending"
+            };
+            println!("{:20} {}", step.name(), status);
+        }
+    }
+
+    fn find_step_index(&self, name: Option<&str>) -> XMachineResult<Option<usize>> {
+        let Some(name) = name else {
+            return Ok(None);
+        };
+        match self.steps.iter().position(|step| step.name() == name) {
+            Some(index) => Ok(Some(index)),
+            None => Err(XMachineError::UnknownStep {
+                name: name.to_string(),
+                known: self
+                    .steps
+                    .iter()
+                    .map(|v| v.name())
+                    .collect::<Vec<_>>()
+                    .join(","),
+                backtrace: Backtrace::capture(),
+            }),
+        }
     }
 }
 
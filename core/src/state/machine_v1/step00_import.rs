@@ -1,13 +1,18 @@
-use crate::gamedata::lua::{LuaEntity, LuaThing, read_lua_tiles};
+use crate::gamedata::lua::{LuaEntity, LuaExport, LuaThing, LuaTile, read_lua_export};
+use crate::gamedata::rcon_scan::read_rcon_export;
 use crate::state::err::XMachineResult;
 use crate::state::machine::{Step, StepParams};
+use crate::state::tuneables::{ImportSource, Tunables};
 use crate::surface::pixel::Pixel;
+use crate::surfacev::err::VResult;
+use crate::surfacev::vamount_map::VAmountMap;
 use crate::surfacev::vsurface::{VSurface, VSurfacePixelAsVsMut, VSurfacePixelMut};
 use facto_loop_miner_common::duration::BasicWatch;
+use facto_loop_miner_fac_engine::admiral::executor::client::AdmiralClient;
 use facto_loop_miner_fac_engine::blueprint::bpfac::position::FacBpPosition;
 use facto_loop_miner_fac_engine::common::vpoint::VPoint;
 use std::collections::HashMap;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use tracing::info;
 
 pub struct Step00 {}
@@ -24,14 +29,38 @@ impl Step for Step00 {
         "step00-import"
     }
 
+    fn tunable_sections(&self) -> &'static [&'static str] {
+        &["import"]
+    }
+
+    fn external_input(&self, work_dir: &Path, tunables: &Tunables) -> Option<PathBuf> {
+        match tunables.import.source {
+            ImportSource::Files => Some(work_dir.join(&tunables.import.input_dir)),
+            // live game has no cheap change detection
+            ImportSource::Rcon => None,
+        }
+    }
+
     fn transformer(&self, params: StepParams) -> XMachineResult<()> {
-        let lua_dir = Path::new("work/lm-artful");
-        let lua_tiles = read_lua_tiles(lua_dir);
+        let LuaExport {
+            entities,
+            tiles,
+            mut obstacles,
+        } = read_export(&params)?;
+        if !params.tunables.import.trees {
+            obstacles.retain(|v| v.name != Pixel::Tree);
+        }
 
         let convert_watch = BasicWatch::start();
-        let radius = find_radius(&lua_tiles) as u32;
+        let radius = find_radius(&entities, &tiles, &obstacles) as u32;
         let mut surface = VSurface::new(radius);
-        translate_entities_to_image(&lua_tiles, &mut surface.pixels_mut(), &params);
+        surface.set_tunables(params.tunables.clone());
+        // resources are drawn over any water
+        translate_entities_to_image(&tiles, &mut surface.pixels_mut(), &params);
+        translate_entities_to_image(&entities, &mut surface.pixels_mut(), &params);
+        // obstacles on ore still block
+        translate_entities_to_image(&obstacles, &mut surface.pixels_mut(), &params);
+        translate_amounts(&entities, surface.amounts_mut());
         info!("Converted in {}", convert_watch);
 
         // let center = surface.get_pixel(VPoint::new(0, 0));
@@ -45,10 +74,30 @@ impl Step for Step00 {
     }
 }
 
-fn find_radius(data: &[LuaEntity]) -> f32 {
+fn read_export(params: &StepParams) -> VResult<LuaExport> {
+    let tunables = &params.tunables.import;
+    match tunables.source {
+        ImportSource::Files => read_lua_export(&params.work_dir().join(&tunables.input_dir)),
+        ImportSource::Rcon => {
+            let mut client = AdmiralClient::new()?;
+            client.auth()?;
+            read_rcon_export(
+                &mut client,
+                tunables.rcon_radius_chunks,
+                tunables.rcon_page_chunks,
+                tunables.rcon_response_bytes,
+                tunables.trees,
+            )
+        }
+    }
+}
+
+fn find_radius(entities: &[LuaEntity], tiles: &[LuaTile], obstacles: &[LuaEntity]) -> f32 {
     let mut bottom_left: FacBpPosition = FacBpPosition { x: 0.0, y: 0.0 };
     let mut top_right = FacBpPosition { x: 0.0, y: 0.0 };
-    find_radius_max(data, &mut bottom_left, &mut top_right);
+    find_radius_max(entities, &mut bottom_left, &mut top_right);
+    find_radius_max(tiles, &mut bottom_left, &mut top_right);
+    find_radius_max(obstacles, &mut bottom_left, &mut top_right);
 
     let mut max_radius = 0.0f32;
     max_radius = max_radius.max(bottom_left.x.abs());
@@ -98,7 +147,7 @@ fn translate_entities_to_image<E>(
         mega_init_entities
             .entry(name)
             .or_default()
-            .push(entity.position().to_vpoint_with_offset(0.5, 0.5));
+            .push(entity.point());
         params
             .metrics
             .borrow_mut()
@@ -109,3 +158,18 @@ fn translate_entities_to_image<E>(
         surface.change_pixels(points).stomp(pixel);
     }
 }
+
+fn translate_amounts(entities: &[LuaEntity], amounts: &mut VAmountMap) {
+    let mut total_with_amount: usize = 0;
+    for entity in entities {
+        if let Some(amount) = entity.amount {
+            amounts.set(&entity.point(), amount);
+            total_with_amount += 1;
+        }
+    }
+    info!(
+        "Imported amounts for {} of {} entities",
+        total_with_amount,
+        entities.len()
+    );
+}
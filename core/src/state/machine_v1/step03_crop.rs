@@ -1,6 +1,6 @@
 use crate::state::err::XMachineResult;
 use crate::state::machine::{Step, StepParams};
-use crate::surfacev::vsurface::{VSurface, VSurfacePixelAsVsMut};
+use crate::surfacev::vsurface::{VSurface, VSurfaceEntityAsVsMut, VSurfacePixelAsVsMut};
 
 pub struct Step03 {}
 
@@ -11,17 +11,22 @@ impl Step03 {
     }
 }
 
-pub const CROP_RADIUS: u32 = 3000;
-
 impl Step for Step03 {
     fn name(&self) -> &'static str {
         "step03-crop"
     }
 
+    fn tunable_sections(&self) -> &'static [&'static str] {
+        &["crop"]
+    }
+
     fn transformer(&self, params: StepParams) -> XMachineResult<()> {
         let mut surface = VSurface::load_from_last_step(&params)?;
 
-        surface.pixels_mut().crop(CROP_RADIUS);
+        let radius = surface.tunables().crop.radius;
+        surface.pixels_mut().crop(radius);
+        surface.entities_mut().crop(radius);
+        surface.amounts_mut().crop(radius);
 
         surface.save(&params.step_out_dir)?;
 
@@ -1,34 +1,27 @@
 use crate::PixelKdTree;
-use crate::opencv::combine_rects_into_big_rect;
+#[cfg(feature = "opencv_re")]
+use crate::opencv::{detect_contour_areas, write_patch_debug_images};
 use crate::state::err::XMachineResult;
 use crate::state::machine::{Step, StepParams};
+use crate::state::tuneables::{PatchDetector, PatchTunables};
 use crate::surface::metric::Metrics;
 use crate::surface::pixel::Pixel;
+use crate::surfacev::patch_labeller::label_component_areas;
 use crate::surfacev::vpatch::VPatch;
 use crate::surfacev::vsurface::{
     VSurface, VSurfacePatchAsVs, VSurfacePatchAsVsMut, VSurfacePixel, VSurfacePixelAsVs,
 };
 use facto_loop_miner_fac_engine::common::varea::VArea;
 use facto_loop_miner_fac_engine::common::vpoint::{VPOINT_ONE, VPoint};
-use facto_loop_miner_fac_engine::opencv_re::core::{
-    Point, Rect, ToInputArray, ToInputOutputArray, Vector,
-};
-use facto_loop_miner_fac_engine::opencv_re::imgcodecs::imwrite;
-use facto_loop_miner_fac_engine::opencv_re::imgproc::{
-    CHAIN_APPROX_NONE, LINE_8, RETR_EXTERNAL, bounding_rect, find_contours, rectangle,
-};
-use facto_loop_miner_fac_engine::opencv_re::prelude::*;
 use itertools::Itertools;
 use kiddo::{Manhattan, NearestNeighbour};
-use std::cmp::Ordering;
 use std::collections::HashSet;
 use std::fmt::Display;
-use std::path::Path;
-use tracing::{debug, info, trace};
+use tracing::{debug, info};
 
 // const WRITE_DEBUG_IMAGE: bool = false;
 
-/// For fun, detect resource patches in image with OpenCV.
+/// Detect resource patches in the surface image, originally for fun with OpenCV.
 pub struct Step04 {}
 
 impl Step04 {
@@ -43,11 +36,21 @@ impl Step for Step04 {
         "step04-contours"
     }
 
+    fn tunable_sections(&self) -> &'static [&'static str] {
+        &["patch"]
+    }
+
     fn transformer(&self, params: StepParams) -> XMachineResult<()> {
         let mut surface = VSurface::load_from_last_step(&params)?;
 
-        let disk_patches = detector(surface.pixels(), &params.step_out_dir);
+        let tunables = surface.tunables().patch.clone();
+        let mut disk_patches = detector(surface.pixels(), &tunables);
+        for patch in &mut disk_patches {
+            patch.update_amounts(surface.amounts());
+        }
         surface.patches_mut().add_patches(disk_patches);
+        #[cfg(feature = "opencv_re")]
+        write_patch_debug_images(&surface, &params.step_out_dir);
 
         // if WRITE_DEBUG_IMAGE {
         //     write_surface_with_all_patches_wrapped(&mut surface);
@@ -66,9 +69,16 @@ impl Step for Step04 {
                 max_height = max_height.max(diff.y());
                 total += 1;
             }
+            let total_amount: u64 = surface
+                .patches()
+                .get_patches()
+                .iter()
+                .filter(|patch| patch.resource == pixel)
+                .map(|patch| patch.total_amount)
+                .sum();
             info!(
-                "Resource {:?} patches {} max_width {} max_height {}",
-                pixel, total, max_width, max_height
+                "Resource {:?} patches {} max_width {} max_height {} total amount {}",
+                pixel, total, max_width, max_height, total_amount
             );
 
             // // validate
@@ -142,57 +152,56 @@ impl Step for Step04 {
 //     );
 // }
 
-fn write_png(path: &Path, img: &impl ToInputArray) {
-    debug!("Wrote debug image {}", path.display());
-    imwrite(path.to_str().unwrap(), img, &Vector::new()).unwrap();
-}
-
-fn detector(surface_meta: VSurfacePixel, out_dir: &Path) -> Vec<VPatch> {
+/// Pixels on the outer ring of the surface are never in a patch, see [label_component_areas]
+fn detector(surface_meta: VSurfacePixel, tunables: &PatchTunables) -> Vec<VPatch> {
     let mut patches: Vec<VPatch> = Vec::new();
     for pixel in Pixel::iter_resource() {
-        let detected_patches = detect_pixel(surface_meta, out_dir, pixel);
+        let detected_patches = detect_pixel(surface_meta, pixel, tunables);
         patches.extend(detected_patches.into_iter());
     }
     patches
 }
 
-fn detect_pixel(surface_meta: VSurfacePixel, out_dir: &Path, pixel: Pixel) -> Vec<VPatch> {
+fn detect_pixel(
+    surface_meta: VSurfacePixel,
+    pixel: Pixel,
+    tunables: &PatchTunables,
+) -> Vec<VPatch> {
     surface_meta.log_pixel_stats("detect_pixel");
-    let mut img_gen = surface_meta.get_pixel_cv_image(Some(pixel));
-    let mut img = img_gen.as_mat();
-    let size = img.size().unwrap();
-    debug!(
-        "Read size {}x{} type {}",
-        size.width,
-        size.height,
-        img.typ(),
-    );
-
-    let mut patch_rects = detect_patch_rectangles(&img);
-    debug!("Found {} patch rects", patch_rects.len());
-
-    let patch_corner_cloud = map_patch_corners_to_kdtree(patch_rects.iter());
-    detect_merge_nearby_patches(&mut patch_rects, &patch_corner_cloud);
+    // image coordinates, 0,0 is the top left corner
+    let mut patch_areas = match tunables.detector {
+        PatchDetector::Labeller => label_component_areas(
+            &surface_meta.get_pixel_mask(pixel),
+            surface_meta.get_diameter(),
+            tunables.connectivity,
+        ),
+        #[cfg(feature = "opencv_re")]
+        PatchDetector::OpenCv => {
+            let mut img_gen = surface_meta.get_pixel_cv_image(Some(pixel));
+            detect_contour_areas(&img_gen.as_mat())
+        }
+        #[cfg(not(feature = "opencv_re"))]
+        PatchDetector::OpenCv => unreachable!("rejected by Tunables::validate"),
+    };
+    debug!("Found {} patch areas", patch_areas.len());
 
-    draw_patch_border(&mut img, patch_rects.iter().cloned());
-    let debug_image_name = format!("cv-{}.png", pixel.as_ref());
-    write_png(&out_dir.join(debug_image_name), &img);
+    detect_merge_nearby_patches(&mut patch_areas, tunables.merge_distance);
 
-    patch_rects
+    patch_areas
         .into_iter()
-        .map(|patch_rect| {
+        .map(|patch_area| {
             let radius_offset =
                 VPoint::new(surface_meta.get_radius_i32(), surface_meta.get_radius_i32());
 
-            // debug!("from {patch_rect:?}");
-            let start = VPoint::from_rect_start(&patch_rect) - radius_offset;
+            // debug!("from {patch_area:?}");
+            let start = patch_area.point_top_left() - radius_offset;
             assert!(
                 !surface_meta.is_point_out_of_bounds(&start),
                 "start {start} not in radius {}",
                 surface_meta.get_radius()
             );
             // debug!("start {start:?}");
-            let end = start + VPoint::new(patch_rect.width, patch_rect.height);
+            let end = patch_area.point_bottom_right() + VPOINT_ONE - radius_offset;
             // if patch_rect.width != 1 && patch_rect.height != 1 {
             //     end -= VPOINT_ONE;
             // }
@@ -205,53 +214,6 @@ fn detect_pixel(surface_meta: VSurfacePixel, out_dir: &Path, pixel: Pixel) -> Ve
             // );
             // debug!("end   {end:?}");
 
-            #[cfg(why_no_work)]
-            {
-                let patch_area = VArea::from_arbitrary_points_pair(start, end);
-                let raw_area_points = patch_area.get_points();
-
-                let any_out_of_bounds = raw_area_points
-                    .iter()
-                    .filter(|p| surface_meta.is_point_out_of_bounds(p))
-                    .collect_vec();
-                let mut debug_any = any_out_of_bounds
-                    .iter()
-                    .map(|v| format!("{}", v))
-                    .join(", ");
-                debug_any.truncate(500);
-                assert_eq!(
-                    any_out_of_bounds.len(),
-                    0,
-                    "points out of bounds {debug_any}",
-                );
-
-                let points = raw_area_points
-                    .into_iter()
-                    .filter(|p| surface_meta.get_pixel(p) == pixel)
-                    .collect_vec();
-                assert_ne!(points.len(), 0, "no points found for pixel");
-
-                // recreate bounding area
-                let test_patch_area = VArea::from_arbitrary_points(&points);
-                // assert_eq!(
-                //     test_patch_area, patch_area,
-                //     "cv rect doesn't match found points"
-                // );
-                if test_patch_area != patch_area {
-                    warn!("initial patch rect {:?}", patch_rect);
-                    panic!(
-                        "cv rect doesn't match found points\n\
-                    from : {patch_area:?}\n\
-                    recal: {test_patch_area:?}\n\
-                    from  size: {:?}\n\
-                    recal size: {:?}",
-                        patch_area.as_size(),
-                        test_patch_area.as_size(),
-                    );
-                }
-                VPatch::new(patch_area, pixel, points)
-            }
-
             {
                 // For some reason the opencv patch rects aren't exactly accurate
                 // Instead make a bigger box and shrink to known points via the surface
@@ -273,86 +235,18 @@ fn detect_pixel(surface_meta: VSurfacePixel, out_dir: &Path, pixel: Pixel) -> Ve
         .collect()
 }
 
-fn detect_patch_rectangles(base: &impl ToInputArray) -> Vec<Rect> {
-    let mut contours: Vector<Vector<Point>> = Vector::default();
-    let offset = Point { x: 0, y: 0 };
-    // RETR_LIST - May make rectangles inside rectangles, other multiple rectangles at whisps
-    // RETR_CCOMP - Same???
-    //
-    // CHAIN_APPROX_NONE - Stores all points. unnecessary
-    // CHAIN_APPROX_SIMPLE - Only store corners, which are the relevant points
-    // CHAIN_APPROX_TC89_L1 - Didn't do anything????
-    find_contours(
-        base,
-        &mut contours,
-        RETR_EXTERNAL,
-        // RETR_LIST,
-        CHAIN_APPROX_NONE,
-        offset,
-    )
-    .unwrap();
-
-    tracing::debug!("found contours {}", contours.len());
-    // metricify("contour sizes", contours.iter().map(|v| v.len()));
-
-    // let first_debug = contours.get(0).unwrap();
-    // tracing::debug!(
-    //     "countour 1 {}",
-    //     first_debug
-    //         .iter()
-    //         .map(|point| format!("{}x{}", point.x, point.y))
-    //         .join(", ")
-    // );
-
-    let rects: Vec<Rect> = contours
-        .into_iter()
-        .map(|contour| bounding_rect(&contour).unwrap())
-        .collect();
-
-    rects
-}
-
 /// Merge, for example Oil wells patches into a single Oil patch.
-fn detect_merge_nearby_patches(patch_rects: &mut Vec<Rect>, cloud: &PixelKdTree) {
-    const SEARCH_UNIT: u32 = 25;
-
-    // Query kdtree for all nearby points for every point
-    let mut search_distance = SEARCH_UNIT;
-    let mut last_nearby_points_count = 0;
-    let mut empty_count = 0;
-    let mut within_search: Vec<Vec<NearestNeighbour<_, _>>>;
-    loop {
-        within_search = patch_rects
-            .iter()
-            .map(|patch_rect| {
-                cloud.within::<Manhattan>(
-                    &VPoint::from_rect_start(patch_rect).to_slice_f32(),
-                    search_distance as f32,
-                )
-            })
-            .collect();
-
-        let nearby_points_count = within_search.len();
-        match last_nearby_points_count.cmp(&nearby_points_count) {
-            Ordering::Less => {
-                trace!(
-                    "increasing search distance to {} because found from {} to {} points",
-                    search_distance, last_nearby_points_count, nearby_points_count
-                );
-                last_nearby_points_count = nearby_points_count;
-            }
-            Ordering::Equal | Ordering::Greater => {
-                if empty_count < 4 {
-                    empty_count += 1;
-                    trace!("empty {}", empty_count);
-                } else {
-                    trace!("too many empty");
-                    break;
-                }
-            }
-        }
-        search_distance += SEARCH_UNIT;
-    }
+fn detect_merge_nearby_patches(patch_areas: &mut Vec<VArea>, merge_distance: u32) {
+    let cloud = map_patch_corners_to_kdtree(patch_areas.iter());
+    let within_search: Vec<Vec<NearestNeighbour<_, _>>> = patch_areas
+        .iter()
+        .map(|patch_area| {
+            cloud.within::<Manhattan>(
+                &patch_area.point_top_left().to_slice_f32(),
+                merge_distance as f32,
+            )
+        })
+        .collect();
 
     // Combine nearby points into groups
     let mut within_index_groups: Vec<HashSet<usize>> = Vec::new();
@@ -378,28 +272,28 @@ fn detect_merge_nearby_patches(patch_rects: &mut Vec<Rect>, cloud: &PixelKdTree)
     }
     debug!("made {} groups to merge", within_index_groups.len());
 
-    // push new groups as a bigger rect containing the points
+    // push new groups as a bigger area containing the points
     for group in &within_index_groups {
-        let super_rect =
-            combine_rects_into_big_rect(group.iter().map(|rect_index| &patch_rects[*rect_index]));
-        patch_rects.push(super_rect);
+        let super_area =
+            combine_areas_into_big_area(group.iter().map(|area_index| &patch_areas[*area_index]));
+        patch_areas.push(super_area);
     }
 
     // remove old patch groups
     for pos in within_index_groups.iter().flatten().sorted().unique().rev() {
-        patch_rects.remove(*pos);
+        patch_areas.remove(*pos);
     }
 }
 
 /// Merge, for example Oil wells patches into a single Oil patch.
 fn detect_merge_nearby_patches_slow(
-    patch_rects: &mut Vec<Rect>,
+    patch_areas: &mut Vec<VArea>,
     cloud: &PixelKdTree,
     pixel: &Pixel,
 ) {
     let mut search_square_size = 0;
     // find largest size
-    // for patch in patch_rects.iter() {
+    // for patch in patch_areas.iter() {
     //     search_square_size = search_square_size.max(patch.width);
     //     search_square_size = search_square_size.max(patch.height);
     // }
@@ -407,11 +301,11 @@ fn detect_merge_nearby_patches_slow(
     // arbitrary size, for some reason within 1 diameter for IronOre still finds max 5...
     search_square_size += pixel.nearby_patch_search_distance(search_square_size);
 
-    let within_search: Vec<Vec<NearestNeighbour<_, _>>> = patch_rects
+    let within_search: Vec<Vec<NearestNeighbour<_, _>>> = patch_areas
         .iter()
-        .map(|patch_rect| {
+        .map(|patch_area| {
             cloud.within::<Manhattan>(
-                &VPoint::from_rect_start(patch_rect).to_slice_f32(),
+                &patch_area.point_top_left().to_slice_f32(),
                 search_square_size as f32,
             )
         })
@@ -422,8 +316,8 @@ fn detect_merge_nearby_patches_slow(
         within_search.iter().map(|input| input.len().to_string()),
     );
 
-    let mut combine_replacements: Vec<Rect> = Vec::new();
-    let mut combine_mask: Vec<bool> = vec![false; patch_rects.len()];
+    let mut combine_replacements: Vec<VArea> = Vec::new();
+    let mut combine_mask: Vec<bool> = vec![false; patch_areas.len()];
     'search: for within in &within_search {
         if within.len() <= 1 {
             continue;
@@ -438,69 +332,46 @@ fn detect_merge_nearby_patches_slow(
             combine_mask[neighbor.item] = true;
         }
 
-        let nearby_rects: Vec<Rect> = within
-            .iter()
-            .map(|neighbor| patch_rects[neighbor.item])
-            .collect();
-
-        // DIY...
-        // let mut super_rect = Rect::default();
-        // for nearby_rect in nearby_rects {
-        //     super_rect.x = super_rect.x.min(nearby_rect.x);
-        //     super_rect.y = super_rect.y.min(nearby_rect.y);
-        //     // warning: type abuse
-        //     super_rect.x = super_rect.x.min(nearby_rect.x);
-        // }
-
-        // lazy opencv way
-        let super_rect = combine_rects_into_big_rect(&nearby_rects);
-
-        // tracing::debug!("====");
-        // tracing::debug!(
-        //     "for {}",
-        //     (&nearby_rects)
-        //         .iter()
-        //         .map(|rect| rect_to_string((rect)))
-        //         .join(", ")
-        // );
-        // tracing::debug!("got super {}", rect_to_string(&super_rect));
-
-        combine_replacements.push(super_rect);
+        let super_area =
+            combine_areas_into_big_area(within.iter().map(|neighbor| &patch_areas[neighbor.item]));
+        combine_replacements.push(super_area);
     }
 
-    tracing::debug!("patches init {}", patch_rects.len());
+    tracing::debug!("patches init {}", patch_areas.len());
     for (pos, mask) in combine_mask.iter().enumerate().rev() {
         if *mask {
-            patch_rects.remove(pos);
+            patch_areas.remove(pos);
         }
     }
-    tracing::debug!("patches with mergeable removed {}", patch_rects.len());
+    tracing::debug!("patches with mergeable removed {}", patch_areas.len());
 
-    // patch_rects.clear();
-    for super_rect in combine_replacements {
-        patch_rects.push(super_rect);
+    // patch_areas.clear();
+    for super_area in combine_replacements {
+        patch_areas.push(super_area);
     }
-    tracing::debug!("patches with merge replacements {}", patch_rects.len());
+    tracing::debug!("patches with merge replacements {}", patch_areas.len());
 }
 
-fn draw_patch_border(img: &mut impl ToInputOutputArray, rects: impl Iterator<Item = Rect>) {
-    for rect in rects {
-        rectangle(img, rect, Pixel::Highlighter.scalar_cv(), 2, LINE_8, 0).unwrap();
+/// Bounding area of the corners, one past the bottom right like the old OpenCV rects
+fn combine_areas_into_big_area<'a>(areas: impl IntoIterator<Item = &'a VArea>) -> VArea {
+    let mut corners: Vec<VPoint> = Vec::new();
+    for area in areas {
+        corners.push(area.point_top_left());
+        corners.push(area.point_bottom_right() + VPOINT_ONE);
     }
+    VArea::from_arbitrary_points(&corners)
 }
 
-pub fn map_patch_corners_to_kdtree<'a>(patch_rects: impl Iterator<Item = &'a Rect>) -> PixelKdTree {
+pub fn map_patch_corners_to_kdtree<'a>(
+    patch_areas: impl Iterator<Item = &'a VArea>,
+) -> PixelKdTree {
     let mut tree: PixelKdTree = PixelKdTree::new();
-    for (patch_counter, patch_rect) in patch_rects.enumerate() {
-        tree.add(&map_rect_corner_to_slice(patch_rect), patch_counter);
+    for (patch_counter, patch_area) in patch_areas.enumerate() {
+        tree.add(&patch_area.point_top_left().to_slice_f32(), patch_counter);
     }
     tree
 }
 
-pub fn map_rect_corner_to_slice(rect: &Rect) -> [f32; 2] {
-    [rect.x as f32, rect.y as f32]
-}
-
 #[allow(dead_code)]
 fn metricify<I, T>(name: &str, input: I)
 where
@@ -516,10 +387,81 @@ where
     }
 }
 
-#[allow(dead_code)]
-fn rect_to_string(rect: &Rect) -> String {
-    format!(
-        "h {} w {} x {} y {}",
-        rect.height, rect.width, rect.x, rect.y
-    )
+#[cfg(test)]
+mod test {
+    use crate::state::machine_v1::step04_contours::detector;
+    use crate::state::tuneables::PatchTunables;
+    use crate::surface::pixel::Pixel;
+    use crate::surfacev::vpatch::VPatch;
+    use crate::surfacev::vsurface::{VSurface, VSurfacePixelAsVs, VSurfacePixelAsVsMut};
+    use facto_loop_miner_fac_engine::common::varea::VArea;
+    use facto_loop_miner_fac_engine::common::vpoint::VPoint;
+
+    fn area(start: (i32, i32), end: (i32, i32)) -> VArea {
+        VArea::from_arbitrary_points_pair(VPoint::new(start.0, start.1), VPoint::new(end.0, end.1))
+    }
+
+    fn resource_areas(patches: &[VPatch], resource: Pixel) -> Vec<(VArea, usize)> {
+        let mut areas: Vec<(VArea, usize)> = patches
+            .iter()
+            .filter(|patch| patch.resource == resource)
+            .map(|patch| (patch.area.clone(), patch.pixel_indexes.len()))
+            .collect();
+        areas.sort();
+        areas
+    }
+
+    #[test]
+    fn test_detector() {
+        let mut surface = VSurface::new(50);
+        for (resource, block) in [
+            (Pixel::IronOre, area((-30, -30), (-27, -27))),
+            (Pixel::IronOre, area((-24, -30), (-21, -27))),
+            (Pixel::IronOre, area((20, 20), (23, 23))),
+            (Pixel::CopperOre, area((0, 0), (2, 2))),
+            // outer ring
+            (Pixel::IronOre, area((-50, 0), (-50, 0))),
+        ] {
+            surface
+                .pixels_mut()
+                .change_pixels(block.get_points())
+                .stomp(resource);
+        }
+
+        let separate = detector(
+            surface.pixels(),
+            &PatchTunables {
+                merge_distance: 0,
+                ..PatchTunables::default()
+            },
+        );
+        assert_eq!(separate.len(), 4);
+        assert_eq!(
+            resource_areas(&separate, Pixel::IronOre),
+            [
+                (area((-30, -30), (-27, -27)), 16),
+                (area((-24, -30), (-21, -27)), 16),
+                (area((20, 20), (23, 23)), 16),
+            ]
+        );
+        assert_eq!(
+            resource_areas(&separate, Pixel::CopperOre),
+            [(area((0, 0), (2, 2)), 9)]
+        );
+
+        let merged = detector(
+            surface.pixels(),
+            &PatchTunables {
+                merge_distance: 10,
+                ..PatchTunables::default()
+            },
+        );
+        assert_eq!(
+            resource_areas(&merged, Pixel::IronOre),
+            [
+                (area((-30, -30), (-21, -27)), 32),
+                (area((20, 20), (23, 23)), 16),
+            ]
+        );
+    }
 }
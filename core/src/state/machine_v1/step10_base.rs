@@ -22,6 +22,10 @@ impl Step for Step10 {
         "step10-base"
     }
 
+    fn tunable_sections(&self) -> &'static [&'static str] {
+        &["base"]
+    }
+
     fn transformer(&self, params: StepParams) -> XMachineResult<()> {
         let mut surface = VSurface::load_from_last_step(&params)?;
         let tunables = &surface.tunables().base.clone();
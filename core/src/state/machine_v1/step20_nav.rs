@@ -1,12 +1,9 @@
-use crate::navigator::planners::altare::start_altare_planner;
-// use crate::navigator::planners::altare::start_altare_planner;
-use crate::navigator::planners::PathingTunables;
-use crate::navigator::planners::debugplan::start_debug_planner;
-use crate::navigator::planners::ruze::start_ruze_planner;
+use crate::navigator::plan_aggregation_outposts;
+use crate::navigator::planners::{PathingTunables, PlannerReport, planner_by_name};
 use crate::state::err::XMachineResult;
 use crate::state::machine::{Step, StepParams};
-use crate::surfacev::vsurface::VSurface;
-use crate::surfacev::vsurface::{VSurfaceNavAsVsMut, VSurfacePatchAsVsMut};
+use crate::surfacev::vsurface::VSurfaceNavAsVsMut;
+use crate::surfacev::vsurface::{PlanOverlay, VSurface};
 
 pub(crate) struct Step20;
 
@@ -21,19 +18,22 @@ impl Step for Step20 {
         "step20-nav"
     }
 
+    fn tunable_sections(&self) -> &'static [&'static str] {
+        &["base", "mori", "nav"]
+    }
+
     fn transformer(&self, params: StepParams) -> XMachineResult<()> {
         let mut surface = VSurface::load_from_last_step(&params)?;
-        let tunables = PathingTunables::from_tunables(surface.tunables());
-        // surface.validate();
+        let tunables =
+            PathingTunables::from_tunables(surface.tunables(), params.paint_sink.clone());
+        let planner = planner_by_name(&surface.tunables().nav.planner)?;
 
-        match 2 {
-            1 => start_ruze_planner(&tunables, &mut surface.nav_mut()),
-            2 => start_altare_planner(&tunables, &mut surface.nav_mut()),
-            9 => start_debug_planner(&tunables, &mut surface.patches_mut()),
-            _ => unimplemented!(),
-        }
+        plan_aggregation_outposts(&tunables, &mut surface.nav_mut());
+        let report = PlannerReport::run(planner.as_ref(), &tunables, &mut surface.nav_mut());
 
         surface.save(&params.step_out_dir)?;
+        report.save(&params.step_out_dir)?;
+        PlanOverlay::new(&surface).save(&params.step_out_dir)?;
 
         Ok(())
     }
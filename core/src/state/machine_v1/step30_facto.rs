@@ -2,7 +2,8 @@ use crate::state::err::XMachineResult;
 use crate::state::machine::{Step, StepParams};
 use crate::surfacev::mine::{MineLocation, MinePath};
 use crate::surfacev::vsurface::{
-    VSurface, VSurfacePatch, VSurfacePatchAsVs, VSurfacePixel, VSurfacePixelAsVs, VSurfaceRailAsVs,
+    VSurface, VSurfaceEntityAsVs, VSurfaceEntityAsVsMut, VSurfacePatch, VSurfacePatchAsVs,
+    VSurfacePixel, VSurfacePixelAsVs, VSurfaceRailAsVs,
 };
 use facto_loop_miner_common::err_bt::PrettyUnwrapMyBacktrace;
 use facto_loop_miner_fac_engine::admiral::err::AdmiralResult;
@@ -11,8 +12,10 @@ use facto_loop_miner_fac_engine::admiral::executor::client::AdmiralClient;
 use facto_loop_miner_fac_engine::admiral::lua_command::LuaCommand;
 use facto_loop_miner_fac_engine::admiral::lua_command::fac_destroy::FacDestroy;
 use facto_loop_miner_fac_engine::admiral::lua_command::fac_render_destroy::FacRenderDestroy;
+use facto_loop_miner_fac_engine::blueprint::bpfac::tile::FacBpTile;
 use facto_loop_miner_fac_engine::blueprint::output::FacItemOutput;
 use facto_loop_miner_fac_engine::common::names::FacEntityNameBuilder;
+use facto_loop_miner_fac_engine::common::names_tile::FacTileName;
 use facto_loop_miner_fac_engine::common::varea::VArea;
 use facto_loop_miner_fac_engine::common::vpoint::{VPOINT_ZERO, VPoint};
 use facto_loop_miner_fac_engine::game_blocks::block::FacBlockFancy;
@@ -37,7 +40,7 @@ impl Step for Step30 {
     }
 
     fn transformer(&self, params: StepParams) -> XMachineResult<()> {
-        let surface_raw = VSurface::load_from_last_step(&params)?;
+        let mut surface_raw = VSurface::load_from_last_step(&params)?;
 
         let output = connect_admiral().pretty_unwrap();
 
@@ -46,6 +49,14 @@ impl Step for Step30 {
 
         output.flush();
 
+        surface_raw
+            .entities_mut()
+            .place_output(output.take_placed());
+        for (name, count) in surface_raw.entities().bill_of_materials() {
+            info!("Placed {count} {name}");
+        }
+        surface_raw.save(&params.step_out_dir)?;
+
         Ok(())
     }
 }
@@ -53,7 +64,9 @@ impl Step for Step30 {
 fn connect_admiral() -> AdmiralResult<Rc<FacItemOutput>> {
     let mut client = AdmiralClient::new()?;
     client.auth()?;
-    Ok(FacItemOutput::new_admiral_dedupe(client).into_rc())
+    Ok(FacItemOutput::new_admiral_dedupe(client)
+        .with_placed_recording()
+        .into_rc())
 }
 
 fn plotter(
@@ -109,6 +122,8 @@ fn plotter(
     //     .generate();
     // }
 
+    place_landfill(needle_path, &output);
+
     FacBlkMineIsland {
         rail_entrance_link: needle_path.sodas.last().unwrap().clone(),
         wagons: 3,
@@ -127,6 +142,13 @@ fn plotter(
     Ok(())
 }
 
+/// Rails can only be placed on land
+fn place_landfill(mine_path: &MinePath, output: &FacItemOutput) {
+    for point in &mine_path.landfill {
+        output.write_tile(FacBpTile::new(FacTileName::Landfill, *point));
+    }
+}
+
 fn destroy_everything(
     surface: VSurfacePixel,
     output: &FacItemOutput,
@@ -1,5 +1,6 @@
 pub mod disk;
 mod err;
+mod fingerprint;
 pub mod machine;
 pub mod machine_v1;
 pub mod tuneables;
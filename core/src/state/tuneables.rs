@@ -1,36 +1,253 @@
-use crate::navigator::MoriCostMode;
 use crate::TILES_PER_CHUNK;
+use crate::navigator::MoriCostMode;
+use crate::navigator::planners::planner_by_name;
+use crate::surface::pixel::Pixel;
+use crate::surfacev::err::{CoreConvertPathResult, VError, VResult};
+use crate::surfacev::patch_labeller::Connectivity;
+use facto_loop_miner_common::err_utils::xbt;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs::read_to_string;
+use std::path::{Path, PathBuf};
+use tracing::info;
+
+/// Searched in the work dir. A copied `tuning-params.json` is a valid `tunables.json`
+const TUNABLES_FILE_NAMES: [&str; 2] = ["tunables.toml", "tunables.json"];
 
-#[derive(Clone, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct Tunables {
     pub crop: CropTunables,
+    pub patch: PatchTunables,
     pub base: BaseTunables,
     pub mori: MoriTunables,
+    pub nav: NavTunables,
+    pub import: ImportTunables,
+    pub save: SaveTunables,
 }
 
 impl Tunables {
     pub fn new() -> Self {
         Self {
             crop: CropTunables::new(),
+            patch: PatchTunables::new(),
             base: BaseTunables::new(),
             mori: MoriTunables::new(),
+            nav: NavTunables::new(),
+            import: ImportTunables::new(),
+            save: SaveTunables::new(),
+        }
+    }
+
+    /// Defaults, overwritten by the tunables file, overwritten by `section.field=value` overrides
+    pub fn load(work_dir: &Path, file: Option<&Path>, overrides: &[String]) -> VResult<Self> {
+        let path = match file {
+            Some(file) => Some(file.to_path_buf()),
+            None => TUNABLES_FILE_NAMES
+                .iter()
+                .map(|name| work_dir.join(name))
+                .find(|path| path.exists()),
+        };
+        let mut tunables = match &path {
+            Some(path) => Self::load_file(path)?,
+            None => {
+                info!("No tunables file in {}, using defaults", work_dir.display());
+                Self::new()
+            }
+        };
+
+        if !overrides.is_empty() {
+            let mut value = serde_json::to_value(&tunables).unwrap();
+            for entry in overrides {
+                apply_override(&mut value, entry)?;
+            }
+            tunables = serde_json::from_value(value).map_err(|e| VError::InvalidTunables {
+                reason: format!("overrides {} {e}", overrides.join(",")),
+                backtrace: xbt(),
+            })?;
+        }
+
+        tunables.validate()?;
+        info!("Tunables {:?}", tunables);
+        Ok(tunables)
+    }
+
+    fn load_file(path: &Path) -> VResult<Self> {
+        info!("Loading tunables from {}", path.display());
+        let raw = read_to_string(path).convert(path)?;
+        let result = if path.extension().is_some_and(|v| v == "toml") {
+            toml::from_str(&raw).map_err(|e| e.to_string())
+        } else {
+            serde_json::from_str(&raw).map_err(|e| e.to_string())
+        };
+        result.map_err(|e| VError::InvalidTunables {
+            reason: format!("{} {e}", path.display()),
+            backtrace: xbt(),
+        })
+    }
+
+    pub fn validate(&self) -> VResult<()> {
+        let mut problems = Vec::new();
+        if self.crop.radius == 0 {
+            problems.push("crop.radius must be positive".to_string());
+        }
+        if self.patch.detector == PatchDetector::OpenCv && !cfg!(feature = "opencv_re") {
+            problems.push("patch.detector OpenCv needs the opencv_re feature".to_string());
+        }
+        if self.base.base_chunks.0 == 0 {
+            problems.push("base.base_chunks must be positive".to_string());
+        }
+        if self.base.resource_clear_chunks.0 < self.base.base_chunks.0 {
+            problems.push("base.resource_clear_chunks smaller than base.base_chunks".to_string());
+        }
+        if self.base.resource_clear_chunks.as_tiles_u32() >= self.crop.radius {
+            problems.push(format!(
+                "base.resource_clear_chunks {} tiles outside crop.radius {}",
+                self.base.resource_clear_chunks.as_tiles(),
+                self.crop.radius
+            ));
         }
+        if self.mori.straight_section_size == 0 {
+            problems.push("mori.straight_section_size must be positive".to_string());
+        }
+        if self.mori.crop_radius == 0 {
+            problems.push("mori.crop_radius must be positive".to_string());
+        }
+        if u8::try_from(self.mori.shift_max_sodas).is_err() {
+            problems.push("mori.shift_max_sodas must be at most 255".to_string());
+        }
+        let obstacles = &self.mori.obstacles;
+        if matches!(
+            obstacles.water,
+            ObstaclePolicy::Deconstruct { .. } | ObstaclePolicy::Ignore
+        ) {
+            problems.push("mori.obstacles.water must be Blocked or Landfill".to_string());
+        }
+        if [
+            &obstacles.cliff,
+            &obstacles.rock,
+            &obstacles.tree,
+            &obstacles.player_entity,
+        ]
+        .iter()
+        .any(|v| matches!(v, ObstaclePolicy::Landfill { .. }))
+        {
+            problems.push("mori.obstacles Landfill is only for water".to_string());
+        }
+        if self.import.rcon_radius_chunks == 0
+            || self.import.rcon_page_chunks == 0
+            || self.import.rcon_response_bytes == 0
+        {
+            problems.push("import.rcon_* sizes must be positive".to_string());
+        }
+        if planner_by_name(&self.nav.planner).is_err() {
+            problems.push(format!("nav.planner {} unknown", self.nav.planner));
+        }
+        let aggregation = &self.nav.aggregation;
+        if aggregation.min_cluster_mines < 2 {
+            problems.push("nav.aggregation.min_cluster_mines must be at least 2".to_string());
+        }
+        if aggregation.max_cluster_mines < aggregation.min_cluster_mines {
+            problems.push(
+                "nav.aggregation.max_cluster_mines smaller than min_cluster_mines".to_string(),
+            );
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(VError::InvalidTunables {
+                reason: problems.join(", "),
+                backtrace: xbt(),
+            })
+        }
+    }
+}
+
+impl Default for Tunables {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Parse `mori.turn_cost_unit=3` . Value is JSON, or a plain string for enums
+fn apply_override(root: &mut Value, entry: &str) -> VResult<()> {
+    let invalid = |reason: &str| VError::InvalidTunables {
+        reason: format!("override {entry} {reason}"),
+        backtrace: xbt(),
+    };
+    let (key, raw_value) = entry
+        .split_once('=')
+        .ok_or_else(|| invalid("expected key=value"))?;
+
+    let mut target = root;
+    for part in key.trim().split('.') {
+        target = target.get_mut(part).ok_or_else(|| invalid("unknown key"))?;
     }
+    if target.is_object() {
+        return Err(invalid("key is a section"));
+    }
+
+    let raw_value = raw_value.trim();
+    *target = serde_json::from_str(raw_value).unwrap_or_else(|_| Value::from(raw_value));
+    Ok(())
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct CropTunables {
-    radius: usize,
+    pub radius: u32,
 }
 
 impl CropTunables {
     fn new() -> Self {
-        Self { radius: 1000 }
+        Self { radius: 3000 }
+    }
+}
+
+impl Default for CropTunables {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PatchTunables {
+    pub detector: PatchDetector,
+    /// Only used by `PatchDetector::Labeller`, OpenCV is always `Eight`
+    pub connectivity: Connectivity,
+    /// Patches with top left corners within this Manhattan distance become one patch,
+    /// eg the separate wells of an Oil field
+    pub merge_distance: u32,
+}
+
+impl PatchTunables {
+    fn new() -> Self {
+        Self {
+            detector: PatchDetector::Labeller,
+            connectivity: Connectivity::Eight,
+            merge_distance: 150,
+        }
     }
 }
 
+impl Default for PatchTunables {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum PatchDetector {
+    /// Pure Rust connected components
+    Labeller,
+    /// OpenCV `find_contours`, needs the opencv_re feature
+    OpenCv,
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct BaseTunables {
     pub base_chunks: ChunkValue,
     pub resource_clear_chunks: ChunkValue,
@@ -45,17 +262,27 @@ impl BaseTunables {
     }
 }
 
+impl Default for BaseTunables {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct MoriTunables {
     pub straight_section_size: usize,
     pub cost_mode: MoriCostMode,
     pub straight_cost_unit: u32,
     pub turn_cost_unit: u32,
-    pub multi_turn_lookback: usize,
-    pub multi_turn_cost_unit: u32,
-    pub direction_cost_unit: u32,
+    /// Per soda forward, a shift covers `length + 1`
+    pub shift_cost_unit: u32,
+    /// Widest shift successor in sodas sideways, 0 disables shifts
+    pub shift_max_sodas: usize,
+    /// Percent added to a link cost at `crop_radius` from y = 0, scaled by distance
     pub axis_cost_unit: u32,
     pub crop_radius: u32,
+    pub obstacles: ObstacleTunables,
 }
 
 impl MoriTunables {
@@ -65,16 +292,214 @@ impl MoriTunables {
             cost_mode: MoriCostMode::Complete,
             straight_cost_unit: 1,
             turn_cost_unit: 2,
-            multi_turn_lookback: usize::MAX,
-            // todo: turn cost unit might be better
-            multi_turn_cost_unit: 0,
-            direction_cost_unit: 10,
-            axis_cost_unit: 5,
+            shift_cost_unit: 2,
+            shift_max_sodas: 2,
+            axis_cost_unit: 100,
             crop_radius: 1000,
+            obstacles: ObstacleTunables::new(),
         }
     }
 }
 
+impl Default for MoriTunables {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// How Mori treats each obstacle `Pixel` kind
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ObstacleTunables {
+    pub cliff: ObstaclePolicy,
+    pub rock: ObstaclePolicy,
+    pub tree: ObstaclePolicy,
+    pub player_entity: ObstaclePolicy,
+    /// Only `Blocked` or `Landfill`
+    pub water: ObstaclePolicy,
+}
+
+impl ObstacleTunables {
+    fn new() -> Self {
+        Self {
+            cliff: ObstaclePolicy::Blocked,
+            rock: ObstaclePolicy::Deconstruct { cost: 20 },
+            tree: ObstaclePolicy::Deconstruct { cost: 5 },
+            player_entity: ObstaclePolicy::Blocked,
+            water: ObstaclePolicy::Blocked,
+        }
+    }
+
+    /// None for non-obstacles
+    pub fn policy(&self, pixel: Pixel) -> Option<&ObstaclePolicy> {
+        match pixel {
+            Pixel::Cliff => Some(&self.cliff),
+            Pixel::Rock => Some(&self.rock),
+            Pixel::Tree => Some(&self.tree),
+            Pixel::PlayerEntity => Some(&self.player_entity),
+            Pixel::Water => Some(&self.water),
+            _ => None,
+        }
+    }
+}
+
+impl Default for ObstacleTunables {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ObstaclePolicy {
+    Blocked,
+    /// Rail may be built over it. Cost is added per tile removed
+    Deconstruct {
+        cost: u32,
+    },
+    /// Rail is built over it for free
+    Ignore,
+    /// Water only. Rail may be built after filling it in. Cost is added per tile filled
+    Landfill {
+        cost: u32,
+    },
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NavTunables {
+    /// `Planner::name` used by step20
+    pub planner: String,
+    pub aggregation: AggregationTunables,
+}
+
+impl NavTunables {
+    fn new() -> Self {
+        Self {
+            planner: "altare".to_string(),
+            aggregation: AggregationTunables::new(),
+        }
+    }
+}
+
+impl Default for NavTunables {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Clusters of small remote mines feed an outpost, which alone is routed to the base
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AggregationTunables {
+    pub enabled: bool,
+    /// Mines with a smaller `area_min` are small
+    pub max_mine_tiles: u32,
+    /// Bird distance from 0,0 where mines become remote
+    pub min_base_distance: u32,
+    /// Bird distance between mine centers in one cluster
+    pub cluster_radius: u32,
+    pub min_cluster_mines: usize,
+    pub max_cluster_mines: usize,
+}
+
+impl AggregationTunables {
+    fn new() -> Self {
+        Self {
+            enabled: false,
+            max_mine_tiles: 96 * 96,
+            min_base_distance: 1500,
+            cluster_radius: 400,
+            min_cluster_mines: 2,
+            max_cluster_mines: 6,
+        }
+    }
+}
+
+impl Default for AggregationTunables {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ImportTunables {
+    /// Directory of scanner export files, relative to the work dir
+    pub input_dir: PathBuf,
+    /// Trees are a large fraction of obstacles. Skipped unless enabled
+    pub trees: bool,
+    pub source: ImportSource,
+    /// Area scanned around 0,0 with `ImportSource::Rcon`
+    pub rcon_radius_chunks: u32,
+    /// Chunks per side of each RCON request
+    pub rcon_page_chunks: u32,
+    /// Page JSON is read in pieces of this size. RCON packets carry at most 4096 bytes
+    pub rcon_response_bytes: u32,
+}
+
+impl ImportTunables {
+    fn new() -> Self {
+        Self {
+            input_dir: PathBuf::from("lm-artful"),
+            trees: false,
+            source: ImportSource::Files,
+            rcon_radius_chunks: 125,
+            rcon_page_chunks: 16,
+            rcon_response_bytes: 4000,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ImportSource {
+    /// Scanner mod export files in `input_dir`
+    Files,
+    /// Running server on the admiral RCON port
+    Rcon,
+}
+
+impl Default for ImportTunables {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SaveTunables {
+    /// Also write `vsurface-state.json` for debugging
+    pub json_state: bool,
+    /// Check surface invariants and write `surface-validation.json`
+    pub validate: bool,
+    /// Where planners send progress paintings
+    pub paint_sink: PaintSink,
+}
+
+impl SaveTunables {
+    fn new() -> Self {
+        Self {
+            json_state: false,
+            validate: false,
+            paint_sink: PaintSink::Disabled,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum PaintSink {
+    Disabled,
+    /// Numbered `frame-000000.png` files in this directory, relative to the work dir
+    Frames(PathBuf),
+    /// One PNG per connection to a `host:port` listener, eg scripts/images-to-video-listen.sh
+    Tcp(String),
+}
+
+impl Default for SaveTunables {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 /// A Factorio chunk
 #[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 #[serde(transparent)]
@@ -94,3 +519,76 @@ impl ChunkValue {
         self.as_tiles() as i32
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::state::tuneables::{ObstaclePolicy, Tunables, apply_override};
+    use crate::surface::pixel::Pixel;
+
+    #[test]
+    fn test_override() {
+        let mut value = serde_json::to_value(Tunables::new()).unwrap();
+        apply_override(&mut value, "mori.turn_cost_unit=7").unwrap();
+        apply_override(&mut value, "mori.cost_mode=DistanceManhattanOnly").unwrap();
+        apply_override(&mut value, "crop.radius = 1500").unwrap();
+        let tunables: Tunables = serde_json::from_value(value.clone()).unwrap();
+        assert_eq!(tunables.mori.turn_cost_unit, 7);
+        assert_eq!(tunables.crop.radius, 1500);
+        tunables.validate().unwrap();
+
+        assert!(apply_override(&mut value, "mori.nope=1").is_err());
+        assert!(apply_override(&mut value, "mori=1").is_err());
+    }
+
+    #[test]
+    fn test_partial_toml() {
+        let tunables: Tunables = toml::from_str("[base]\nbase_chunks = 3\n").unwrap();
+        assert_eq!(tunables.base.base_chunks.as_tiles(), 96);
+        assert_eq!(tunables.crop.radius, Tunables::new().crop.radius);
+        assert!(toml::from_str::<Tunables>("[base]\ntypo = 3\n").is_err());
+    }
+
+    #[test]
+    fn test_obstacle_policy() {
+        let tunables: Tunables = toml::from_str(
+            "[mori.obstacles]\ncliff = { Deconstruct = { cost = 50 } }\ntree = \"Ignore\"\n",
+        )
+        .unwrap();
+        let obstacles = &tunables.mori.obstacles;
+        assert_eq!(
+            obstacles.policy(Pixel::Cliff),
+            Some(&ObstaclePolicy::Deconstruct { cost: 50 })
+        );
+        assert_eq!(obstacles.policy(Pixel::Tree), Some(&ObstaclePolicy::Ignore));
+        assert_eq!(obstacles.policy(Pixel::Coal), None);
+
+        let mut value = serde_json::to_value(Tunables::new()).unwrap();
+        apply_override(&mut value, "mori.obstacles.rock=Blocked").unwrap();
+        let tunables: Tunables = serde_json::from_value(value).unwrap();
+        assert_eq!(
+            tunables.mori.obstacles.policy(Pixel::Rock),
+            Some(&ObstaclePolicy::Blocked)
+        );
+    }
+
+    #[test]
+    fn test_water_policy() {
+        let mut tunables: Tunables = toml::from_str(
+            "[mori.obstacles]
+water = { Landfill = { cost = 30 } }
+",
+        )
+        .unwrap();
+        assert_eq!(
+            tunables.mori.obstacles.policy(Pixel::Water),
+            Some(&ObstaclePolicy::Landfill { cost: 30 })
+        );
+        tunables.validate().unwrap();
+
+        tunables.mori.obstacles.water = ObstaclePolicy::Ignore;
+        assert!(tunables.validate().is_err());
+        tunables.mori.obstacles.water = ObstaclePolicy::Blocked;
+        tunables.mori.obstacles.rock = ObstaclePolicy::Landfill { cost: 30 };
+        assert!(tunables.validate().is_err());
+    }
+}
@@ -1,4 +1,5 @@
 use crate::surfacev::err::{VError, VResult};
+#[cfg(feature = "opencv_re")]
 use facto_loop_miner_fac_engine::opencv_re::core::{Scalar, Vec3b};
 use image::codecs::png::PngEncoder;
 use image::{ExtendedColorType, ImageEncoder};
@@ -38,6 +39,12 @@ pub enum Pixel {
     //
     SteelChest = 70,
     //
+    Cliff = 80,
+    Rock = 81,
+    Tree = 82,
+    /// Anything built by the player force
+    PlayerEntity = 83,
+    //
     EdgeWall = 200,
     Rail = 225,
     Highlighter = 250,
@@ -65,6 +72,11 @@ impl Pixel {
             //
             Pixel::SteelChest => [0x5c, 0x60, 0x66], //grey?
             //
+            Pixel::Cliff => [0x8b, 0x5a, 0x2b],
+            Pixel::Rock => [0x9e, 0x8e, 0x7e],
+            Pixel::Tree => [0x2e, 0x7d, 0x32],
+            Pixel::PlayerEntity => [0xe0, 0x40, 0xfb],
+            //
             Pixel::Empty => [0x00, 0x00, 0x00],
             Pixel::EdgeWall => [0xBD, 0x5F, 0x5F],
             Pixel::Rail => [0xB9, 0x7A, 0x57],
@@ -74,6 +86,17 @@ impl Pixel {
         }
     }
 
+    /// Zoomed out map tiles merge a block into one pixel. A thin pixel with priority wins over
+    /// the rest of the block so rails stay visible, otherwise the most common pixel wins
+    pub const fn downsample_priority(&self) -> u8 {
+        match self {
+            Pixel::Highlighter => 3,
+            Pixel::Rail => 2,
+            Pixel::EdgeWall => 1,
+            _ => 0,
+        }
+    }
+
     pub fn from_string(input: &str) -> VResult<Self> {
         match input {
             "iron-ore" => Ok(Pixel::IronOre),
@@ -85,6 +108,11 @@ impl Pixel {
             "crude-oil" => Ok(Pixel::CrudeOil),
             //
             "steel-chest" => Ok(Pixel::SteelChest),
+            // scanner groups obstacle prototypes
+            "cliff" => Ok(Pixel::Cliff),
+            "rock" => Ok(Pixel::Rock),
+            "tree" => Ok(Pixel::Tree),
+            "player-entity" => Ok(Pixel::PlayerEntity),
             _ => Err(VError::UnknownName {
                 name: input.to_string(),
                 backtrace: Backtrace::force_capture(),
@@ -111,12 +139,14 @@ impl Pixel {
 
     /// Because OpenCV is BGR not RGB...
     /// Because OpenCV uses (boost?) Vector not rust Vec
+    #[cfg(feature = "opencv_re")]
     pub fn color_cv(&self) -> Vec3b {
         let mut rev = self.color();
         rev.reverse();
         Vec3b::from(rev)
     }
 
+    #[cfg(feature = "opencv_re")]
     pub fn scalar_cv(self) -> Scalar {
         let id = self.into_id();
         Scalar::from(id as i32)
@@ -144,9 +174,17 @@ impl Pixel {
     pub fn iter_resource() -> impl Iterator<Item = Self> {
         Self::iter().filter(Pixel::is_resource)
     }
+
+    /// Pre-existing things in the way of rail. See `ObstacleTunables`
+    pub const fn is_obstacle(&self) -> bool {
+        matches!(
+            self,
+            Pixel::Cliff | Pixel::Rock | Pixel::Tree | Pixel::PlayerEntity | Pixel::Water
+        )
+    }
 }
 
-pub const LOOKUP_IMAGE_ORDER: [Pixel; 10] = [
+pub const LOOKUP_IMAGE_ORDER: [Pixel; 14] = [
     Pixel::IronOre,
     Pixel::CopperOre,
     Pixel::Stone,
@@ -155,6 +193,11 @@ pub const LOOKUP_IMAGE_ORDER: [Pixel; 10] = [
     Pixel::Water,
     Pixel::CrudeOil,
     //
+    Pixel::Cliff,
+    Pixel::Rock,
+    Pixel::Tree,
+    Pixel::PlayerEntity,
+    //
     Pixel::Empty,
     Pixel::EdgeWall,
     Pixel::Rail,
@@ -1,5 +1,7 @@
+use crate::surface::pixel::Pixel;
 use facto_loop_miner_common::err_bt::MyBacktrace;
 use facto_loop_miner_common::err_utils::xbt;
+use facto_loop_miner_fac_engine::admiral::err::AdmiralError;
 use facto_loop_miner_fac_engine::common::vpoint::VPoint;
 use facto_loop_miner_io::err::{UringError, VStdIoError};
 use image::ImageError;
@@ -42,8 +44,36 @@ pub enum VError {
         path: String,
         backtrace: Backtrace,
     },
+    #[error("ImportConflict {path} at {pos} existing {existing:?} new {new:?}")]
+    ImportConflict {
+        path: String,
+        pos: VPoint,
+        existing: Pixel,
+        new: Pixel,
+        backtrace: Backtrace,
+    },
+    #[error("ExportParse {path} at byte {offset} {message}")]
+    ExportParse {
+        path: String,
+        offset: u64,
+        message: String,
+        backtrace: Backtrace,
+    },
+    #[error("InvalidState {path} {reason}")]
+    InvalidState {
+        path: String,
+        reason: String,
+        backtrace: Backtrace,
+    },
+    #[error("InvalidTunables {reason}")]
+    InvalidTunables {
+        reason: String,
+        backtrace: Backtrace,
+    },
     #[error("UringError {0}")]
     UringError(#[from] UringError),
+    #[error("Admiral {0}")]
+    Admiral(#[from] AdmiralError),
 }
 
 impl MyBacktrace for VError {
@@ -53,9 +83,14 @@ impl MyBacktrace for VError {
             | VError::IoError { backtrace, .. }
             | VError::UnknownName { backtrace, .. }
             | VError::SimdJsonFail { backtrace, .. }
+            | VError::InvalidTunables { backtrace, .. }
+            | VError::ImportConflict { backtrace, .. }
+            | VError::ExportParse { backtrace, .. }
+            | VError::InvalidState { backtrace, .. }
             // | VError::NotADirectory { backtrace, .. }
             | VError::Image { backtrace, .. } => backtrace,
             VError::UringError(e) => e.my_backtrace(),
+            VError::Admiral(e) => e.my_backtrace(),
         }
     }
 }
@@ -1,12 +1,11 @@
+use crate::navigator::base_source::BaseSourceEighth;
 use crate::surface::pixel::Pixel;
 use crate::surfacev::vsurface::{
     VSurfacePatch, VSurfacePixel, VSurfacePixelAsVs, VSurfacePixelMut,
 };
 use facto_loop_miner_common::LOCALE;
 use facto_loop_miner_fac_engine::common::varea::VArea;
-use facto_loop_miner_fac_engine::common::vpoint::{
-    VPOINT_SECTION, VPOINT_SECTION_Y_ONLY, VPOINT_TEN, VPoint,
-};
+use facto_loop_miner_fac_engine::common::vpoint::{VPOINT_SECTION, VPOINT_SECTION_Y_ONLY, VPoint};
 use facto_loop_miner_fac_engine::common::vpoint_direction::{VPointDirectionQ, VSegment};
 use facto_loop_miner_fac_engine::game_blocks::rail_hope::RailHopeLink;
 use facto_loop_miner_fac_engine::game_blocks::rail_hope_single::{HopeLink, SECTION_POINTS_I32};
@@ -28,6 +27,37 @@ pub struct MinePath {
     pub sodas: Vec<HopeSodaLink>,
     pub segment: VSegment,
     pub cost: u32,
+    /// Missing in states from before aggregation outposts
+    #[serde(default)]
+    pub kind: MinePathKind,
+    /// Water tiles under the rail, filled before the rail is placed
+    #[serde(default)]
+    pub landfill: Vec<VPoint>,
+    /// Cleared obstacles under the rail, recorded when added and restored when removed
+    #[serde(default)]
+    pub replaced_obstacles: Vec<(VPoint, Pixel)>,
+}
+
+/// What the rail loop connects
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug, Default)]
+pub enum MinePathKind {
+    #[default]
+    MineToBase,
+    /// Short train from the mine to the aggregation outpost `station`
+    MineToAggregator { station: MineLocation },
+    /// Long train from the aggregation outpost to the base
+    AggregatorToBase,
+}
+
+impl MinePathKind {
+    /// Kind of a path from a base source to `location`
+    pub fn to_base(location: &MineLocation) -> Self {
+        if location.is_aggregation_station() {
+            MinePathKind::AggregatorToBase
+        } else {
+            MinePathKind::MineToBase
+        }
+    }
 }
 
 #[derive(PartialEq, Eq, Hash, Clone, Debug, PartialOrd, Ord, Serialize, Deserialize)]
@@ -38,6 +68,9 @@ pub struct MineLocation {
     area_buffered: VArea,
     endpoints: Vec<VPoint>,
     endpoints_adjust_direction: Vec<FacDirectionQuarter>,
+    /// Missing in states from before aggregation outposts
+    #[serde(default)]
+    is_aggregation_station: bool,
 }
 
 impl MinePath {
@@ -60,6 +93,53 @@ impl MinePath {
         }
         new_points
     }
+
+    /// Tiles of `total_area` that are still water on the surface
+    pub fn find_landfill(&self, surface: VSurfacePixel) -> Vec<VPoint> {
+        self.total_area()
+            .into_iter()
+            .filter(|v| surface.get_pixel(v) == Pixel::Water)
+            .collect()
+    }
+
+    /// Tiles of `total_area` with anything besides water, which `landfill` already covers
+    pub fn find_obstacles(&self, surface: VSurfacePixel) -> Vec<(VPoint, Pixel)> {
+        self.total_area()
+            .into_iter()
+            .map(|v| (v, surface.get_pixel(v)))
+            .filter(|(_, pixel)| *pixel != Pixel::Empty && *pixel != Pixel::Water)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+impl MinePath {
+    /// Straight path of `sodas` sections from `start` to a mine at the end
+    pub fn new_test_straight(
+        surface: VSurfacePixel,
+        start: VPointDirectionQ,
+        sodas: usize,
+    ) -> Self {
+        use facto_loop_miner_fac_engine::common::vpoint::VPOINT_TEN;
+        use facto_loop_miner_fac_engine::game_blocks::rail_hope_soda::sodas_to_links;
+
+        let mut soda_links = vec![HopeSodaLink::new_soda_straight(start.0, start.1)];
+        while soda_links.len() < sodas {
+            soda_links.push(soda_links.last().unwrap().add_straight_section());
+        }
+        let end = soda_links.last().unwrap().my_q();
+        let mine_area = VArea::from_arbitrary_points_pair(end.0, end.0 + VPOINT_TEN);
+        MinePath {
+            location: MineLocation::from_area(surface, vec![0], mine_area).unwrap(),
+            links: sodas_to_links(&soda_links).collect(),
+            segment: VSegment { start, end },
+            cost: sodas as u32,
+            sodas: soda_links,
+            kind: MinePathKind::MineToBase,
+            landfill: Vec::new(),
+            replaced_obstacles: Vec::new(),
+        }
+    }
 }
 
 impl MineLocation {
@@ -77,17 +157,28 @@ impl MineLocation {
             })
             .flat_map(|p| p.area.get_corner_points());
         let area_min = VArea::from_arbitrary_points(patch_corners);
+        Self::from_area(surface.pixels(), patch_indexes, area_min)
+    }
+
+    /// Station of an aggregation outpost, routed to the base like a mine without patches
+    pub fn new_aggregation_station(surface: VSurfacePixel, area_min: VArea) -> Option<Self> {
+        let mut station = Self::from_area(surface, Vec::new(), area_min)?;
+        station.is_aggregation_station = true;
+        Some(station)
+    }
 
+    fn from_area(
+        surface: VSurfacePixel,
+        patch_indexes: Vec<usize>,
+        area_min: VArea,
+    ) -> Option<Self> {
         let area_no_touch = area_min
             .normalize_step_rail(0)
-            .normalize_within_radius(surface.pixels().get_radius_i32() - 1);
+            .normalize_within_radius(surface.get_radius_i32() - 1);
         // -- sanity --
         {
-            if !surface
-                .pixels()
-                .is_point_out_of_bounds(&(area_no_touch.point_top_left() - VPOINT_SECTION))
+            if !surface.is_point_out_of_bounds(&(area_no_touch.point_top_left() - VPOINT_SECTION))
                 && !surface
-                    .pixels()
                     .is_point_out_of_bounds(&(area_no_touch.point_bottom_right() + VPOINT_SECTION))
             {
                 let size = area_no_touch.as_size();
@@ -101,12 +192,12 @@ impl MineLocation {
             area_no_touch.point_top_left() - VPOINT_SECTION_Y_ONLY,
             area_no_touch.point_bottom_right() + VPOINT_SECTION_Y_ONLY,
         )
-        .normalize_within_radius(surface.pixels().get_radius_i32() - 1);
+        .normalize_within_radius(surface.get_radius_i32() - 1);
 
         assert!(area_no_touch.get_points().len() < area_buffered.get_points().len());
 
         let Some((endpoints, endpoints_adjust_direction)) =
-            Self::new_endpoints(surface.pixels(), &area_no_touch)
+            Self::new_endpoints(surface, &area_no_touch)
         else {
             warn!("Excluding mine at {}", area_no_touch);
             return None;
@@ -119,6 +210,7 @@ impl MineLocation {
             area_buffered,
             endpoints,
             endpoints_adjust_direction,
+            is_aggregation_station: false,
         })
     }
 
@@ -163,7 +255,13 @@ impl MineLocation {
         }
     }
 
-    pub fn revalidate_endpoints_after_no_touch(&mut self, surface: VSurfacePixel) {
+    /// `face` is the base face whose scan strips contain this mine
+    pub fn revalidate_endpoints_after_no_touch(
+        &mut self,
+        surface: VSurfacePixel,
+        face: &BaseSourceEighth,
+    ) {
+        let max_intra_offset = face.max_intra_offset();
         assert_eq!(self.endpoints.len(), self.endpoints_adjust_direction.len());
         trace!("start {}", self.area_min().point_center());
 
@@ -172,16 +270,12 @@ impl MineLocation {
             let adjust_direction = self.endpoints_adjust_direction[endpoint_index];
             trace!("dir {adjust_direction}");
 
-            /// See [crate::navigator::base_source::BaseSourceEighth]
-            /// This is always applied vertically
-            const MAX_INTRA_OFFSET: VPoint = VPoint::new(0, 4 * 6);
-
             /// Given endpoint is center of dual rail, which always is inside of area
             const DUAL_RAIL_OFFSET: i32 = 4;
             endpoint = endpoint.move_direction_sideways_int(adjust_direction, DUAL_RAIL_OFFSET);
 
             for adjust_i in 0..3 {
-                let mut new_origin = endpoint
+                let new_origin = endpoint
                     .move_direction_sideways_int(adjust_direction, adjust_i * SECTION_POINTS_I32);
 
                 match self.is_adjust_endpoint(
@@ -206,25 +300,24 @@ impl MineLocation {
                 trace!("uopdat! {endpoint_index}");
 
                 // now try with intra offset
-                new_origin += MAX_INTRA_OFFSET;
-                match self.is_adjust_endpoint(
+                let intra_origin = new_origin + max_intra_offset;
+                let intra_usable = match self.is_adjust_endpoint(
                     surface,
-                    new_origin,
-                    format_args!("mine endpoint {endpoint} at {adjust_i}-intra (cur {new_origin})"),
+                    intra_origin,
+                    format_args!(
+                        "mine endpoint {endpoint} at {adjust_i}-intra (cur {intra_origin})"
+                    ),
                 ) {
-                    Adjustment::Usable => {
-                        // success!
-                        trace!("final good!");
-                        continue 'endpoints;
-                    }
-                    Adjustment::AdjustMore => {
-                        // just skip ahead
-                        continue;
-                    }
-                    Adjustment::BadEndpoint => {
-                        // maybe the next adjustment is better?
-                        continue;
-                    }
+                    Adjustment::Usable => true,
+                    // just skip ahead
+                    Adjustment::AdjustMore => false,
+                    // maybe the next adjustment is better?
+                    Adjustment::BadEndpoint => false,
+                };
+                if intra_usable {
+                    // success!
+                    trace!("final good!");
+                    continue 'endpoints;
                 }
             }
             trace!("out of adjustment");
@@ -407,18 +500,12 @@ impl MineLocation {
         {
             // assert_eq!(surface.get_pixel(point), Pixel::MineNoTouch);
             let pixel = surface.pixels().get_pixel(point);
+            // executor threads have no paint sink, the area locates it instead
             if !matches!(pixel, Pixel::MineNoTouch | Pixel::Empty | Pixel::UraniumOre) {
-                surface
-                    .change_square(&VArea::from_arbitrary_points_pair(
-                        point,
-                        point + VPOINT_TEN,
-                    ))
-                    .stomp(Pixel::Highlighter);
-                surface
-                    .pixels()
-                    .paint_pixel_colored_zoomed()
-                    .save_to_oculante();
-                panic!("for {point} is {pixel:?}")
+                panic!(
+                    "for {point} is {pixel:?} in buffered {}",
+                    self.area_buffered
+                )
             }
         }
 
@@ -475,6 +562,11 @@ impl MineLocation {
     pub(super) fn patch_indexes(&self) -> &[usize] {
         self.patch_indexes.as_slice()
     }
+
+    /// Aggregation outposts have no patches of their own
+    pub fn is_aggregation_station(&self) -> bool {
+        self.is_aggregation_station
+    }
 }
 
 enum Adjustment {
@@ -491,18 +583,20 @@ pub struct DebugMinePatch {
 
 #[cfg(test)]
 mod test {
+    use crate::navigator::base_source::BaseSourceEighth;
     use crate::navigator::planners::{debug_draw_mine_links, debug_draw_segment};
     use crate::surface::pixel::Pixel;
     use crate::surfacev::mine::{DebugMinePatch, MineLocation};
     use crate::surfacev::vpatch::VPatch;
     use crate::surfacev::vsurface::{
-        VSurface, VSurfacePatchAsVs, VSurfacePatchAsVsMut, VSurfacePixelAsVs, VSurfacePixelAsVsMut,
+        SurfacePaintSink, VSurface, VSurfacePatchAsVs, VSurfacePatchAsVsMut, VSurfacePixelAsVs,
+        VSurfacePixelAsVsMut,
     };
     use facto_loop_miner_common::duration::BasicWatch;
     use facto_loop_miner_common::log_init_trace;
     use facto_loop_miner_fac_engine::common::varea::VArea;
     use facto_loop_miner_fac_engine::common::vpoint::{
-        VPOINT_SECTION, VPOINT_SECTION_NEGATIVE, VPoint,
+        VPOINT_SECTION, VPOINT_SECTION_NEGATIVE, VPOINT_ZERO, VPoint,
     };
     use facto_loop_miner_fac_engine::common::vpoint_direction::VPointDirectionQ;
     use facto_loop_miner_fac_engine::game_blocks::rail_hope_single::SECTION_POINTS_I32;
@@ -517,6 +611,8 @@ mod test {
             area: VArea::from_arbitrary_points_pair(VPoint::new(-5, -5), VPoint::new(6, 6)),
             resource: Pixel::CrudeOil,
             pixel_indexes: Vec::new(),
+            total_amount: 0,
+            amount_centroid: None,
         }]);
 
         let mine = MineLocation::from_patch_indexes(surface.patches(), vec![0]).unwrap();
@@ -567,6 +663,8 @@ mod test {
                 pixel_indexes: v.points.clone(),
                 resource: v.pixel,
                 area: VArea::from_arbitrary_points(&v.points),
+                total_amount: 0,
+                amount_centroid: None,
             }));
         // blank surface doesn't have pixels
         for patch in &patches {
@@ -586,7 +684,9 @@ mod test {
         // debug_draw_mine_links(surface, [&mine]);
 
         // <<<
-        mine.revalidate_endpoints_after_no_touch(surface.pixels());
+        let east_face =
+            BaseSourceEighth::new(VPointDirectionQ(VPOINT_ZERO, FacDirectionQuarter::East), 1);
+        mine.revalidate_endpoints_after_no_touch(surface.pixels(), &east_face);
         assert_ne!(mine.destinations().next(), None);
 
         debug_draw_mine_links(&mut surface.pixels_mut(), [&mine]);
@@ -611,7 +711,7 @@ mod test {
         surface
             .pixels()
             .paint_pixel_colored_entire()
-            .save_to_oculante();
+            .save_to_sink(&SurfacePaintSink::default());
     }
 
     fn load_mine_patch() -> Vec<DebugMinePatch> {
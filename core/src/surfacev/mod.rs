@@ -2,7 +2,11 @@ pub mod bit_grid;
 pub mod err;
 pub mod fast_metrics;
 pub mod mine;
+pub mod patch_labeller;
 pub mod rail_turn_templates;
+pub mod vamount_map;
+mod vchunk_array;
 mod ventity_map;
 pub mod vpatch;
+pub mod vspatial;
 pub mod vsurface;
@@ -0,0 +1,182 @@
+use facto_loop_miner_fac_engine::common::varea::VArea;
+use facto_loop_miner_fac_engine::common::vpoint::VPoint;
+use serde::{Deserialize, Serialize};
+
+const UNLABELED: u32 = 0;
+
+/// Neighbours that join two resource pixels into one patch
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Connectivity {
+    /// Edges only
+    Four,
+    /// Edges and corners, same as OpenCV `find_contours`
+    Eight,
+}
+
+impl Connectivity {
+    fn offsets(&self) -> &'static [(isize, isize)] {
+        match self {
+            Connectivity::Four => &[(0, -1), (-1, 0), (1, 0), (0, 1)],
+            Connectivity::Eight => &[
+                (-1, -1),
+                (0, -1),
+                (1, -1),
+                (-1, 0),
+                (1, 0),
+                (-1, 1),
+                (0, 1),
+                (1, 1),
+            ],
+        }
+    }
+
+    /// Background must use the other one, or diagonal gaps would leak into holes
+    fn background(&self) -> Connectivity {
+        match self {
+            Connectivity::Four => Connectivity::Eight,
+            Connectivity::Eight => Connectivity::Four,
+        }
+    }
+}
+
+/// Bounding areas of connected components of a row major `size * size` mask, in image
+/// coordinates, last found first.
+///
+/// Like `find_contours` with `RETR_EXTERNAL`, components inside another component's hole
+/// are dropped. The 1 pixel border is always background, also like `find_contours`, so
+/// resource pixels on the outermost ring of the surface are never in a patch
+pub fn label_component_areas(mask: &[bool], size: usize, connectivity: Connectivity) -> Vec<VArea> {
+    assert_eq!(mask.len(), size * size, "mask is not {size}x{size}");
+    if size < 3 {
+        return Vec::new();
+    }
+    let is_inner = |x: usize, y: usize| x > 0 && y > 0 && x < size - 1 && y < size - 1;
+
+    // min x, min y, max x, max y per label - 1
+    let mut bounds: Vec<[usize; 4]> = Vec::new();
+    let mut labels = vec![UNLABELED; size * size];
+    let mut stack: Vec<(usize, usize)> = Vec::new();
+    for y in 1..size - 1 {
+        for x in 1..size - 1 {
+            let index = y * size + x;
+            if !mask[index] || labels[index] != UNLABELED {
+                continue;
+            }
+            bounds.push([x, y, x, y]);
+            let label = bounds.len() as u32;
+            labels[index] = label;
+            stack.push((x, y));
+            while let Some((x, y)) = stack.pop() {
+                let bound = bounds.last_mut().unwrap();
+                bound[0] = bound[0].min(x);
+                bound[1] = bound[1].min(y);
+                bound[2] = bound[2].max(x);
+                bound[3] = bound[3].max(y);
+                for (next_x, next_y) in neighbours(x, y, size, connectivity) {
+                    let next_index = next_y * size + next_x;
+                    if is_inner(next_x, next_y)
+                        && mask[next_index]
+                        && labels[next_index] == UNLABELED
+                    {
+                        labels[next_index] = label;
+                        stack.push((next_x, next_y));
+                    }
+                }
+            }
+        }
+    }
+
+    // Flood the background from the border. Components it touches are outermost
+    let background = connectivity.background();
+    let mut outer = vec![false; bounds.len()];
+    let mut outside = vec![false; size * size];
+    for i in 0..size {
+        for (x, y) in [(i, 0), (i, size - 1), (0, i), (size - 1, i)] {
+            if !outside[y * size + x] {
+                outside[y * size + x] = true;
+                stack.push((x, y));
+            }
+        }
+    }
+    while let Some((x, y)) = stack.pop() {
+        for (next_x, next_y) in neighbours(x, y, size, background) {
+            let next_index = next_y * size + next_x;
+            let label = labels[next_index];
+            if label != UNLABELED {
+                outer[label as usize - 1] = true;
+            } else if !outside[next_index] {
+                outside[next_index] = true;
+                stack.push((next_x, next_y));
+            }
+        }
+    }
+
+    bounds
+        .into_iter()
+        .zip(outer)
+        .rev()
+        .filter(|(_, outer)| *outer)
+        .map(|([min_x, min_y, max_x, max_y], _)| {
+            VArea::from_arbitrary_points_pair(
+                VPoint::new(min_x as i32, min_y as i32),
+                VPoint::new(max_x as i32, max_y as i32),
+            )
+        })
+        .collect()
+}
+
+fn neighbours(
+    x: usize,
+    y: usize,
+    size: usize,
+    connectivity: Connectivity,
+) -> impl Iterator<Item = (usize, usize)> {
+    connectivity.offsets().iter().filter_map(move |(dx, dy)| {
+        let next_x = x.checked_add_signed(*dx).filter(|v| *v < size)?;
+        let next_y = y.checked_add_signed(*dy).filter(|v| *v < size)?;
+        Some((next_x, next_y))
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::surfacev::patch_labeller::{Connectivity, label_component_areas};
+    use facto_loop_miner_fac_engine::common::varea::VArea;
+    use facto_loop_miner_fac_engine::common::vpoint::VPoint;
+
+    #[test]
+    fn test_hole_and_diagonal() {
+        let rows = [
+            "#.........",
+            ".#####.#..",
+            ".#...#..#.",
+            ".#.#.#....",
+            ".#...#....",
+            ".#####....",
+            "..........",
+            "..........",
+            "..........",
+            "..........",
+        ];
+        let mask: Vec<bool> = rows
+            .iter()
+            .flat_map(|row| row.chars().map(|c| c == '#'))
+            .collect();
+        let area = |start: (i32, i32), end: (i32, i32)| {
+            VArea::from_arbitrary_points_pair(
+                VPoint::new(start.0, start.1),
+                VPoint::new(end.0, end.1),
+            )
+        };
+        let ring = area((1, 1), (5, 5));
+
+        assert_eq!(
+            label_component_areas(&mask, 10, Connectivity::Eight),
+            [area((7, 1), (8, 2)), ring.clone()]
+        );
+        assert_eq!(
+            label_component_areas(&mask, 10, Connectivity::Four),
+            [area((8, 2), (8, 2)), area((7, 1), (7, 1)), ring]
+        );
+    }
+}
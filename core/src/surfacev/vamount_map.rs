@@ -0,0 +1,195 @@
+use crate::surfacev::err::{CoreConvertPathResult, VError, VResult};
+use crate::surfacev::vchunk_array::VChunkCells;
+use facto_loop_miner_common::duration::BasicWatch;
+use facto_loop_miner_common::err_utils::xbt;
+use facto_loop_miner_fac_engine::common::vpoint::VPoint;
+use facto_loop_miner_io::{read_entire_file, write_entire_file};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use tracing::debug;
+
+/// Resource amount at each xy position inside the same square as the pixel `VEntityMap`
+///
+/// Zero when unknown, eg the export was in the older `[name, x, y]` format.
+/// Chunked like `XyStorage::Chunked` since only resource tiles have an amount
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct VAmountMap {
+    /// Stored as a raw file next to the pixel xy file
+    #[serde(skip)]
+    amounts: VChunkCells<u32>,
+    radius: u32,
+}
+
+/// x, y, amount
+const RECORD_BYTES: usize = 12;
+
+impl VAmountMap {
+    pub fn new(radius: u32) -> Self {
+        VAmountMap {
+            amounts: VChunkCells::new(radius as usize * 2, 0),
+            radius,
+        }
+    }
+
+    fn is_out_of_bounds(&self, point: &VPoint) -> bool {
+        let radius = self.radius as i32;
+        let x_valid = point.x() >= -radius && point.x() < radius;
+        let y_valid = point.y() >= -radius && point.y() < radius;
+        !x_valid || !y_valid
+    }
+
+    fn point_to_index(&self, point: &VPoint) -> usize {
+        let radius = self.radius as i32;
+        let diameter = self.amounts.diameter() as i32;
+        ((point.y() + radius) * diameter + point.x() + radius) as usize
+    }
+
+    fn index_to_point(&self, index: usize) -> VPoint {
+        let radius = self.radius as i32;
+        let diameter = self.amounts.diameter();
+        VPoint::new(
+            (index % diameter) as i32 - radius,
+            (index / diameter) as i32 - radius,
+        )
+    }
+
+    pub fn get(&self, point: &VPoint) -> u32 {
+        assert!(
+            !self.is_out_of_bounds(point),
+            "amount {point} outside radius {}",
+            self.radius
+        );
+        self.amounts.get(self.point_to_index(point))
+    }
+
+    pub fn set(&mut self, point: &VPoint, amount: u32) {
+        assert!(
+            !self.is_out_of_bounds(point),
+            "amount {point} outside radius {}",
+            self.radius
+        );
+        let index = self.point_to_index(point);
+        self.amounts.set(index, amount);
+    }
+
+    pub fn crop(&mut self, new_radius: u32) {
+        let mut cropped = Self::new(new_radius);
+        for (point, amount) in self.iter_occupied() {
+            if !cropped.is_out_of_bounds(&point) {
+                cropped.set(&point, amount);
+            }
+        }
+        *self = cropped;
+    }
+
+    /// Non-zero amounts in chunk order
+    fn iter_occupied(&self) -> impl Iterator<Item = (VPoint, u32)> + '_ {
+        self.amounts
+            .iter_occupied()
+            .map(|(index, amount)| (self.index_to_point(index), amount))
+    }
+
+    pub fn save_file(&self, path: &Path) -> VResult<()> {
+        let write_watch = BasicWatch::start();
+        let mut data: Vec<u8> = Vec::new();
+        for (point, amount) in self.iter_occupied() {
+            data.extend(point.x().to_le_bytes());
+            data.extend(point.y().to_le_bytes());
+            data.extend(amount.to_le_bytes());
+        }
+        write_entire_file(path, &data).convert(path)?;
+        debug!(
+            "Saving Amount XY write {} bytes path {} in {write_watch}",
+            data.len(),
+            path.display()
+        );
+        Ok(())
+    }
+
+    /// Surfaces saved before the amount layer existed have no file
+    pub fn load_file_or_empty(&mut self, path: &Path, radius: u32) -> VResult<()> {
+        *self = Self::new(radius);
+        if !path.exists() {
+            debug!("No Amount XY file {}, using empty", path.display());
+            return Ok(());
+        }
+        let data = read_entire_file(path, true).convert(path)?;
+        let (records, remainder) = data.as_chunks::<RECORD_BYTES>();
+        if !remainder.is_empty() {
+            return Err(invalid_amounts(
+                path,
+                format!("partial amount of {} bytes at end", remainder.len()),
+            ));
+        }
+        for record in records {
+            let x = i32::from_le_bytes(record[0..4].try_into().unwrap());
+            let y = i32::from_le_bytes(record[4..8].try_into().unwrap());
+            let amount = u32::from_le_bytes(record[8..12].try_into().unwrap());
+            let point = VPoint::new(x, y);
+            if self.is_out_of_bounds(&point) {
+                return Err(invalid_amounts(
+                    path,
+                    format!("amount {point} outside radius {radius}"),
+                ));
+            }
+            self.set(&point, amount);
+        }
+        Ok(())
+    }
+}
+
+fn invalid_amounts(path: &Path, reason: String) -> VError {
+    VError::InvalidState {
+        path: path.to_string_lossy().to_string(),
+        reason,
+        backtrace: xbt(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::surfacev::err::VError;
+    use crate::surfacev::vamount_map::VAmountMap;
+    use facto_loop_miner_fac_engine::common::vpoint::VPoint;
+    use std::fs;
+
+    #[test]
+    fn test_crop_keeps_amounts() {
+        let mut amounts = VAmountMap::new(10);
+        amounts.set(&VPoint::new(-3, 4), 1500);
+        amounts.set(&VPoint::new(9, 9), 20);
+        amounts.crop(5);
+        assert_eq!(amounts.get(&VPoint::new(-3, 4)), 1500);
+        assert_eq!(amounts.get(&VPoint::new(4, 4)), 0);
+    }
+
+    #[test]
+    fn test_save_load_chunk_order() {
+        let dir = std::env::temp_dir().join(format!("amount-map-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("amounts.dat");
+
+        let mut amounts = VAmountMap::new(40);
+        amounts.set(&VPoint::new(30, 30), 3);
+        amounts.set(&VPoint::new(-40, -40), 1);
+        amounts.set(&VPoint::new(-39, -40), 2);
+        amounts.save_file(&path).unwrap();
+        let data = fs::read(&path).unwrap();
+        assert_eq!(data.len(), 3 * 12);
+        assert_eq!(i32::from_le_bytes(data[0..4].try_into().unwrap()), -40);
+        assert_eq!(i32::from_le_bytes(data[24..28].try_into().unwrap()), 30);
+
+        let mut loaded = VAmountMap::default();
+        loaded.load_file_or_empty(&path, 40).unwrap();
+        assert_eq!(loaded.get(&VPoint::new(-39, -40)), 2);
+        assert_eq!(loaded.get(&VPoint::new(30, 30)), 3);
+
+        let err = loaded.load_file_or_empty(&path, 20).unwrap_err();
+        assert!(matches!(err, VError::InvalidState { .. }), "{err}");
+
+        fs::write(&path, &data[..data.len() - 5]).unwrap();
+        let err = loaded.load_file_or_empty(&path, 40).unwrap_err();
+        assert!(matches!(err, VError::InvalidState { .. }), "{err}");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,351 @@
+use crate::surfacev::err::{CoreConvertPathResult, VResult};
+use facto_loop_miner_io::varray::{EMPTY_XY_INDEX, VArray};
+use std::io;
+use std::io::ErrorKind;
+use std::path::Path;
+
+pub const XY_CHUNK_SIZE: usize = 32;
+const XY_CHUNK_AREA: usize = XY_CHUNK_SIZE * XY_CHUNK_SIZE;
+/// First word of a chunked xy file. Dense files start with an entity index, which is never this big
+pub const XY_CHUNKED_MAGIC: usize = 0x4C4D_4348_554E_4B31;
+const HEADER_WORDS: usize = 3;
+const NO_SLOT: u32 = u32::MAX;
+
+/// Sparse alternative to a dense `VArray` using the same row-major indexes.
+/// 32x32 chunks are only allocated on the first non-empty write.
+///
+/// File layout in usize words is `[magic, diameter, chunk count, chunk ids..., chunk data...]`
+#[derive(Clone)]
+pub struct VChunkArray {
+    diameter: usize,
+    chunks_per_side: usize,
+    /// Storage slot of every chunk in the square, NO_SLOT until written
+    slots: Vec<u32>,
+    /// Loaded file. The first `base_chunks` slots are its chunk data
+    base: VArray,
+    base_chunks: usize,
+    base_data_offset: usize,
+    /// Allocated since load
+    extra: Vec<Box<[usize]>>,
+}
+
+impl VChunkArray {
+    pub fn new(diameter: usize) -> Self {
+        let chunks_per_side = diameter.div_ceil(XY_CHUNK_SIZE);
+        VChunkArray {
+            diameter,
+            chunks_per_side,
+            slots: vec![NO_SLOT; chunks_per_side * chunks_per_side],
+            base: VArray::new_length(0),
+            base_chunks: 0,
+            base_data_offset: 0,
+            extra: Vec::new(),
+        }
+    }
+
+    /// Wrap a loaded (usually mmap'd) file starting with `XY_CHUNKED_MAGIC`
+    pub fn from_loaded(base: VArray, path: &Path) -> VResult<Self> {
+        let words = base.as_slice();
+        let invalid = |reason: &str| {
+            Err::<Self, _>(io::Error::new(ErrorKind::InvalidData, reason.to_string())).convert(path)
+        };
+        if words.len() < HEADER_WORDS || words[0] != XY_CHUNKED_MAGIC {
+            return invalid("missing chunked xy header");
+        }
+        let diameter = words[1];
+        let base_chunks = words[2];
+        let base_data_offset = HEADER_WORDS + base_chunks;
+        if words.len() != base_data_offset + base_chunks * XY_CHUNK_AREA {
+            return invalid("chunked xy size mismatch");
+        }
+
+        let mut result = Self::new(diameter);
+        for (slot, chunk_id) in words[HEADER_WORDS..base_data_offset].iter().enumerate() {
+            if *chunk_id >= result.slots.len() {
+                return invalid("chunk id outside diameter");
+            }
+            result.slots[*chunk_id] = slot as u32;
+        }
+        result.base = base;
+        result.base_chunks = base_chunks;
+        result.base_data_offset = base_data_offset;
+        Ok(result)
+    }
+
+    pub fn diameter(&self) -> usize {
+        self.diameter
+    }
+
+    pub fn len(&self) -> usize {
+        self.diameter * self.diameter
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.diameter == 0
+    }
+
+    pub fn allocated_chunks(&self) -> usize {
+        self.base_chunks + self.extra.len()
+    }
+
+    fn locate(&self, index: usize) -> (usize, usize) {
+        locate(self.diameter, self.chunks_per_side, index)
+    }
+
+    fn chunk(&self, slot: usize) -> &[usize] {
+        if slot < self.base_chunks {
+            let start = self.base_data_offset + slot * XY_CHUNK_AREA;
+            &self.base.as_slice()[start..start + XY_CHUNK_AREA]
+        } else {
+            &self.extra[slot - self.base_chunks]
+        }
+    }
+
+    fn chunk_mut(&mut self, slot: usize) -> &mut [usize] {
+        if slot < self.base_chunks {
+            let start = self.base_data_offset + slot * XY_CHUNK_AREA;
+            &mut self.base.as_mut_slice()[start..start + XY_CHUNK_AREA]
+        } else {
+            &mut self.extra[slot - self.base_chunks]
+        }
+    }
+
+    pub fn get(&self, index: usize) -> usize {
+        assert!(index < self.len(), "index {index} outside {}", self.len());
+        let (chunk_id, inner) = self.locate(index);
+        match self.slots[chunk_id] {
+            NO_SLOT => EMPTY_XY_INDEX,
+            slot => self.chunk(slot as usize)[inner],
+        }
+    }
+
+    pub fn set(&mut self, index: usize, value: usize) {
+        assert!(index < self.len(), "index {index} outside {}", self.len());
+        let (chunk_id, inner) = self.locate(index);
+        let slot = match self.slots[chunk_id] {
+            NO_SLOT if value == EMPTY_XY_INDEX => return,
+            NO_SLOT => {
+                let slot = self.allocated_chunks();
+                self.extra
+                    .push(vec![EMPTY_XY_INDEX; XY_CHUNK_AREA].into_boxed_slice());
+                self.slots[chunk_id] = slot as u32;
+                slot
+            }
+            slot => slot as usize,
+        };
+        self.chunk_mut(slot)[inner] = value;
+    }
+
+    /// Every index in row-major order, same as a dense slice
+    pub fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.len()).map(|index| self.get(index))
+    }
+
+    /// Only non-empty indexes from allocated chunks, in no particular order
+    pub fn iter_occupied(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.slots
+            .iter()
+            .enumerate()
+            .filter(|(_, slot)| **slot != NO_SLOT)
+            .flat_map(move |(chunk_id, slot)| {
+                self.chunk(*slot as usize)
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, value)| **value != EMPTY_XY_INDEX)
+                    .map(move |(inner, value)| {
+                        let index = unlocate(self.diameter, self.chunks_per_side, chunk_id, inner);
+                        (index, *value)
+                    })
+            })
+    }
+
+    /// Chunks allocated since load are only in memory
+    pub fn is_dirty_for_clone(&self) -> bool {
+        self.base.is_dirty_for_clone() || !self.extra.is_empty()
+    }
+
+    pub fn to_file_words(&self) -> Vec<usize> {
+        let total_chunks = self.allocated_chunks();
+        let mut chunk_ids = vec![0; total_chunks];
+        for (chunk_id, slot) in self.slots.iter().enumerate() {
+            if *slot != NO_SLOT {
+                chunk_ids[*slot as usize] = chunk_id;
+            }
+        }
+
+        let mut words = Vec::with_capacity(HEADER_WORDS + total_chunks * (1 + XY_CHUNK_AREA));
+        words.extend([XY_CHUNKED_MAGIC, self.diameter, total_chunks]);
+        words.extend(&chunk_ids);
+        for slot in 0..total_chunks {
+            words.extend_from_slice(self.chunk(slot));
+        }
+        words
+    }
+}
+
+/// Chunk id and position inside the chunk of a row-major index
+fn locate(diameter: usize, chunks_per_side: usize, index: usize) -> (usize, usize) {
+    let x = index % diameter;
+    let y = index / diameter;
+    let chunk_id = (y / XY_CHUNK_SIZE) * chunks_per_side + x / XY_CHUNK_SIZE;
+    let inner = (y % XY_CHUNK_SIZE) * XY_CHUNK_SIZE + x % XY_CHUNK_SIZE;
+    (chunk_id, inner)
+}
+
+/// Row-major index of a position inside a chunk
+fn unlocate(diameter: usize, chunks_per_side: usize, chunk_id: usize, inner: usize) -> usize {
+    let x = (chunk_id % chunks_per_side) * XY_CHUNK_SIZE + inner % XY_CHUNK_SIZE;
+    let y = (chunk_id / chunks_per_side) * XY_CHUNK_SIZE + inner / XY_CHUNK_SIZE;
+    y * diameter + x
+}
+
+/// Same chunks as [VChunkArray] for cells that are never mmap'd, eg resource amounts
+#[derive(Clone)]
+pub struct VChunkCells<T> {
+    diameter: usize,
+    chunks_per_side: usize,
+    empty: T,
+    /// Position in `chunks` of every chunk in the square, NO_SLOT until written
+    slots: Vec<u32>,
+    chunks: Vec<Box<[T]>>,
+}
+
+impl<T: Copy + PartialEq + Default> Default for VChunkCells<T> {
+    fn default() -> Self {
+        VChunkCells::new(0, T::default())
+    }
+}
+
+impl<T: Copy + PartialEq> VChunkCells<T> {
+    pub fn new(diameter: usize, empty: T) -> Self {
+        let chunks_per_side = diameter.div_ceil(XY_CHUNK_SIZE);
+        VChunkCells {
+            diameter,
+            chunks_per_side,
+            empty,
+            slots: vec![NO_SLOT; chunks_per_side * chunks_per_side],
+            chunks: Vec::new(),
+        }
+    }
+
+    pub fn diameter(&self) -> usize {
+        self.diameter
+    }
+
+    pub fn len(&self) -> usize {
+        self.diameter * self.diameter
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.diameter == 0
+    }
+
+    pub fn get(&self, index: usize) -> T {
+        assert!(index < self.len(), "index {index} outside {}", self.len());
+        let (chunk_id, inner) = locate(self.diameter, self.chunks_per_side, index);
+        match self.slots[chunk_id] {
+            NO_SLOT => self.empty,
+            slot => self.chunks[slot as usize][inner],
+        }
+    }
+
+    pub fn set(&mut self, index: usize, value: T) {
+        assert!(index < self.len(), "index {index} outside {}", self.len());
+        let (chunk_id, inner) = locate(self.diameter, self.chunks_per_side, index);
+        let slot = match self.slots[chunk_id] {
+            NO_SLOT if value == self.empty => return,
+            NO_SLOT => {
+                let slot = self.chunks.len();
+                self.chunks
+                    .push(vec![self.empty; XY_CHUNK_AREA].into_boxed_slice());
+                self.slots[chunk_id] = slot as u32;
+                slot
+            }
+            slot => slot as usize,
+        };
+        self.chunks[slot][inner] = value;
+    }
+
+    /// Non-empty cells by chunk id, so equal contents always iterate the same
+    pub fn iter_occupied(&self) -> impl Iterator<Item = (usize, T)> + '_ {
+        self.slots
+            .iter()
+            .enumerate()
+            .filter(|(_, slot)| **slot != NO_SLOT)
+            .flat_map(move |(chunk_id, slot)| {
+                self.chunks[*slot as usize]
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, value)| **value != self.empty)
+                    .map(move |(inner, value)| {
+                        let index = unlocate(self.diameter, self.chunks_per_side, chunk_id, inner);
+                        (index, *value)
+                    })
+            })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::surfacev::vchunk_array::{VChunkArray, VChunkCells};
+    use facto_loop_miner_io::varray::{EMPTY_XY_INDEX, VArray};
+    use std::path::Path;
+
+    #[test]
+    fn test_sparse_allocation() {
+        // not a multiple of the chunk size
+        let mut array = VChunkArray::new(100);
+        assert_eq!(array.get(5_000), EMPTY_XY_INDEX);
+        array.set(5_000, EMPTY_XY_INDEX);
+        assert_eq!(array.allocated_chunks(), 0);
+
+        array.set(0, 7);
+        array.set(99 * 100 + 99, 8);
+        array.set(1, 9);
+        assert_eq!(array.allocated_chunks(), 2);
+        assert_eq!(array.get(0), 7);
+        assert_eq!(array.get(9_999), 8);
+        assert_eq!(array.iter().filter(|v| *v != EMPTY_XY_INDEX).count(), 3);
+
+        let mut occupied: Vec<(usize, usize)> = array.iter_occupied().collect();
+        occupied.sort();
+        assert_eq!(occupied, [(0, 7), (1, 9), (9_999, 8)]);
+    }
+
+    #[test]
+    fn test_file_words_roundtrip() {
+        let mut array = VChunkArray::new(64);
+        array.set(63 * 64, 3);
+        array.set(40, 4);
+
+        let mut base = VArray::new_length(array.to_file_words().len());
+        base.as_mut_slice().copy_from_slice(&array.to_file_words());
+        let mut loaded = VChunkArray::from_loaded(base, Path::new("test")).unwrap();
+        assert_eq!(loaded.get(63 * 64), 3);
+        assert_eq!(loaded.get(40), 4);
+        assert_eq!(loaded.get(41), EMPTY_XY_INDEX);
+
+        loaded.set(41, 5);
+        assert_eq!(loaded.allocated_chunks(), 2);
+        loaded.set(0, 6);
+        assert_eq!(loaded.allocated_chunks(), 3);
+        assert_eq!(loaded.get(41), 5);
+
+        assert!(VChunkArray::from_loaded(VArray::new_length(4), Path::new("test")).is_err());
+    }
+
+    #[test]
+    fn test_cells_occupied_order() {
+        let mut cells = VChunkCells::new(100, 0u32);
+        cells.set(99 * 100 + 99, 3);
+        cells.set(1, 2);
+        cells.set(0, 1);
+        cells.set(50, 0);
+        assert_eq!(cells.get(1), 2);
+        assert_eq!(cells.get(50), 0);
+        assert_eq!(
+            cells.iter_occupied().collect::<Vec<_>>(),
+            [(0, 1), (1, 2), (9_999, 3)]
+        );
+    }
+}
@@ -1,16 +1,22 @@
+#[cfg(feature = "opencv_re")]
 use crate::opencv::GeneratedMat;
 use crate::surface::pixel::Pixel;
 use crate::surfacev::err::{CoreConvertPathResult, VError, VResult};
+#[cfg(feature = "opencv_re")]
 use crate::surfacev::fast_metrics::{FastMetric, FastMetrics};
+use crate::surfacev::vchunk_array::{VChunkArray, XY_CHUNKED_MAGIC};
 use facto_loop_miner_common::LOCALE;
 use facto_loop_miner_common::duration::BasicWatch;
+use facto_loop_miner_fac_engine::common::varea::VArea;
 use facto_loop_miner_fac_engine::common::vpoint::VPoint;
 use facto_loop_miner_io::varray::{EMPTY_XY_INDEX, VArray};
 use facto_loop_miner_io::{get_mebibytes_of_slice_usize, write_entire_file};
+use itertools::Either;
 use num_format::ToFormattedString;
 use serde::{Deserialize, Serialize};
+#[cfg(feature = "opencv_re")]
 use std::cell::RefCell;
-use std::fmt::{Debug, Display, Formatter};
+use std::fmt::{Display, Formatter};
 use std::fs::remove_file;
 use std::io::ErrorKind;
 use std::path::Path;
@@ -18,6 +24,9 @@ use std::simd::prelude::{SimdInt, SimdPartialOrd};
 use std::simd::{Mask, Simd};
 use tracing::debug;
 
+/// Above this a dense xy array is over 2GiB, mostly of empty space
+const DENSE_MAX_RADIUS: u32 = 8192;
+
 /// Collection of entities and xy positions they cover
 ///
 /// For example, ore tiles cover 1 positions. Assembly machines cover 9 positions
@@ -26,9 +35,12 @@ pub struct VEntityMap<E> {
     entities: Vec<E>,
     /// More efficient to store a (radius * 2)^2 length Array as a raw file instead of JSON
     #[serde(skip)]
-    xy_to_entity: VArray,
+    xy_to_entity: XyStorage,
     /// A *square* centered on 0,0
     radius: u32,
+    /// Undo history, only recorded between `begin` and `commit`
+    #[serde(skip)]
+    journal: Option<VJournal<E>>,
 }
 
 impl<E> VEntityMap<E>
@@ -36,12 +48,33 @@ impl<E> VEntityMap<E>
 //     E: Clone + Eq + Hash + Debug,
 {
     pub fn new(radius: u32) -> Self {
-        let res = VEntityMap {
+        if radius > DENSE_MAX_RADIUS {
+            Self::new_chunked(radius)
+        } else {
+            Self::new_dense(radius)
+        }
+    }
+
+    pub fn new_dense(radius: u32) -> Self {
+        VEntityMap {
             entities: Vec::new(),
-            xy_to_entity: VArray::new_length(Self::_xy_array_length_from_radius(radius)),
+            xy_to_entity: XyStorage::new_dense(radius),
             radius,
-        };
-        res
+            journal: None,
+        }
+    }
+
+    pub fn new_chunked(radius: u32) -> Self {
+        VEntityMap {
+            entities: Vec::new(),
+            xy_to_entity: XyStorage::new_chunked(radius),
+            radius,
+            journal: None,
+        }
+    }
+
+    pub fn is_chunked(&self) -> bool {
+        matches!(self.xy_to_entity, XyStorage::Chunked(_))
     }
 
     pub fn radius(&self) -> u32 {
@@ -115,14 +148,14 @@ impl<E> VEntityMap<E>
     }
 
     pub fn is_points_free_safe(&self, points: &[VPoint]) -> bool {
-        let xy_lookup = self.xy_to_entity.as_slice();
-
         points.iter().all(|v| {
             if self.is_point_out_of_bounds(v) {
                 // silent
                 true
             } else {
-                xy_lookup[self.xy_to_index_unchecked(v.x(), v.y())] == EMPTY_XY_INDEX
+                self.xy_to_entity
+                    .get(self.xy_to_index_unchecked(v.x(), v.y()))
+                    == EMPTY_XY_INDEX
             }
         })
 
@@ -146,7 +179,14 @@ impl<E> VEntityMap<E>
 
     // #[inline(never)]
     pub fn is_points_free_unchecked_iter(&self, points: &[VPoint]) -> bool {
-        let xy_lookup = self.xy_to_entity.as_slice();
+        let xy_lookup = match &self.xy_to_entity {
+            XyStorage::Dense(xy_lookup) => xy_lookup.as_slice(),
+            XyStorage::Chunked(chunks) => {
+                return points.iter().all(|v| {
+                    chunks.get(self.xy_to_index_unchecked(v.x(), v.y())) == EMPTY_XY_INDEX
+                });
+            }
+        };
 
         // This is an extremely hot function. Attempt SIMD
         if false {
@@ -213,10 +253,17 @@ impl<E> VEntityMap<E>
 
     /// crop entities then rebuild xy_to_entity lookup
     pub fn crop(&mut self, new_radius: u32) {
+        assert!(self.journal.is_none(), "cannot crop inside a journal");
+        let new_xy_to_entity = if self.is_chunked() {
+            XyStorage::new_chunked(new_radius)
+        } else {
+            XyStorage::new_dense(new_radius)
+        };
         let mut new = Self {
             radius: new_radius,
             entities: Vec::new(), // dummy
-            xy_to_entity: VArray::new_length(Self::_xy_array_length_from_radius(new_radius)),
+            xy_to_entity: new_xy_to_entity,
+            journal: None,
         };
         debug!(
             "Reduce entities from {} to {}, xy_map from {} to {}",
@@ -226,26 +273,110 @@ impl<E> VEntityMap<E>
             new.xy_to_entity.len().to_formatted_string(&LOCALE)
         );
 
-        let old_xy_to_entity = self.xy_to_entity.as_slice();
-        for xy_index in 0..new.xy_to_entity.as_mut_slice().len() {
-            let position = new.index_to_xy(xy_index);
-            new.xy_to_entity.as_mut_slice()[xy_index] =
-                old_xy_to_entity[self.xy_to_index_unchecked(position.x(), position.y())];
+        for (old_xy_index, entity_index) in self.xy_to_entity.iter_occupied() {
+            let position = self.index_to_xy(old_xy_index);
+            if !new.is_point_out_of_bounds(&position) {
+                let new_xy_index = new.point_to_index_unchecked(&position);
+                new.xy_to_entity.set(new_xy_index, entity_index);
+            }
         }
 
         let Self {
             radius,
             entities: _, // we didn't touch this
             xy_to_entity,
+            journal: _,
         } = new;
         self.radius = radius;
         self.xy_to_entity = xy_to_entity;
     }
 
+    //<editor-fold desc="journal">
+    /// Start recording changes, or mark a nested point if already recording
+    pub fn begin(&mut self) -> VCheckpoint {
+        let journal = self.journal.get_or_insert_with(VJournal::default);
+        VCheckpoint {
+            xy_len: journal.xy_committed + journal.xy_previous.len(),
+            entity_edit_len: journal.entity_committed + journal.entity_previous.len(),
+            entities_len: self.entities.len(),
+        }
+    }
+
+    /// Exactly restore xy and entities as they were at the checkpoint.
+    /// Later checkpoints are invalid afterwards, earlier ones still work
+    pub fn rollback_to(&mut self, checkpoint: VCheckpoint) {
+        let journal = self.journal.as_mut().expect("rollback without journal");
+        let (xy_start, entity_edit_start) = journal.relative(&checkpoint);
+        assert!(
+            checkpoint.entities_len <= self.entities.len(),
+            "checkpoint {checkpoint:?} is not from this journal"
+        );
+
+        for (xy_index, entity_index) in journal.xy_previous.drain(xy_start..).rev() {
+            self.xy_to_entity.set(xy_index, entity_index);
+        }
+        for (entity_index, entity) in journal.entity_previous.drain(entity_edit_start..).rev() {
+            self.entities[entity_index] = entity;
+        }
+        // nothing in xy points past here anymore
+        self.entities.truncate(checkpoint.entities_len);
+    }
+
+    /// Keep all changes and stop recording. Every checkpoint is invalid afterwards
+    pub fn commit(&mut self) {
+        self.journal = None;
+    }
+
+    /// Keep changes before the checkpoint, dropping their history.
+    /// Later checkpoints still work, earlier ones are invalid afterwards
+    pub fn commit_before(&mut self, checkpoint: VCheckpoint) {
+        let journal = self.journal.as_mut().expect("commit without journal");
+        let (xy_start, entity_edit_start) = journal.relative(&checkpoint);
+        journal.xy_previous.drain(..xy_start);
+        journal.entity_previous.drain(..entity_edit_start);
+        journal.xy_committed = checkpoint.xy_len;
+        journal.entity_committed = checkpoint.entity_edit_len;
+    }
+
+    pub fn is_journaling(&self) -> bool {
+        self.journal.is_some()
+    }
+
+    /// Copy for scratch work, like executor threads, without the undo history
+    pub fn clone_without_journal(&self) -> Self
+    where
+        E: Clone,
+    {
+        VEntityMap {
+            entities: self.entities.clone(),
+            xy_to_entity: self.xy_to_entity.clone(),
+            radius: self.radius,
+            journal: None,
+        }
+    }
+
+    /// Every xy write goes through here to be journaled
+    fn set_xy(&mut self, xy_index: usize, entity_index: usize) {
+        if let Some(journal) = &mut self.journal {
+            journal
+                .xy_previous
+                .push((xy_index, self.xy_to_entity.get(xy_index)));
+        }
+        self.xy_to_entity.set(xy_index, entity_index);
+    }
+    //</editor-fold>
+
     //<editor-fold desc="io">
     pub fn save_xy_file(&self, path: &Path) -> VResult<()> {
         let write_watch = BasicWatch::start();
-        let source = self.xy_to_entity.as_slice();
+        let chunked_words;
+        let source = match &self.xy_to_entity {
+            XyStorage::Dense(xy_to_entity) => xy_to_entity.as_slice(),
+            XyStorage::Chunked(chunks) => {
+                chunked_words = chunks.to_file_words();
+                chunked_words.as_slice()
+            }
+        };
         let source_len = source.len();
         let (before, data, after) = unsafe { source.align_to() };
         assert_eq!(before.len(), 0);
@@ -325,11 +456,18 @@ impl<E> VEntityMap<E>
 
     fn _load_xy_file_mmap(&mut self, path: &Path) -> VResult<()> {
         let total_watch = BasicWatch::start();
-        self.xy_to_entity = VArray::from_path(path).convert(path)?;
+        let raw = VArray::from_path(path).convert(path)?;
+        let raw_size = get_mebibytes_of_slice_usize(raw.as_slice());
+        // Chunked files are self-describing, loaded in parallel to the state JSON
+        self.xy_to_entity = if raw.as_slice().first() == Some(&XY_CHUNKED_MAGIC) {
+            XyStorage::Chunked(VChunkArray::from_loaded(raw, path)?)
+        } else {
+            XyStorage::Dense(raw)
+        };
         debug!(
             "Loading Entity XY (mmap) total {} / {} in {} path {}",
             self.xy_to_entity.len().to_formatted_string(&LOCALE),
-            get_mebibytes_of_slice_usize(self.xy_to_entity.as_slice()),
+            raw_size,
             total_watch,
             path.display()
         );
@@ -388,9 +526,8 @@ impl<E> VEntityMap<E>
     ) -> Vec<u8> {
         let result: Vec<u8> = self
             .xy_to_entity
-            .as_slice()
             .iter()
-            .map(|index| self.entities.get(*index))
+            .map(|index| self.entities.get(index))
             .flat_map(mapper)
             .collect();
         assert_eq!(
@@ -404,7 +541,7 @@ impl<E> VEntityMap<E>
     }
 
     pub fn get_entity_id_at(&self, point: &VPoint) -> usize {
-        self.xy_to_entity.as_slice()[self.point_to_index_safe(point)]
+        self.xy_to_entity.get(self.point_to_index_safe(point))
     }
 
     pub fn get_entity_by_index(&self, index: usize) -> &E {
@@ -414,8 +551,15 @@ impl<E> VEntityMap<E>
         }
     }
 
-    pub fn get_entity_by_index_mut(&mut self, index: usize) -> &mut E {
-        self.entities.get_mut(index).unwrap()
+    pub fn get_entity_by_index_mut(&mut self, index: usize) -> &mut E
+    where
+        E: Clone,
+    {
+        let entity = self.entities.get_mut(index).unwrap();
+        if let Some(journal) = &mut self.journal {
+            journal.entity_previous.push((index, entity.clone()));
+        }
+        entity
     }
 
     pub fn get_entity_by_point(&self, point: &VPoint) -> Option<&E> {
@@ -427,7 +571,10 @@ impl<E> VEntityMap<E>
         }
     }
 
-    pub fn get_entity_by_point_mut(&mut self, point: &VPoint) -> Option<&mut E> {
+    pub fn get_entity_by_point_mut(&mut self, point: &VPoint) -> Option<&mut E>
+    where
+        E: Clone,
+    {
         let entity_id = self.get_entity_id_at(point);
         if entity_id == EMPTY_XY_INDEX {
             None
@@ -438,34 +585,27 @@ impl<E> VEntityMap<E>
 
     pub fn iter_xy_entities_and_points(&self) -> impl Iterator<Item = (VPoint, Option<&E>)> {
         self.xy_to_entity
-            .as_slice()
             .iter()
             .enumerate()
             .map(|(index, entity_id)| {
                 let point = self.index_to_xy(index);
-                if *entity_id == EMPTY_XY_INDEX {
+                if entity_id == EMPTY_XY_INDEX {
                     (point, None)
                 } else {
-                    (point, Some(self.get_entity_by_index(*entity_id)))
+                    (point, Some(self.get_entity_by_index(entity_id)))
                 }
             })
     }
 
     //</editor-fold>
 
-    pub fn validate(&self)
-    where
-        E: Debug,
-    {
-        let mut checks = 0;
-        for entity_index in self.xy_to_entity.as_slice().iter() {
-            if *entity_index == EMPTY_XY_INDEX {
-                continue;
-            }
-            assert!(*entity_index < self.entities.len());
-            checks += 1;
-        }
-        debug!("validate {checks} checks");
+    /// Points whose xy index is past the entity list
+    pub fn find_dangling_xy(&self) -> Vec<VPoint> {
+        self.xy_to_entity
+            .iter_occupied()
+            .filter(|(_, entity_index)| *entity_index >= self.entities.len())
+            .map(|(xy_index, _)| self.index_to_xy(xy_index))
+            .collect()
     }
 }
 
@@ -494,15 +634,16 @@ impl VPixel {
 
 impl VEntityMap<VPixel> {
     pub fn iter_xy_pixels(&self) -> impl Iterator<Item = &Pixel> {
-        self.xy_to_entity.as_slice().iter().map(|index| {
-            if *index == EMPTY_XY_INDEX {
+        self.xy_to_entity.iter().map(|index| {
+            if index == EMPTY_XY_INDEX {
                 &Pixel::Empty
             } else {
-                self.entities[*index].pixel()
+                self.entities[index].pixel()
             }
         })
     }
 
+    #[cfg(feature = "opencv_re")]
     pub fn map_pixel_xy_to_cv(&self, filter: Option<Pixel>) -> GeneratedMat {
         let metrics = RefCell::new(FastMetrics::new("map_pixel_xy_to_cv".to_string()));
 
@@ -552,6 +693,183 @@ impl VEntityMap<VPixel> {
     // }
 }
 
+/// Multi-tile entity placed on the map, covering `width` x `height` from its top left position
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct VEntity {
+    /// Factorio prototype name
+    pub name: String,
+    pub position: VPoint,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl VEntity {
+    pub fn area(&self) -> VArea {
+        VArea::from_arbitrary_points_pair(
+            self.position,
+            self.position + VPoint::new(self.width as i32 - 1, self.height as i32 - 1),
+        )
+    }
+}
+
+impl VEntityMap<VEntity> {
+    /// Later placements win the shared positions, eg overlapping rail pieces
+    pub fn place(&mut self, entity: VEntity) {
+        let points = entity.area().get_points();
+        assert!(
+            !self.is_points_out_of_bounds_any(&points),
+            "entity out of bounds {entity:?}"
+        );
+
+        let entity_index = self.entities.len();
+        for point in &points {
+            let xy_index = self.point_to_index_unchecked(point);
+            self.set_xy(xy_index, entity_index);
+        }
+        self.entities.push(entity);
+    }
+
+    /// Placed entities covering any point of the area, out of bounds points are ignored
+    pub fn find_collisions(&self, area: &VArea) -> Vec<&VEntity> {
+        let mut entity_indexes: Vec<usize> = area
+            .get_points()
+            .iter()
+            .filter(|point| !self.is_point_out_of_bounds(point))
+            .map(|point| self.xy_to_entity.get(self.point_to_index_unchecked(point)))
+            .filter(|entity_index| *entity_index != EMPTY_XY_INDEX)
+            .collect();
+        entity_indexes.sort_unstable();
+        entity_indexes.dedup();
+        entity_indexes
+            .into_iter()
+            .map(|entity_index| &self.entities[entity_index])
+            .collect()
+    }
+}
+
+/// Dense `VArray` for regular maps, chunked for mostly empty huge maps. Same row-major indexes
+#[derive(Clone)]
+enum XyStorage {
+    Dense(VArray),
+    Chunked(VChunkArray),
+}
+
+impl XyStorage {
+    fn new_dense(radius: u32) -> Self {
+        XyStorage::Dense(VArray::new_length((radius as usize * 2).pow(2)))
+    }
+
+    fn new_chunked(radius: u32) -> Self {
+        XyStorage::Chunked(VChunkArray::new(radius as usize * 2))
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            XyStorage::Dense(xy) => xy.len(),
+            XyStorage::Chunked(xy) => xy.len(),
+        }
+    }
+
+    fn get(&self, index: usize) -> usize {
+        match self {
+            XyStorage::Dense(xy) => xy.as_slice()[index],
+            XyStorage::Chunked(xy) => xy.get(index),
+        }
+    }
+
+    fn set(&mut self, index: usize, entity_index: usize) {
+        match self {
+            XyStorage::Dense(xy) => xy.as_mut_slice()[index] = entity_index,
+            XyStorage::Chunked(xy) => xy.set(index, entity_index),
+        }
+    }
+
+    fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        match self {
+            XyStorage::Dense(xy) => Either::Left(xy.as_slice().iter().copied()),
+            XyStorage::Chunked(xy) => Either::Right(xy.iter()),
+        }
+    }
+
+    /// Non-empty `(xy index, entity index)`
+    fn iter_occupied(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        match self {
+            XyStorage::Dense(xy) => Either::Left(
+                xy.as_slice()
+                    .iter()
+                    .copied()
+                    .enumerate()
+                    .filter(|(_, entity_index)| *entity_index != EMPTY_XY_INDEX),
+            ),
+            XyStorage::Chunked(xy) => Either::Right(xy.iter_occupied()),
+        }
+    }
+
+    fn is_dirty_for_clone(&self) -> bool {
+        match self {
+            XyStorage::Dense(xy) => xy.is_dirty_for_clone(),
+            XyStorage::Chunked(xy) => xy.is_dirty_for_clone(),
+        }
+    }
+}
+
+// purely for serde deserialize
+impl Default for XyStorage {
+    fn default() -> Self {
+        XyStorage::Dense(VArray::default())
+    }
+}
+
+#[derive(Clone)]
+struct VJournal<E> {
+    /// xy index and the entity index it had before the write
+    xy_previous: Vec<(usize, usize)>,
+    /// entity index and its value before an in-place edit
+    entity_previous: Vec<(usize, E)>,
+    /// Entries dropped by `commit_before`, checkpoints count from the start
+    xy_committed: usize,
+    entity_committed: usize,
+}
+
+impl<E> VJournal<E> {
+    /// Checkpoint position in the remaining entries
+    fn relative(&self, checkpoint: &VCheckpoint) -> (usize, usize) {
+        let xy_start = checkpoint.xy_len.checked_sub(self.xy_committed);
+        let entity_edit_start = checkpoint
+            .entity_edit_len
+            .checked_sub(self.entity_committed);
+        match (xy_start, entity_edit_start) {
+            (Some(xy_start), Some(entity_edit_start))
+                if xy_start <= self.xy_previous.len()
+                    && entity_edit_start <= self.entity_previous.len() =>
+            {
+                (xy_start, entity_edit_start)
+            }
+            _ => panic!("checkpoint {checkpoint:?} is not from this journal"),
+        }
+    }
+}
+
+// derive would require E: Default
+impl<E> Default for VJournal<E> {
+    fn default() -> Self {
+        VJournal {
+            xy_previous: Vec::new(),
+            entity_previous: Vec::new(),
+            xy_committed: 0,
+            entity_committed: 0,
+        }
+    }
+}
+
+/// Point in a `VEntityMap` journal to roll back to
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct VCheckpoint {
+    xy_len: usize,
+    entity_edit_len: usize,
+    entities_len: usize,
+}
+
 /// One-stop collection of change operations
 pub struct VMapChange<'m, N, I: IntoIterator<Item = VPoint>> {
     map: &'m mut VEntityMap<N>,
@@ -567,8 +885,7 @@ impl<'m> VMapChange<'m, VPixel, Vec<VPoint>> {
 
         for position in &self.positions {
             let xy_index = self.map.point_to_index_unchecked(position);
-            let existing_entity_index = &mut self.map.xy_to_entity.as_mut_slice()[xy_index];
-            *existing_entity_index = entity_index;
+            self.map.set_xy(xy_index, entity_index);
         }
 
         assert_eq!(self.map.entities.len(), entity_index);
@@ -588,10 +905,9 @@ where
         for position in self.positions {
             // use safe since iterator can't pre-pass
             let xy_index = self.map.point_to_index_safe(&position);
-            let existing_entity_index = &mut self.map.xy_to_entity.as_mut_slice()[xy_index];
-            if *existing_entity_index == EMPTY_XY_INDEX {
+            if self.map.xy_to_entity.get(xy_index) == EMPTY_XY_INDEX {
                 // remove existing
-                *existing_entity_index = entity_index;
+                self.map.set_xy(xy_index, entity_index);
             }
         }
 
@@ -608,11 +924,11 @@ where
         for position in self.positions {
             // use safe since iterator can't pre-pass
             let xy_index = self.map.point_to_index_safe(&position);
-            let existing_entity_index = &mut self.map.xy_to_entity.as_mut_slice()[xy_index];
-            if *existing_entity_index != EMPTY_XY_INDEX
-                && self.map.entities[*existing_entity_index].pixel == find
+            let existing_entity_index = self.map.xy_to_entity.get(xy_index);
+            if existing_entity_index != EMPTY_XY_INDEX
+                && self.map.entities[existing_entity_index].pixel == find
             {
-                *existing_entity_index = entity_index;
+                self.map.set_xy(xy_index, entity_index);
             }
         }
 
@@ -625,14 +941,16 @@ where
             assert!(!self.map.is_point_out_of_bounds(&point));
 
             let xy_index = self.map.point_to_index_unchecked(&point);
-            self.map.xy_to_entity.as_mut_slice()[xy_index] = EMPTY_XY_INDEX;
+            self.map.set_xy(xy_index, EMPTY_XY_INDEX);
         }
     }
 }
 
 #[cfg(test)]
 mod test {
-    use crate::surfacev::ventity_map::{VEntityMap, VPixel};
+    use crate::surface::pixel::Pixel;
+    use crate::surfacev::ventity_map::{VEntity, VEntityMap, VPixel};
+    use facto_loop_miner_fac_engine::common::varea::VArea;
     use facto_loop_miner_fac_engine::common::vpoint::VPoint;
 
     #[test]
@@ -657,4 +975,114 @@ mod test {
             buffer.index_to_xy(buffer.xy_to_index_safe(test.x(), test.y())),
         );
     }
+
+    #[test]
+    pub fn chunked_matches_dense() {
+        let mut dense: VEntityMap<VPixel> = VEntityMap::new_dense(50);
+        let mut chunked: VEntityMap<VPixel> = VEntityMap::new_chunked(50);
+        for map in [&mut dense, &mut chunked] {
+            map.change(vec![VPoint::new(-50, -50), VPoint::new(10, 20)])
+                .stomp(Pixel::Coal);
+            map.change(vec![VPoint::new(10, 20), VPoint::new(11, 20)])
+                .find_into(Pixel::Coal, Pixel::Water);
+            map.change([VPoint::new(-50, -50)]).remove();
+            map.crop(40);
+        }
+        assert!(chunked.is_chunked());
+
+        let dense_xy: Vec<_> = dense.iter_xy_pixels().collect();
+        let chunked_xy: Vec<_> = chunked.iter_xy_pixels().collect();
+        assert_eq!(dense_xy, chunked_xy);
+        assert_eq!(
+            chunked
+                .get_entity_by_point(&VPoint::new(10, 20))
+                .map(VPixel::pixel),
+            Some(&Pixel::Water)
+        );
+        assert_eq!(chunked.get_entity_by_point(&VPoint::new(11, 20)), None);
+        assert!(!chunked.is_points_free_safe(&[VPoint::new(10, 20)]));
+        assert!(chunked.is_points_free_safe(&[VPoint::new(-39, 39)]));
+    }
+
+    #[test]
+    pub fn entity_footprint_collisions() {
+        let mut map: VEntityMap<VEntity> = VEntityMap::new_chunked(20);
+        map.place(VEntity {
+            name: "electric-mining-drill".to_string(),
+            position: VPoint::new(0, 0),
+            width: 3,
+            height: 3,
+        });
+        map.place(VEntity {
+            name: "small-electric-pole".to_string(),
+            position: VPoint::new(3, 0),
+            width: 1,
+            height: 1,
+        });
+
+        let drill_corner = map.get_entity_by_point(&VPoint::new(2, 2)).unwrap();
+        assert_eq!(drill_corner.name, "electric-mining-drill");
+        assert_eq!(map.get_entity_by_point(&VPoint::new(3, 1)), None);
+
+        let collisions = map.find_collisions(&VArea::from_arbitrary_points_pair(
+            VPoint::new(2, 0),
+            VPoint::new(25, 0),
+        ));
+        assert_eq!(collisions.len(), 2);
+        let far_area = VArea::from_radius(VPoint::new(-10, -10), 2);
+        assert!(map.find_collisions(&far_area).is_empty());
+    }
+
+    #[test]
+    pub fn journal_rollback_is_exact() {
+        let mut map: VEntityMap<VPixel> = VEntityMap::new(20);
+        map.change(vec![VPoint::new(1, 1), VPoint::new(2, 2)])
+            .stomp(Pixel::Coal);
+        let before: Vec<Pixel> = map.iter_xy_pixels().copied().collect();
+
+        let outer = map.begin();
+        map.change(vec![VPoint::new(2, 2), VPoint::new(3, 3)])
+            .stomp(Pixel::Rail);
+        let after_rail: Vec<Pixel> = map.iter_xy_pixels().copied().collect();
+
+        let inner = map.begin();
+        map.change([VPoint::new(1, 1)]).remove();
+        map.get_entity_by_point_mut(&VPoint::new(3, 3))
+            .unwrap()
+            .pixel = Pixel::Water;
+        map.rollback_to(inner);
+        assert_eq!(
+            map.iter_xy_pixels().copied().collect::<Vec<_>>(),
+            after_rail
+        );
+
+        map.rollback_to(outer);
+        assert_eq!(map.iter_xy_pixels().copied().collect::<Vec<_>>(), before);
+        // the Rail entity itself is gone too
+        assert_eq!(map.iter_entities().count(), 1);
+        map.commit();
+        assert!(!map.is_journaling());
+    }
+
+    #[test]
+    pub fn journal_commit_before_keeps_later() {
+        let mut map: VEntityMap<VPixel> = VEntityMap::new(20);
+        map.begin();
+        map.change(vec![VPoint::new(1, 1), VPoint::new(2, 2)])
+            .stomp(Pixel::Rail);
+        let after_first: Vec<Pixel> = map.iter_xy_pixels().copied().collect();
+
+        let second = map.begin();
+        map.change(vec![VPoint::new(3, 3)]).stomp(Pixel::Rail);
+        map.commit_before(second);
+        assert_eq!(map.journal.as_ref().unwrap().xy_previous.len(), 1);
+
+        map.begin();
+        map.change(vec![VPoint::new(4, 4)]).stomp(Pixel::Rail);
+        map.rollback_to(second);
+        assert_eq!(
+            map.iter_xy_pixels().copied().collect::<Vec<_>>(),
+            after_first
+        );
+    }
 }
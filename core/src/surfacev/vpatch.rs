@@ -1,4 +1,5 @@
 use crate::surface::pixel::Pixel;
+use crate::surfacev::vamount_map::VAmountMap;
 use derivative::Derivative;
 use facto_loop_miner_fac_engine::common::varea::VArea;
 use facto_loop_miner_fac_engine::common::vpoint::VPoint;
@@ -11,6 +12,12 @@ pub struct VPatch {
     pub area: VArea,
     #[derivative(Debug = "ignore")]
     pub pixel_indexes: Vec<VPoint>,
+    /// Sum of resource amount over `pixel_indexes`, 0 if the export had no amounts
+    #[serde(default)]
+    pub total_amount: u64,
+    /// Center of mass weighted by resource amount
+    #[serde(default)]
+    pub amount_centroid: Option<VPoint>,
 }
 
 const ALERT: i32 = 1000;
@@ -28,9 +35,47 @@ impl VPatch {
             resource,
             area,
             pixel_indexes,
+            total_amount: 0,
+            amount_centroid: None,
         }
     }
 
+    pub fn update_amounts(&mut self, amounts: &VAmountMap) {
+        let mut total: u64 = 0;
+        let mut weighted_x: i128 = 0;
+        let mut weighted_y: i128 = 0;
+        for point in &self.pixel_indexes {
+            let amount = amounts.get(point) as u64;
+            total += amount;
+            weighted_x += point.x() as i128 * amount as i128;
+            weighted_y += point.y() as i128 * amount as i128;
+        }
+        self.total_amount = total;
+        self.amount_centroid = if total == 0 {
+            None
+        } else {
+            Some(VPoint::new(
+                (weighted_x / total as i128) as i32,
+                (weighted_y / total as i128) as i32,
+            ))
+        };
+    }
+
+    /// Average amount per tile
+    pub fn mean_richness(&self) -> f32 {
+        if self.pixel_indexes.is_empty() {
+            0.0
+        } else {
+            self.total_amount as f32 / self.pixel_indexes.len() as f32
+        }
+    }
+
+    /// Richness weighted center, or area center without amounts
+    pub fn richness_centroid(&self) -> VPoint {
+        self.amount_centroid
+            .unwrap_or_else(|| self.area.point_center())
+    }
+
     // pub fn new_from_rect(rect: Rect, resource: Pixel, pixel_indexes: Vec<VPoint>) -> Self {
     //     if !resource.is_resource() {
     //         panic!("not a resource {:?}", resource);
@@ -56,3 +101,41 @@ impl VPatch {
     //     new
     // }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::surface::pixel::Pixel;
+    use crate::surfacev::vamount_map::VAmountMap;
+    use crate::surfacev::vpatch::VPatch;
+    use facto_loop_miner_fac_engine::common::varea::VArea;
+    use facto_loop_miner_fac_engine::common::vpoint::VPoint;
+
+    #[test]
+    fn test_richness() {
+        let pixel_indexes = vec![
+            VPoint::new(0, 0),
+            VPoint::new(4, 0),
+            VPoint::new(0, 4),
+            VPoint::new(4, 4),
+        ];
+        let area = VArea::from_arbitrary_points(&pixel_indexes);
+        let mut patch = VPatch::new(area.clone(), Pixel::IronOre, pixel_indexes);
+        assert_eq!(patch.mean_richness(), 0.0);
+        assert_eq!(patch.richness_centroid(), area.point_center());
+
+        let mut amounts = VAmountMap::new(10);
+        amounts.set(&VPoint::new(0, 0), 100);
+        amounts.set(&VPoint::new(4, 0), 300);
+        // outside the patch
+        amounts.set(&VPoint::new(8, 8), 5000);
+        patch.update_amounts(&amounts);
+        assert_eq!(patch.total_amount, 400);
+        assert_eq!(patch.mean_richness(), 100.0);
+        assert_eq!(patch.richness_centroid(), VPoint::new(3, 0));
+
+        patch.update_amounts(&VAmountMap::new(10));
+        assert_eq!(patch.total_amount, 0);
+        assert_eq!(patch.amount_centroid, None);
+        assert_eq!(patch.richness_centroid(), area.point_center());
+    }
+}
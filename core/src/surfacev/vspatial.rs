@@ -0,0 +1,307 @@
+use crate::surfacev::mine::MinePath;
+use crate::surfacev::vpatch::VPatch;
+use facto_loop_miner_fac_engine::common::varea::VArea;
+use facto_loop_miner_fac_engine::common::vpoint::VPoint;
+use facto_loop_miner_fac_engine::common::vpoint_direction::VPointDirectionQ;
+use facto_loop_miner_fac_engine::game_blocks::rail_hope::RailHopeLink;
+use rstar::{AABB, Envelope, PointDistance, RTree, RTreeObject};
+use std::collections::HashMap;
+use tracing::warn;
+
+/// What an indexed area belongs to
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SpatialKey {
+    /// Index in the surface patches
+    Patch(usize),
+    /// Buffered area of the mine served by the rail starting here
+    Mine(VPointDirectionQ),
+    /// One link of the rail starting here
+    Rail(VPointDirectionQ),
+}
+
+impl SpatialKey {
+    pub fn is_patch(&self) -> bool {
+        matches!(self, SpatialKey::Patch(_))
+    }
+
+    pub fn is_rail(&self) -> bool {
+        matches!(self, SpatialKey::Rail(_))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct SpatialEntry {
+    envelope: AABB<[i64; 2]>,
+    key: SpatialKey,
+}
+
+impl SpatialEntry {
+    fn new(area: &VArea, key: SpatialKey) -> Self {
+        SpatialEntry {
+            envelope: area_to_envelope(area),
+            key,
+        }
+    }
+}
+
+impl RTreeObject for SpatialEntry {
+    type Envelope = AABB<[i64; 2]>;
+
+    fn envelope(&self) -> Self::Envelope {
+        self.envelope
+    }
+}
+
+impl PointDistance for SpatialEntry {
+    fn distance_2(&self, point: &[i64; 2]) -> i64 {
+        self.envelope.distance_2(point)
+    }
+}
+
+/// R-tree over patch areas, mine areas and rail link footprints.
+/// Not persisted, rebuilt on load and kept in sync by the patch and rail plugs
+#[derive(Clone, Default)]
+pub struct VSpatialIndex {
+    tree: RTree<SpatialEntry>,
+    /// Rail keys back to their index in the surface rails
+    rail_indexes: HashMap<VPointDirectionQ, usize>,
+}
+
+impl VSpatialIndex {
+    pub fn from_surface(patches: &[VPatch], rails: &[MinePath]) -> Self {
+        let mut entries = patch_entries(patches);
+        let mut rail_indexes = HashMap::with_capacity(rails.len());
+        for (path_index, path) in rails.iter().enumerate() {
+            entries.extend(path_entries(path));
+            rail_indexes.insert(path.segment.start, path_index);
+        }
+        VSpatialIndex {
+            tree: RTree::bulk_load(entries),
+            rail_indexes,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.tree.size()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tree.size() == 0
+    }
+
+    pub fn insert_path(&mut self, path: &MinePath, path_index: usize) {
+        for entry in path_entries(path) {
+            self.tree.insert(entry);
+        }
+        self.rail_indexes.insert(path.segment.start, path_index);
+    }
+
+    /// Later rail indexes shift down, like `Vec::remove`
+    pub fn remove_path(&mut self, path: &MinePath, path_index: usize) {
+        for entry in path_entries(path) {
+            if self.tree.remove(&entry).is_none() {
+                warn!("spatial index missing {:?}", entry.key);
+            }
+        }
+        self.rail_indexes.remove(&path.segment.start);
+        // removing the last rail, eg a rollback, is the common case
+        if path_index < self.rail_indexes.len() {
+            for index in self.rail_indexes.values_mut() {
+                if *index > path_index {
+                    *index -= 1;
+                }
+            }
+        }
+    }
+
+    /// Index in the surface rails of a [SpatialKey::Rail] or [SpatialKey::Mine] start
+    pub fn rail_index(&self, start: &VPointDirectionQ) -> Option<usize> {
+        self.rail_indexes.get(start).copied()
+    }
+
+    /// Patch indexes shift on removal, so every patch entry is rebuilt
+    pub fn replace_patches(&mut self, patches: &[VPatch]) {
+        let mut entries: Vec<SpatialEntry> = self
+            .tree
+            .iter()
+            .filter(|entry| !entry.key.is_patch())
+            .cloned()
+            .collect();
+        entries.extend(patch_entries(patches));
+        self.tree = RTree::bulk_load(entries);
+    }
+
+    /// Closest matching key with its squared distance, 0 if the point is inside
+    pub fn nearest(
+        &self,
+        point: VPoint,
+        filter: impl Fn(&SpatialKey) -> bool,
+    ) -> Option<(SpatialKey, i64)> {
+        self.tree
+            .nearest_neighbor_iter_with_distance_2(&point_to_array(point))
+            .find(|(entry, _)| filter(&entry.key))
+            .map(|(entry, distance_2)| (entry.key, distance_2))
+    }
+
+    pub fn intersecting(&self, area: &VArea) -> impl Iterator<Item = SpatialKey> + '_ {
+        self.tree
+            .locate_in_envelope_intersecting(&area_to_envelope(area))
+            .map(|entry| entry.key)
+    }
+
+    /// Keys with any part of their area within distance of the point
+    pub fn within_distance(
+        &self,
+        point: VPoint,
+        distance: f32,
+    ) -> impl Iterator<Item = SpatialKey> + '_ {
+        let distance_2 = (distance * distance).ceil() as i64;
+        self.tree
+            .locate_within_distance(point_to_array(point), distance_2)
+            .map(|entry| entry.key)
+    }
+}
+
+fn patch_entries(patches: &[VPatch]) -> Vec<SpatialEntry> {
+    patches
+        .iter()
+        .enumerate()
+        .map(|(patch_index, patch)| SpatialEntry::new(&patch.area, SpatialKey::Patch(patch_index)))
+        .collect()
+}
+
+fn path_entries(path: &MinePath) -> impl Iterator<Item = SpatialEntry> + '_ {
+    let start = path.segment.start;
+    let mine = SpatialEntry::new(path.location.area_buffered(), SpatialKey::Mine(start));
+    let links = path.links.iter().map(move |link| {
+        SpatialEntry::new(
+            &VArea::from_arbitrary_points(link.area_vec()),
+            SpatialKey::Rail(start),
+        )
+    });
+    std::iter::once(mine).chain(links)
+}
+
+fn point_to_array(point: VPoint) -> [i64; 2] {
+    [point.x() as i64, point.y() as i64]
+}
+
+fn area_to_envelope(area: &VArea) -> AABB<[i64; 2]> {
+    AABB::from_corners(
+        point_to_array(area.point_top_left()),
+        point_to_array(area.point_bottom_right()),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use crate::surface::pixel::Pixel;
+    use crate::surfacev::mine::MinePath;
+    use crate::surfacev::vpatch::VPatch;
+    use crate::surfacev::vspatial::{SpatialKey, VSpatialIndex};
+    use crate::surfacev::vsurface::{
+        VSurface, VSurfacePixelAsVs, VSurfaceRailAsVs, VSurfaceRailAsVsMut,
+    };
+    use facto_loop_miner_fac_engine::common::varea::VArea;
+    use facto_loop_miner_fac_engine::common::vpoint::VPoint;
+    use facto_loop_miner_fac_engine::common::vpoint_direction::VPointDirectionQ;
+    use facto_loop_miner_fac_engine::game_blocks::rail_hope_single::SECTION_POINTS_I32;
+    use facto_loop_miner_fac_engine::game_entities::direction::FacDirectionQuarter;
+
+    fn test_patch(x: i32, y: i32) -> VPatch {
+        VPatch {
+            resource: Pixel::IronOre,
+            area: VArea::from_arbitrary_points_pair(VPoint::new(x, y), VPoint::new(x + 9, y + 9)),
+            pixel_indexes: Vec::new(),
+            total_amount: 0,
+            amount_centroid: None,
+        }
+    }
+
+    #[test]
+    fn test_patch_queries() {
+        let mut index = VSpatialIndex::from_surface(
+            &[test_patch(0, 0), test_patch(100, 0), test_patch(0, 300)],
+            &[],
+        );
+        assert_eq!(index.len(), 3);
+
+        let nearest = index.nearest(VPoint::new(95, 5), SpatialKey::is_patch);
+        assert_eq!(nearest, Some((SpatialKey::Patch(1), 25)));
+        assert_eq!(index.nearest(VPoint::new(95, 5), SpatialKey::is_rail), None);
+
+        let mut near: Vec<_> = index.within_distance(VPoint::new(50, 5), 60.0).collect();
+        near.sort_by_key(|key| format!("{key:?}"));
+        assert_eq!(near, [SpatialKey::Patch(0), SpatialKey::Patch(1)]);
+
+        let crossing = VArea::from_arbitrary_points_pair(VPoint::new(5, 5), VPoint::new(5, 305));
+        assert_eq!(index.intersecting(&crossing).count(), 2);
+
+        index.replace_patches(&[test_patch(0, 300)]);
+        assert_eq!(
+            index.intersecting(&crossing).collect::<Vec<_>>(),
+            [SpatialKey::Patch(0)]
+        );
+    }
+
+    #[test]
+    fn test_rail_plug_sync() {
+        let mut surface = VSurface::new(400);
+        let paths = [-4, 0, 4].map(|row| {
+            let start = VPoint::new(-4 * SECTION_POINTS_I32, row * SECTION_POINTS_I32);
+            MinePath::new_test_straight(
+                surface.pixels(),
+                VPointDirectionQ(start, FacDirectionQuarter::East),
+                4,
+            )
+        });
+        let starts = paths.each_ref().map(|v| v.segment.start);
+        let rails_at = |surface: &VSurface, path_index: usize| {
+            let probe = VArea::from_radius(starts[path_index].0, 4);
+            surface
+                .spatial
+                .intersecting(&probe)
+                .filter(|key| *key == SpatialKey::Rail(starts[path_index]))
+                .count()
+        };
+
+        let [first, second, third] = paths;
+        surface.rails_mut().add_mine_path(first);
+        surface.rails_mut().add_mine_path(second);
+        let checkpoint = surface.rails_mut().begin();
+        surface.rails_mut().add_mine_path(third);
+        for path_index in 0..3 {
+            assert_eq!(
+                surface
+                    .rails()
+                    .nearest_mine_path_index(starts[path_index].0),
+                Some(path_index)
+            );
+            assert_ne!(rails_at(&surface, path_index), 0);
+        }
+
+        surface.rails_mut().remove_mine_path_at(0).unwrap();
+        assert_eq!(rails_at(&surface, 0), 0);
+        assert_eq!(
+            surface.rails().nearest_mine_path_index(starts[1].0),
+            Some(0)
+        );
+        assert_eq!(
+            surface.rails().nearest_mine_path_index(starts[2].0),
+            Some(1)
+        );
+
+        let removed = surface.rails_mut().rollback_to(checkpoint);
+        assert_eq!(removed.len(), 1);
+        assert_eq!(rails_at(&surface, 2), 0);
+        assert_eq!(
+            surface.rails().nearest_mine_path_index(starts[2].0),
+            Some(0)
+        );
+        // nothing left behind
+        assert_eq!(
+            surface.spatial.len(),
+            VSpatialIndex::from_surface(&[], surface.rails().get_mine_paths()).len()
+        );
+    }
+}
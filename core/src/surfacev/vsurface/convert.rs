@@ -11,7 +11,7 @@ macro_rules! vs_impl_for {
         vs_impl_for!(@plug_impl
             patch => $target_mod,
             patches_mut => patches,
-            patches, pixels,
+            patches, pixels, spatial,
         );
     };
 
@@ -19,7 +19,7 @@ macro_rules! vs_impl_for {
         vs_impl_for!(@plug_impl
             rail => $target_mod,
             rails_mut => rails,
-            rails, pixels,
+            rails, pixels, spatial, failed_mines,
         );
     };
 
@@ -72,8 +72,9 @@ macro_rules! vs_builder {
     }
 }
 vs_builder!(pixel, pixels, pixels,);
-vs_builder!(patch, patches, patches, pixels,);
-vs_builder!(rail, rails, rails, pixels,);
+vs_builder!(patch, patches, patches, pixels, spatial,);
+vs_builder!(rail, rails, rails, pixels, spatial, failed_mines,);
+vs_builder!(entity, entities, entities,);
 
 macro_rules! vs_main {
     (
@@ -107,26 +108,32 @@ vs_main!(
     super::core::VSurface,
     patch,
     patches_mut => patches,
-    patches, pixels,
+    patches, pixels, spatial,
 );
 vs_main!(
     super::core::VSurface,
     rail,
     rails_mut => rails,
-    rails, pixels,
+    rails, pixels, spatial, failed_mines,
+);
+vs_main!(
+    super::core::VSurface,
+    entity,
+    entities_mut => entities,
+    entities,
 );
 vs_main!(
     super::core::VSurface,
     nav,
     nav_mut => nav,
-    rails, patches, pixels,
+    rails, patches, pixels, spatial, failed_mines,
 );
 
 vs_main!(
     super::rail::PlugCopy,
     rail,
     rails_mut => rails,
-    rails, pixels,
+    rails, pixels, spatial, failed_mines,
 );
 vs_main!(
     super::rail::PlugCopy,
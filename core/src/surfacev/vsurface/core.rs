@@ -2,10 +2,16 @@ use crate::state::machine::StepParams;
 use crate::state::tuneables::Tunables;
 use crate::surface::pixel::Pixel;
 use crate::surfacev::err::{CoreConvertPathResult, VResult};
-use crate::surfacev::mine::MinePath;
-use crate::surfacev::ventity_map::{VEntityMap, VPixel};
+use crate::surfacev::mine::{MineLocation, MinePath};
+use crate::surfacev::vamount_map::VAmountMap;
+use crate::surfacev::ventity_map::{VEntity, VEntityMap, VPixel};
 use crate::surfacev::vpatch::VPatch;
+use crate::surfacev::vspatial::VSpatialIndex;
+use crate::surfacev::vsurface::SurfaceValidation;
 use crate::surfacev::vsurface::pixel::AsVs;
+use crate::surfacev::vsurface::state_file::{
+    STATE_VERSION, SurfaceState, SurfaceStateRef, invalid_state, read_state, write_state,
+};
 use facto_loop_miner_common::duration::BasicWatch;
 use facto_loop_miner_io::{read_entire_file, write_entire_file};
 use serde::{Deserialize, Serialize};
@@ -25,10 +31,20 @@ use tracing::{debug, info, trace};
 #[derive(Serialize, Deserialize)]
 pub struct VSurface {
     pub(crate) pixels: VEntityMap<VPixel>,
-    // entities: VEntityMap<VEntity>,
+    /// Placed multi-tile entities. Empty until built, old JSON states are migrated on load
+    #[serde(default = "empty_entities")]
+    pub(crate) entities: VEntityMap<VEntity>,
     pub(crate) patches: Vec<VPatch>,
     #[serde(default)]
     pub(crate) rails: Vec<MinePath>,
+    /// Mines a planner gave up on, kept for the plan overlay
+    #[serde(default)]
+    pub(crate) failed_mines: Vec<MineLocation>,
+    #[serde(default)]
+    pub(crate) amounts: VAmountMap,
+    /// Derived from patches and rails on load
+    #[serde(skip)]
+    pub(crate) spatial: VSpatialIndex,
     #[serde(skip, default = "Tunables::new")]
     tunables: Tunables,
 }
@@ -39,9 +55,13 @@ impl VSurface {
     pub fn new(radius: u32) -> Self {
         VSurface {
             pixels: VEntityMap::new(radius),
-            // entities: VEntityMap::new(radius),
+            // mostly empty even on small surfaces
+            entities: VEntityMap::new_chunked(radius),
             patches: Vec::new(),
             rails: Vec::new(),
+            failed_mines: Vec::new(),
+            amounts: VAmountMap::new(radius),
+            spatial: VSpatialIndex::default(),
             tunables: Tunables::new(),
         }
     }
@@ -52,17 +72,31 @@ impl VSurface {
 
         let surface_out_dir = out_dir.to_path_buf();
         let surface_thread = thread::spawn(move || Self::load_state(&surface_out_dir));
-        // let (pixel_thread, entity_thread) = Self::new(1).load_entity_buffers(out_dir);
-        let (pixel_thread,) = Self::new(1).load_entity_buffers(out_dir);
+        let (pixel_thread, entity_thread) = Self::new(1).load_entity_buffers(out_dir);
 
         let mut new_surface = surface_thread.join().expect("surface join failed")?;
 
-        // new_surface
-        //     .entities
-        //     .load_xy_from_other(entity_thread.join().expect("entity thread failed")?);
         new_surface
             .pixels
             .load_xy_from_other(pixel_thread.join().expect("pixel thread failed")?);
+        let radius = new_surface.pixels.radius();
+        match entity_thread.join().expect("entity thread failed")? {
+            Some(entities) => new_surface.entities.load_xy_from_other(entities),
+            None => {
+                // saved before the entity layer existed
+                if new_surface.entities.iter_entities().next().is_some() {
+                    return Err(invalid_state(
+                        &path_entity_xy_indexes(out_dir),
+                        "missing, but the state has entities",
+                    ));
+                }
+                new_surface.entities = VEntityMap::new_chunked(radius);
+            }
+        }
+        new_surface
+            .amounts
+            .load_file_or_empty(&path_amount_xy(out_dir), radius)?;
+        new_surface.spatial = VSpatialIndex::from_surface(&new_surface.patches, &new_surface.rails);
 
         // todo: error check
         // new_surface.pixels.assert_no_empty_pixels();
@@ -78,6 +112,11 @@ impl VSurface {
     // }
 
     fn load_state(out_dir: &Path) -> VResult<Self> {
+        let binary_path = path_state_binary(out_dir);
+        if binary_path.exists() {
+            return Self::_load_state_binary(&binary_path);
+        }
+        // Version 1 before the binary format, migrated by the next save
         match 1 {
             0 => Self::_load_state_sequential(out_dir),
             1 => Self::_load_state_reader(out_dir),
@@ -85,6 +124,40 @@ impl VSurface {
         }
     }
 
+    fn _load_state_binary(path: &Path) -> VResult<Self> {
+        let total_watch = BasicWatch::start();
+        let data = read_entire_file(path, true).convert(path)?;
+        let SurfaceState {
+            version,
+            pixels,
+            entities,
+            patches,
+            rails,
+            failed_mines,
+            amounts,
+        } = read_state(&data, path)?;
+        let migrated = if version == STATE_VERSION {
+            String::new()
+        } else {
+            format!(" migrated to v{STATE_VERSION}")
+        };
+        info!(
+            "Loading state v{version}{migrated} in {} from {}",
+            total_watch,
+            path.display(),
+        );
+        Ok(VSurface {
+            pixels,
+            entities,
+            patches,
+            rails,
+            failed_mines,
+            amounts,
+            spatial: VSpatialIndex::default(),
+            tunables: Tunables::new(),
+        })
+    }
+
     fn _load_state_sequential(out_dir: &Path) -> VResult<Self> {
         let mut read_watch = BasicWatch::start();
         let path = path_state(out_dir);
@@ -118,17 +191,13 @@ impl VSurface {
     }
 
     #[allow(clippy::type_complexity)]
-    // fn load_entity_buffers(
-    //     &mut self,
-    //     out_dir: &Path,
-    // ) -> (
-    //     JoinHandle<VResult<VEntityMap<VPixel>>>,
-    //     JoinHandle<VResult<VEntityMap<VEntity>>>,
-    // ) {
     fn load_entity_buffers(
         &mut self,
         out_dir: &Path,
-    ) -> (JoinHandle<VResult<VEntityMap<VPixel>>>,) {
+    ) -> (
+        JoinHandle<VResult<VEntityMap<VPixel>>>,
+        JoinHandle<VResult<Option<VEntityMap<VEntity>>>>,
+    ) {
         let out_dir_buf = out_dir.to_path_buf();
         let pixel_thread = thread::Builder::new()
             .name("pixel-loader".to_string())
@@ -140,23 +209,27 @@ impl VSurface {
             })
             .unwrap();
 
-        // let out_dir_buf = out_dir.to_path_buf();
-        // let entity_thread = thread::Builder::new()
-        //     .name("entity-loader".to_string())
-        //     .spawn(move || {
-        //         trace!("start entity thread");
-        //         let entity_path = &path_entity_xy_indexes(&out_dir_buf);
-        //         let mut buffer = VEntityMap::<VEntity>::new(0);
-        //         buffer.load_xy_file(entity_path).map(|_| buffer)
-        //     })
-        //     .unwrap();
-
-        // (pixel_thread, entity_thread)
-        (pixel_thread,)
+        let out_dir_buf = out_dir.to_path_buf();
+        let entity_thread = thread::Builder::new()
+            .name("entity-loader".to_string())
+            .spawn(move || {
+                trace!("start entity thread");
+                let entity_path = &path_entity_xy_indexes(&out_dir_buf);
+                if !entity_path.exists() {
+                    return Ok(None);
+                }
+                let mut buffer = VEntityMap::<VEntity>::new_chunked(0);
+                buffer.load_xy_file(entity_path).map(|_| Some(buffer))
+            })
+            .unwrap();
+
+        (pixel_thread, entity_thread)
     }
 
     pub fn load_from_last_step(params: &StepParams) -> VResult<Self> {
-        Self::load(params.previous_step_dir())
+        let mut surface = Self::load(params.previous_step_dir())?;
+        surface.set_tunables(params.tunables.clone());
+        Ok(surface)
     }
 
     pub fn path_pixel_buffer_from_last_step(params: &StepParams) -> PathBuf {
@@ -178,12 +251,49 @@ impl VSurface {
             .save_to_file(out_dir)?;
         self.save_entity_buffers(out_dir)?;
         self.save_tuning_parameters(out_dir)?;
+        if self.tunables.save.validate {
+            let validation = self.validate();
+            validation.log_report();
+            validation.save(out_dir)?;
+        }
         info!("+++ Saved in {} to {}", total_save_watch, out_dir.display());
         Ok(())
     }
 
     fn save_state(&self, out_dir: &Path) -> VResult<()> {
-        let state_path = out_dir.join("vsurface-state.json");
+        let state_path = path_state_binary(out_dir);
+        let save_watch = BasicWatch::start();
+        let mut data = Vec::new();
+        write_state(
+            &mut data,
+            &SurfaceStateRef {
+                pixels: &self.pixels,
+                entities: &self.entities,
+                patches: &self.patches,
+                rails: &self.rails,
+                failed_mines: &self.failed_mines,
+                amounts: &self.amounts,
+                tunables: &self.tunables,
+            },
+            &state_path,
+        )?;
+        write_entire_file(&state_path, &data).convert(&state_path)?;
+        debug!(
+            "Saving state v{STATE_VERSION} {} bytes in {} to {}",
+            data.len(),
+            save_watch,
+            state_path.display(),
+        );
+
+        if self.tunables.save.json_state {
+            self.save_state_json(out_dir)?;
+        }
+        Ok(())
+    }
+
+    /// Debug export, not loaded when the binary state exists
+    fn save_state_json(&self, out_dir: &Path) -> VResult<()> {
+        let state_path = path_state(out_dir);
 
         let mut serialize_watch = BasicWatch::start();
         let data = simd_json::to_vec(self).convert(&state_path)?;
@@ -206,8 +316,10 @@ impl VSurface {
         let pixel_path = path_pixel_xy_indexes(out_dir);
         self.pixels.save_xy_file(&pixel_path)?;
 
-        // let entity_path = path_entity_xy_indexes(out_dir);
-        // self.entities.save_xy_file(&entity_path)?;
+        self.amounts.save_file(&path_amount_xy(out_dir))?;
+
+        let entity_path = path_entity_xy_indexes(out_dir);
+        self.entities.save_xy_file(&entity_path)?;
 
         Ok(())
     }
@@ -221,22 +333,44 @@ impl VSurface {
         Ok(())
     }
 
+    pub fn amounts(&self) -> &VAmountMap {
+        &self.amounts
+    }
+
+    pub fn amounts_mut(&mut self) -> &mut VAmountMap {
+        &mut self.amounts
+    }
+
     pub fn tunables(&self) -> &Tunables {
         &self.tunables
     }
+
+    /// Not persisted, each run uses the tunables it was started with
+    pub fn set_tunables(&mut self, tunables: Tunables) {
+        self.tunables = tunables;
+    }
+
+    pub fn validate(&self) -> SurfaceValidation {
+        SurfaceValidation::new(self)
+    }
 }
 
 impl Display for VSurface {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "VSurface pixels {{ {} }} patches {{ {} }}",
+            "VSurface pixels {{ {} }} entities {{ {} }} patches {{ {} }}",
             self.pixels,
+            self.entities,
             display_patches(&self.patches)
         )
     }
 }
 
+fn empty_entities() -> VEntityMap<VEntity> {
+    VEntityMap::new_chunked(0)
+}
+
 fn display_patches(patches: &Vec<VPatch>) -> String {
     let mut map: HashMap<Pixel, usize> = HashMap::new();
     for patch in patches {
@@ -257,16 +391,24 @@ fn path_pixel_xy_indexes(out_dir: &Path) -> PathBuf {
     out_dir.join("pixel-xy-indexes.dat")
 }
 
+fn path_amount_xy(out_dir: &Path) -> PathBuf {
+    out_dir.join("amount-xy.dat")
+}
+
 pub(super) fn path_pixel_xy_indexes_clone() -> PathBuf {
     Path::new("/tmp/pixel-xy-indexes-clone.dat").into()
 }
 
-// fn path_entity_xy_indexes(out_dir: &Path) -> PathBuf {
-//     out_dir.join("entity-xy-indexes.dat")
-// }
+fn path_entity_xy_indexes(out_dir: &Path) -> PathBuf {
+    out_dir.join("entity-xy-indexes.dat")
+}
 
 fn path_state(out_dir: &Path) -> PathBuf {
     out_dir.join("vsurface-state.json")
 }
 
+fn path_state_binary(out_dir: &Path) -> PathBuf {
+    out_dir.join("vsurface-state.bin")
+}
+
 //</editor-fold>
@@ -0,0 +1,221 @@
+use crate::surface::pixel::Pixel;
+use crate::surfacev::mine::MinePath;
+use crate::surfacev::vsurface::{VSurface, VSurfacePixel, VSurfacePixelAsVs, VSurfaceRailAsVs};
+use facto_loop_miner_common::LOCALE;
+use facto_loop_miner_fac_engine::common::vpoint_direction::VSegment;
+use num_format::ToFormattedString;
+use std::collections::BTreeMap;
+use tracing::info;
+
+/// What changed between two step outputs of the same map
+#[derive(Debug, Default)]
+pub struct SurfaceDiff {
+    /// Only pixels whose points changed
+    pub pixels: BTreeMap<Pixel, PixelDelta>,
+    pub changed_points: usize,
+    pub paths_added: Vec<VSegment>,
+    pub paths_removed: Vec<VSegment>,
+    /// Same segment with different links
+    pub paths_rerouted: Vec<VSegment>,
+    pub cost_before: u64,
+    pub cost_after: u64,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct PixelDelta {
+    pub before: usize,
+    pub after: usize,
+    /// Points that became this pixel
+    pub gained: usize,
+    /// Points that were this pixel and now are something else
+    pub lost: usize,
+}
+
+impl SurfaceDiff {
+    pub fn new(before: &VSurface, after: &VSurface) -> Self {
+        let mut diff = SurfaceDiff::default();
+        diff.diff_pixels(before.pixels(), after.pixels());
+        diff.diff_paths(
+            before.rails().get_mine_paths(),
+            after.rails().get_mine_paths(),
+        );
+        diff
+    }
+
+    fn diff_pixels(&mut self, before: VSurfacePixel, after: VSurfacePixel) {
+        let mut counts: BTreeMap<Pixel, PixelDelta> = BTreeMap::new();
+        for (_, pixel) in before.get_pixels_all() {
+            counts.entry(pixel).or_default().before += 1;
+        }
+        for (_, pixel) in after.get_pixels_all() {
+            counts.entry(pixel).or_default().after += 1;
+        }
+
+        // Surfaces may have different radius after cropping, missing points count as Empty
+        let (larger, smaller, larger_is_after) = if after.get_radius() >= before.get_radius() {
+            (after, before, true)
+        } else {
+            (before, after, false)
+        };
+        for (point, larger_pixel) in larger.get_pixels_all() {
+            let smaller_pixel = if smaller.is_point_out_of_bounds(&point) {
+                Pixel::Empty
+            } else {
+                smaller.get_pixel(point)
+            };
+            if larger_pixel == smaller_pixel {
+                continue;
+            }
+            let (before_pixel, after_pixel) = if larger_is_after {
+                (smaller_pixel, larger_pixel)
+            } else {
+                (larger_pixel, smaller_pixel)
+            };
+            self.changed_points += 1;
+            counts.entry(before_pixel).or_default().lost += 1;
+            counts.entry(after_pixel).or_default().gained += 1;
+        }
+
+        counts.retain(|_, delta| delta.gained != 0 || delta.lost != 0);
+        self.pixels = counts;
+    }
+
+    fn diff_paths(&mut self, before: &[MinePath], after: &[MinePath]) {
+        let before_paths: BTreeMap<&VSegment, &MinePath> =
+            before.iter().map(|path| (&path.segment, path)).collect();
+        let after_paths: BTreeMap<&VSegment, &MinePath> =
+            after.iter().map(|path| (&path.segment, path)).collect();
+
+        for (segment, after_path) in &after_paths {
+            match before_paths.get(segment) {
+                None => self.paths_added.push((*segment).clone()),
+                Some(before_path) if before_path.links != after_path.links => {
+                    self.paths_rerouted.push((*segment).clone())
+                }
+                Some(_) => {}
+            }
+        }
+        self.paths_removed = before_paths
+            .keys()
+            .filter(|segment| !after_paths.contains_key(*segment))
+            .map(|segment| (*segment).clone())
+            .collect();
+
+        self.cost_before = before.iter().map(|path| path.cost as u64).sum();
+        self.cost_after = after.iter().map(|path| path.cost as u64).sum();
+    }
+
+    pub fn cost_change(&self) -> i64 {
+        self.cost_after as i64 - self.cost_before as i64
+    }
+
+    pub fn log_report(&self) {
+        info!(
+            "Changed {} points",
+            self.changed_points.to_formatted_string(&LOCALE)
+        );
+        for (pixel, delta) in &self.pixels {
+            info!(
+                "Pixel {pixel:?} before {} after {} gained {} lost {}",
+                delta.before.to_formatted_string(&LOCALE),
+                delta.after.to_formatted_string(&LOCALE),
+                delta.gained.to_formatted_string(&LOCALE),
+                delta.lost.to_formatted_string(&LOCALE),
+            );
+        }
+        for (name, segments) in [
+            ("added", &self.paths_added),
+            ("removed", &self.paths_removed),
+            ("rerouted", &self.paths_rerouted),
+        ] {
+            info!("Mine paths {name} {}", segments.len());
+            for segment in segments {
+                info!("  {segment}");
+            }
+        }
+        info!(
+            "Total cost {} -> {} ({:+})",
+            self.cost_before.to_formatted_string(&LOCALE),
+            self.cost_after.to_formatted_string(&LOCALE),
+            self.cost_change()
+        );
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::surface::pixel::Pixel;
+    use crate::surfacev::mine::MinePath;
+    use crate::surfacev::vsurface::diff::{PixelDelta, SurfaceDiff};
+    use crate::surfacev::vsurface::{VSurface, VSurfacePixelAsVs, VSurfacePixelAsVsMut};
+    use facto_loop_miner_fac_engine::common::vpoint::VPoint;
+    use facto_loop_miner_fac_engine::common::vpoint_direction::VPointDirectionQ;
+    use facto_loop_miner_fac_engine::game_blocks::rail_hope_single::SECTION_POINTS_I32;
+    use facto_loop_miner_fac_engine::game_entities::direction::FacDirectionQuarter;
+
+    #[test]
+    fn test_pixel_delta() {
+        let mut before = VSurface::new(10);
+        before
+            .pixels_mut()
+            .change_pixels(vec![VPoint::new(1, 1), VPoint::new(2, 2)])
+            .stomp(Pixel::Rail);
+        let mut after = VSurface::new(10);
+        after
+            .pixels_mut()
+            .change_pixels(vec![
+                VPoint::new(2, 2),
+                VPoint::new(3, 3),
+                VPoint::new(4, 4),
+            ])
+            .stomp(Pixel::Rail);
+
+        let diff = SurfaceDiff::new(&before, &after);
+        assert_eq!(diff.changed_points, 3);
+        assert_eq!(
+            diff.pixels.get(&Pixel::Rail),
+            Some(&PixelDelta {
+                before: 2,
+                after: 3,
+                gained: 2,
+                lost: 1,
+            })
+        );
+        assert_eq!(diff.pixels.len(), 2);
+        assert!(diff.paths_added.is_empty());
+        assert_eq!(diff.cost_change(), 0);
+    }
+
+    #[test]
+    fn test_path_changes() {
+        let surface = VSurface::new(400);
+        let path = |row: i32| {
+            let start = VPoint::new(-4 * SECTION_POINTS_I32, row * SECTION_POINTS_I32);
+            MinePath::new_test_straight(
+                surface.pixels(),
+                VPointDirectionQ(start, FacDirectionQuarter::East),
+                4,
+            )
+        };
+        let kept = path(0);
+        let removed = path(2);
+        let rerouted = path(4);
+        let added = path(6);
+        let mut rerouted_after = rerouted.clone();
+        rerouted_after.links.reverse();
+        rerouted_after.cost += 10;
+
+        let mut diff = SurfaceDiff::default();
+        diff.diff_paths(
+            &[kept.clone(), removed.clone(), rerouted.clone()],
+            &[rerouted_after, kept, added.clone()],
+        );
+        assert_eq!(diff.paths_added, [added.segment]);
+        assert_eq!(diff.paths_removed, [removed.segment]);
+        assert_eq!(diff.paths_rerouted, [rerouted.segment]);
+        assert_eq!(
+            diff.cost_change(),
+            10 - removed.cost as i64 + added.cost as i64
+        );
+    }
+}
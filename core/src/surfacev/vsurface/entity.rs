@@ -0,0 +1,99 @@
+use crate::surfacev::ventity_map::{VEntity, VEntityMap};
+use facto_loop_miner_common::LOCALE;
+use facto_loop_miner_fac_engine::blueprint::output::FacItemPlaced;
+use facto_loop_miner_fac_engine::common::varea::VArea;
+use facto_loop_miner_fac_engine::common::vpoint::VPoint;
+use num_format::ToFormattedString;
+use std::collections::BTreeMap;
+use tracing::{info, warn};
+
+pub struct PlugMut<'s> {
+    pub(super) entities: &'s mut VEntityMap<VEntity>,
+}
+
+impl<'s> PlugMut<'s> {
+    pub fn crop(&mut self, new_radius: u32) {
+        self.entities.crop(new_radius);
+    }
+
+    pub fn place_entity(&mut self, entity: VEntity) {
+        self.entities.place(entity);
+    }
+
+    /// Record everything a `FacItemOutput` wrote. Entities outside the surface are skipped
+    pub fn place_output(&mut self, placed: Vec<FacItemPlaced>) {
+        let total = placed.len();
+        let mut outside = 0;
+        for FacItemPlaced {
+            name,
+            position,
+            size,
+        } in placed
+        {
+            let entity = VEntity {
+                name: name.to_fac_name(),
+                position,
+                width: *size.width() as u32,
+                height: *size.height() as u32,
+            };
+            let area = entity.area();
+            if self.entities.is_point_out_of_bounds(&area.point_top_left())
+                || self
+                    .entities
+                    .is_point_out_of_bounds(&area.point_bottom_right())
+            {
+                outside += 1;
+                continue;
+            }
+            self.entities.place(entity);
+        }
+        if outside != 0 {
+            warn!("Skipped {outside} of {total} placed entities outside the surface");
+        }
+        info!(
+            "Recorded {} placed entities",
+            (total - outside).to_formatted_string(&LOCALE)
+        );
+    }
+}
+
+pub struct Plug<'s> {
+    pub(super) entities: &'s VEntityMap<VEntity>,
+}
+
+impl<'s> Plug<'s> {
+    pub fn get_entity_at(&self, point: &VPoint) -> Option<&'s VEntity> {
+        if self.entities.is_point_out_of_bounds(point) {
+            None
+        } else {
+            self.entities.get_entity_by_point(point)
+        }
+    }
+
+    pub fn find_collisions(&self, area: &VArea) -> Vec<&'s VEntity> {
+        self.entities.find_collisions(area)
+    }
+
+    pub fn iter_entities(&self) -> impl Iterator<Item = &'s VEntity> {
+        self.entities.iter_entities()
+    }
+
+    /// Count of every placed entity by Factorio name
+    pub fn bill_of_materials(&self) -> BTreeMap<&'s str, usize> {
+        let mut materials = BTreeMap::new();
+        for entity in self.entities.iter_entities() {
+            *materials.entry(entity.name.as_str()).or_default() += 1;
+        }
+        materials
+    }
+}
+
+//
+
+pub trait AsVsMut {
+    fn entities_mut(&mut self) -> PlugMut<'_>;
+}
+
+pub trait AsVs {
+    fn entities(&self) -> Plug<'_>;
+}
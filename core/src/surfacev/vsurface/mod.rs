@@ -1,11 +1,26 @@
 mod convert;
 mod core;
+mod diff;
+mod entity;
 mod nav;
+mod overlay;
+mod paint_sink;
 mod patch;
 mod pixel;
 mod rail;
+mod state_file;
+mod tiles;
+mod validate;
 
 pub use core::VSurface;
+pub use diff::SurfaceDiff;
+pub use entity::{
+    //
+    AsVs as VSurfaceEntityAsVs,
+    AsVsMut as VSurfaceEntityAsVsMut,
+    Plug as VSurfaceEntity,
+    PlugMut as VSurfaceEntityMut,
+};
 pub use nav::{
     //
     AsVs as VSurfaceNavAsVs,
@@ -13,6 +28,8 @@ pub use nav::{
     Plug as VSurfaceNav,
     PlugMut as VSurfaceNavMut,
 };
+pub use overlay::PlanOverlay;
+pub use paint_sink::SurfacePaintSink;
 pub use patch::{
     //
     AsVs as VSurfacePatchAsVs,
@@ -33,4 +50,6 @@ pub use rail::{
     AsVsMut as VSurfaceRailAsVsMut,
     Plug as VSurfaceRail,
     PlugMut as VSurfaceRailMut,
+    RailCheckpoint,
 };
+pub use validate::{SurfaceValidation, SurfaceViolation};
@@ -1,11 +1,14 @@
-use crate::surfacev::mine::MinePath;
+use crate::surfacev::mine::{MineLocation, MinePath};
 use crate::surfacev::ventity_map::{VEntityMap, VPixel};
 use crate::surfacev::vpatch::VPatch;
+use crate::surfacev::vspatial::VSpatialIndex;
 
 pub struct PlugMut<'s> {
     pub(super) rails: &'s mut Vec<MinePath>,
     pub(super) patches: &'s mut Vec<VPatch>,
     pub(super) pixels: &'s mut VEntityMap<VPixel>,
+    pub(super) spatial: &'s mut VSpatialIndex,
+    pub(super) failed_mines: &'s mut Vec<MineLocation>,
 }
 
 #[derive(Clone, Copy)]
@@ -13,6 +16,8 @@ pub struct Plug<'s> {
     pub(super) rails: &'s Vec<MinePath>,
     pub(super) patches: &'s Vec<VPatch>,
     pub(super) pixels: &'s VEntityMap<VPixel>,
+    pub(super) spatial: &'s VSpatialIndex,
+    pub(super) failed_mines: &'s Vec<MineLocation>,
 }
 
 //
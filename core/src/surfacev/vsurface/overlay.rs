@@ -0,0 +1,412 @@
+use crate::surface::pixel::Pixel;
+use crate::surfacev::err::{CoreConvertPathResult, VResult};
+use crate::surfacev::mine::MinePathKind;
+use crate::surfacev::vsurface::{VSurface, VSurfacePatchAsVs, VSurfacePixelAsVs, VSurfaceRailAsVs};
+use facto_loop_miner_fac_engine::common::varea::VArea;
+use facto_loop_miner_fac_engine::common::vpoint::{VPOINT_ONE, VPoint};
+use facto_loop_miner_fac_engine::game_blocks::rail_hope::RailHopeLink;
+use serde_json::{Map, Value, json};
+use std::fmt::Write;
+use std::fs::write;
+use std::path::Path;
+use tracing::info;
+
+const LABEL_STYLE: &str = r#"font-size="24" fill="white" text-anchor="middle""#;
+
+/// Vector plan layer: patches, mine areas, rail centre lines, base sources and failed mines.
+/// Positions are Factorio tiles. GeoJSON flips y so north is up in GIS tools,
+/// SVG keeps Factorio y so it lines up with `pixel-map.png`
+pub struct PlanOverlay {
+    radius: u32,
+    features: Vec<OverlayFeature>,
+}
+
+struct OverlayFeature {
+    layer: OverlayLayer,
+    shape: OverlayShape,
+    properties: Map<String, Value>,
+    label: Option<String>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum OverlayLayer {
+    Patch(Pixel),
+    MineAreaMin,
+    MineAreaBuffered,
+    Rail,
+    /// Short trains into an aggregation outpost
+    RailFeeder,
+    /// Long trains from an aggregation outpost to the base
+    RailAggregator,
+    BaseSource,
+    FailedMine,
+}
+
+enum OverlayShape {
+    Area(VArea),
+    Line(Vec<VPoint>),
+    Point(VPoint),
+}
+
+impl OverlayLayer {
+    fn name(&self) -> &'static str {
+        match self {
+            OverlayLayer::Patch(_) => "patch",
+            OverlayLayer::MineAreaMin => "mine_area_min",
+            OverlayLayer::MineAreaBuffered => "mine_area_buffered",
+            OverlayLayer::Rail => "rail",
+            OverlayLayer::RailFeeder => "rail_feeder",
+            OverlayLayer::RailAggregator => "rail_aggregator",
+            OverlayLayer::BaseSource => "base_source",
+            OverlayLayer::FailedMine => "failed_mine",
+        }
+    }
+
+    fn svg_style(&self) -> String {
+        match self {
+            OverlayLayer::Patch(resource) => {
+                let [r, g, b] = resource.color();
+                format!(r#"fill="rgb({r},{g},{b})" fill-opacity="0.4" stroke="none""#)
+            }
+            OverlayLayer::MineAreaMin => {
+                r##"fill="none" stroke="#53e1ff" stroke-width="2""##.into()
+            }
+            OverlayLayer::MineAreaBuffered => {
+                r##"fill="none" stroke="#53e1ff" stroke-width="2" stroke-dasharray="8 8""##.into()
+            }
+            OverlayLayer::Rail => r##"fill="none" stroke="#b97a57" stroke-width="4""##.into(),
+            OverlayLayer::RailFeeder => r##"fill="none" stroke="#e0b88f" stroke-width="2""##.into(),
+            OverlayLayer::RailAggregator => {
+                r##"fill="none" stroke="#7a3f1d" stroke-width="8""##.into()
+            }
+            OverlayLayer::BaseSource => r##"fill="#dde005""##.into(),
+            OverlayLayer::FailedMine => {
+                r#"fill="red" fill-opacity="0.2" stroke="red" stroke-width="4""#.into()
+            }
+        }
+    }
+}
+
+impl PlanOverlay {
+    pub fn new(surface: &VSurface) -> Self {
+        let mut features = Vec::new();
+        for (patch_index, patch) in surface.patches().get_patches().iter().enumerate() {
+            features.push(OverlayFeature {
+                layer: OverlayLayer::Patch(patch.resource),
+                shape: OverlayShape::Area(patch.area.clone()),
+                properties: properties(json!({
+                    "patch_index": patch_index,
+                    "resource": patch.resource.as_ref(),
+                    "total_amount": patch.total_amount,
+                })),
+                label: None,
+            });
+        }
+
+        for (mine_index, mine_path) in surface.rails().get_mine_paths().iter().enumerate() {
+            let location = &mine_path.location;
+            for (layer, area) in [
+                (OverlayLayer::MineAreaMin, location.area_min()),
+                (OverlayLayer::MineAreaBuffered, location.area_buffered()),
+            ] {
+                features.push(OverlayFeature {
+                    layer,
+                    shape: OverlayShape::Area(area.clone()),
+                    properties: properties(json!({ "mine_index": mine_index })),
+                    // same as debug_draw_mine_index_labels
+                    label: (layer == OverlayLayer::MineAreaMin).then(|| mine_index.to_string()),
+                });
+            }
+
+            let mut centre_line: Vec<VPoint> = Vec::with_capacity(mine_path.links.len() + 1);
+            if let Some(first) = mine_path.links.first() {
+                centre_line.push(first.pos_start());
+            }
+            centre_line.extend(mine_path.links.iter().map(|link| link.pos_next()));
+            let length: f32 = centre_line
+                .windows(2)
+                .map(|pair| pair[0].distance_bird(&pair[1]))
+                .sum();
+            let rail_layer = match mine_path.kind {
+                MinePathKind::MineToBase => OverlayLayer::Rail,
+                MinePathKind::MineToAggregator { .. } => OverlayLayer::RailFeeder,
+                MinePathKind::AggregatorToBase => OverlayLayer::RailAggregator,
+            };
+            features.push(OverlayFeature {
+                layer: rail_layer,
+                shape: OverlayShape::Line(centre_line),
+                properties: properties(json!({
+                    "mine_index": mine_index,
+                    "cost": mine_path.cost,
+                    "length": length.round() as u64,
+                    "links": mine_path.links.len(),
+                    "landfill": mine_path.landfill.len(),
+                    "segment": mine_path.segment.to_string(),
+                })),
+                label: None,
+            });
+
+            let source = mine_path.segment.start;
+            features.push(OverlayFeature {
+                layer: OverlayLayer::BaseSource,
+                shape: OverlayShape::Point(*source.point()),
+                properties: properties(json!({
+                    "mine_index": mine_index,
+                    "direction": source.direction().to_string(),
+                })),
+                label: None,
+            });
+        }
+
+        for (failed_index, location) in surface.rails().get_failed_mines().iter().enumerate() {
+            features.push(OverlayFeature {
+                layer: OverlayLayer::FailedMine,
+                shape: OverlayShape::Area(location.area_buffered().clone()),
+                properties: properties(json!({ "failed_index": failed_index })),
+                label: Some(format!("failed {failed_index}")),
+            });
+        }
+
+        PlanOverlay {
+            radius: surface.pixels().get_radius(),
+            features,
+        }
+    }
+
+    pub fn to_geojson(&self) -> Value {
+        let features: Vec<Value> = self
+            .features
+            .iter()
+            .map(|feature| {
+                let geometry = match &feature.shape {
+                    OverlayShape::Area(area) => json!({
+                        "type": "Polygon",
+                        "coordinates": [area_outline(area).map(geo_position)],
+                    }),
+                    OverlayShape::Line(points) => {
+                        let positions: Vec<[i32; 2]> =
+                            points.iter().copied().map(geo_position).collect();
+                        json!({
+                            "type": "LineString",
+                            "coordinates": positions,
+                        })
+                    }
+                    OverlayShape::Point(point) => json!({
+                        "type": "Point",
+                        "coordinates": geo_position(*point),
+                    }),
+                };
+                let mut properties = feature.properties.clone();
+                properties.insert("layer".into(), feature.layer.name().into());
+                json!({
+                    "type": "Feature",
+                    "geometry": geometry,
+                    "properties": properties,
+                })
+            })
+            .collect();
+        json!({
+            "type": "FeatureCollection",
+            "features": features,
+        })
+    }
+
+    pub fn to_svg(&self) -> String {
+        let radius = self.radius;
+        let diameter = radius * 2;
+        let mut svg = String::new();
+        write!(svg, r#"<svg xmlns="http://www.w3.org/2000/svg""#).unwrap();
+        write!(
+            svg,
+            r#" viewBox="-{radius} -{radius} {diameter} {diameter}""#
+        )
+        .unwrap();
+        writeln!(svg, r#" width="{diameter}" height="{diameter}">"#).unwrap();
+        let mut labels = String::new();
+        for feature in &self.features {
+            let style = feature.layer.svg_style();
+            let layer = feature.layer.name();
+            match &feature.shape {
+                OverlayShape::Area(area) => {
+                    let top_left = area.point_top_left();
+                    let size = area.as_size() + VPOINT_ONE;
+                    writeln!(
+                        svg,
+                        r#"<rect class="{layer}" x="{}" y="{}" width="{}" height="{}" {style}/>"#,
+                        top_left.x(),
+                        top_left.y(),
+                        size.x(),
+                        size.y()
+                    )
+                    .unwrap();
+                }
+                OverlayShape::Line(points) => {
+                    let points: Vec<String> = points
+                        .iter()
+                        .map(|point| format!("{},{}", point.x(), point.y()))
+                        .collect();
+                    writeln!(
+                        svg,
+                        r#"<polyline class="{layer}" points="{}" {style}/>"#,
+                        points.join(" ")
+                    )
+                    .unwrap();
+                }
+                OverlayShape::Point(point) => {
+                    writeln!(
+                        svg,
+                        r#"<circle class="{layer}" cx="{}" cy="{}" r="6" {style}/>"#,
+                        point.x(),
+                        point.y()
+                    )
+                    .unwrap();
+                }
+            }
+            if let (Some(label), OverlayShape::Area(area)) = (&feature.label, &feature.shape) {
+                let center = area.point_center();
+                writeln!(
+                    labels,
+                    r#"<text x="{}" y="{}" {LABEL_STYLE}>{label}</text>"#,
+                    center.x(),
+                    center.y()
+                )
+                .unwrap();
+            }
+        }
+        // labels last so they are drawn on top
+        svg.push_str(&labels);
+        svg.push_str("</svg>\n");
+        svg
+    }
+
+    /// Writes `plan.geojson` and `plan.svg`
+    pub fn save(&self, out_dir: &Path) -> VResult<()> {
+        let geojson_path = out_dir.join("plan.geojson");
+        let geojson = serde_json::to_vec_pretty(&self.to_geojson()).unwrap();
+        write(&geojson_path, geojson).convert(&geojson_path)?;
+
+        let svg_path = out_dir.join("plan.svg");
+        write(&svg_path, self.to_svg()).convert(&svg_path)?;
+
+        info!(
+            "Saved plan overlay of {} features to {}",
+            self.features.len(),
+            out_dir.display()
+        );
+        Ok(())
+    }
+}
+
+fn properties(value: Value) -> Map<String, Value> {
+    match value {
+        Value::Object(map) => map,
+        _ => unreachable!(),
+    }
+}
+
+/// Closed ring around the outside of the tiles
+fn area_outline(area: &VArea) -> [VPoint; 5] {
+    let top_left = area.point_top_left();
+    let bottom_right = area.point_bottom_right() + VPOINT_ONE;
+    [
+        top_left,
+        VPoint::new(bottom_right.x(), top_left.y()),
+        bottom_right,
+        VPoint::new(top_left.x(), bottom_right.y()),
+        top_left,
+    ]
+}
+
+fn geo_position(point: VPoint) -> [i32; 2] {
+    [point.x(), -point.y()]
+}
+
+#[cfg(test)]
+mod test {
+    use crate::surfacev::mine::MinePath;
+    use crate::surfacev::vsurface::overlay::{area_outline, geo_position};
+    use crate::surfacev::vsurface::{
+        PlanOverlay, VSurface, VSurfacePixelAsVs, VSurfaceRailAsVsMut,
+    };
+    use facto_loop_miner_fac_engine::common::varea::VArea;
+    use facto_loop_miner_fac_engine::common::vpoint::VPoint;
+    use facto_loop_miner_fac_engine::common::vpoint_direction::VPointDirectionQ;
+    use facto_loop_miner_fac_engine::game_blocks::rail_hope_single::SECTION_POINTS_I32;
+    use facto_loop_miner_fac_engine::game_entities::direction::FacDirectionQuarter;
+    use serde_json::json;
+
+    #[test]
+    fn test_area_outline() {
+        let area = VArea::from_arbitrary_points_pair(VPoint::new(-2, -2), VPoint::new(1, 3));
+        let outline = area_outline(&area);
+        assert_eq!(outline[0], outline[4]);
+        assert_eq!(outline[2], VPoint::new(2, 4));
+        assert_eq!(geo_position(outline[2]), [2, -4]);
+    }
+
+    #[test]
+    fn test_rail_and_failed_mine() {
+        let mut surface = VSurface::new(200);
+        let start = VPointDirectionQ(
+            VPoint::new(-2 * SECTION_POINTS_I32, 0),
+            FacDirectionQuarter::East,
+        );
+        let path = MinePath::new_test_straight(surface.pixels(), start, 4);
+        let links = path.links.len();
+        let failed = path.location.clone();
+        surface.rails_mut().add_mine_path(path);
+        surface.rails_mut().record_failed_mine(failed);
+        let overlay = PlanOverlay::new(&surface);
+
+        let geojson = overlay.to_geojson();
+        let features = geojson["features"].as_array().unwrap();
+        let layers: Vec<&str> = features
+            .iter()
+            .map(|feature| feature["properties"]["layer"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            layers,
+            [
+                "mine_area_min",
+                "mine_area_buffered",
+                "rail",
+                "base_source",
+                "failed_mine"
+            ]
+        );
+        let rail = &features[2];
+        assert_eq!(rail["geometry"]["type"], "LineString");
+        assert_eq!(rail["properties"]["mine_index"], 0);
+        assert_eq!(rail["properties"]["cost"], 4);
+        assert_eq!(rail["properties"]["links"], links);
+        let source = &features[3];
+        assert_eq!(
+            source["geometry"]["coordinates"],
+            json!(geo_position(start.0))
+        );
+        assert_eq!(source["properties"]["direction"], start.1.to_string());
+        assert_eq!(features[4]["geometry"]["type"], "Polygon");
+        assert_eq!(features[4]["properties"]["failed_index"], 0);
+
+        let svg = overlay.to_svg();
+        assert!(
+            svg.starts_with(
+                r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="-200 -200 400 400""#
+            )
+        );
+        for (element, count) in [
+            (r#"<rect class="mine_area_min""#, 1),
+            (r#"<rect class="mine_area_buffered""#, 1),
+            (r#"<polyline class="rail""#, 1),
+            (r#"<circle class="base_source""#, 1),
+            (r#"<rect class="failed_mine""#, 1),
+            (">0</text>", 1),
+            (">failed 0</text>", 1),
+        ] {
+            assert_eq!(svg.matches(element).count(), count, "{element}");
+        }
+        // labels after every shape
+        assert!(svg.rfind("<rect").unwrap() < svg.find("<text").unwrap());
+        assert!(svg.ends_with("</svg>\n"));
+    }
+}
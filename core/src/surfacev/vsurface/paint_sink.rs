@@ -0,0 +1,161 @@
+use crate::state::tuneables::PaintSink;
+use crate::surfacev::err::{CoreConvertPathResult, VError, VResult};
+use facto_loop_miner_common::err_utils::xbt;
+use image::ImageResult;
+use std::cell::RefCell;
+use std::fs::{File, create_dir_all, read_dir};
+use std::io::{BufWriter, Write};
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+#[derive(Default)]
+enum ActiveSink {
+    #[default]
+    Disabled,
+    Frames {
+        dir: PathBuf,
+        next_frame: usize,
+    },
+    Tcp(SocketAddr),
+}
+
+/// Painting is on the planning thread, an unreachable viewer must not stall it
+const TCP_CONNECT_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Where `SurfacePainting::save_to_sink` goes, opened once per run from `save.paint_sink`
+#[derive(Default)]
+pub struct SurfacePaintSink {
+    active: RefCell<ActiveSink>,
+}
+
+impl SurfacePaintSink {
+    pub fn new(work_dir: &Path, sink: &PaintSink) -> VResult<Self> {
+        let active = match sink {
+            PaintSink::Disabled => ActiveSink::Disabled,
+            PaintSink::Frames(dir) => {
+                let dir = work_dir.join(dir);
+                create_dir_all(&dir).convert(&dir)?;
+                // keep numbering after earlier runs so one video can cover several
+                let next_frame = read_dir(&dir)
+                    .convert(&dir)?
+                    .filter_map(|entry| frame_number(&entry.ok()?.file_name().to_string_lossy()))
+                    .max()
+                    .map_or(0, |last| last + 1);
+                info!("Painting frames to {} from {next_frame}", dir.display());
+                ActiveSink::Frames { dir, next_frame }
+            }
+            PaintSink::Tcp(address) => {
+                let resolved = address.to_socket_addrs().ok().and_then(|mut v| v.next());
+                let Some(resolved) = resolved else {
+                    return Err(VError::InvalidTunables {
+                        reason: format!("save.paint_sink address {address} does not resolve"),
+                        backtrace: xbt(),
+                    });
+                };
+                info!("Painting frames to tcp {resolved}");
+                ActiveSink::Tcp(resolved)
+            }
+        };
+        Ok(Self {
+            active: RefCell::new(active),
+        })
+    }
+
+    pub(super) fn send(
+        &self,
+        debug_description: &str,
+        write_png: impl FnOnce(&mut dyn Write) -> ImageResult<()>,
+    ) {
+        let mut sink = self.active.borrow_mut();
+        match &mut *sink {
+            ActiveSink::Disabled => {}
+            ActiveSink::Frames { dir, next_frame } => {
+                let path = dir.join(frame_file_name(*next_frame));
+                *next_frame += 1;
+                debug!("Painting {debug_description} to frame {}", path.display());
+                let result = File::create(&path)
+                    .map_err(|e| e.to_string())
+                    .and_then(|file| {
+                        write_png(&mut BufWriter::new(file)).map_err(|e| e.to_string())
+                    });
+                if let Err(e) = result {
+                    warn!(
+                        "Painting {debug_description} to {} failed {e}",
+                        path.display()
+                    );
+                }
+            }
+            ActiveSink::Tcp(address) => {
+                debug!("Painting {debug_description} to tcp {address}");
+                let result = TcpStream::connect_timeout(address, TCP_CONNECT_TIMEOUT)
+                    .map_err(|e| e.to_string())
+                    .and_then(|mut stream| write_png(&mut stream).map_err(|e| e.to_string()));
+                if let Err(e) = result {
+                    warn!(
+                        "Painting {debug_description} to tcp {address} failed {e}, disabling sink"
+                    );
+                    *sink = ActiveSink::Disabled;
+                }
+            }
+        }
+    }
+}
+
+fn frame_file_name(frame: usize) -> String {
+    format!("frame-{frame:06}.png")
+}
+
+fn frame_number(file_name: &str) -> Option<usize> {
+    file_name
+        .strip_prefix("frame-")?
+        .strip_suffix(".png")?
+        .parse()
+        .ok()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::state::tuneables::PaintSink;
+    use crate::surfacev::vsurface::paint_sink::{SurfacePaintSink, frame_file_name, frame_number};
+    use std::fs::{read, remove_dir_all};
+    use std::io::Write;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_frame_names() {
+        assert_eq!(frame_file_name(42), "frame-000042.png");
+        assert_eq!(frame_number(&frame_file_name(1234567)), Some(1234567));
+        assert_eq!(frame_number("pixel-map.png"), None);
+    }
+
+    #[test]
+    fn test_frames_sink() {
+        let work_dir = std::env::temp_dir().join(format!("paint-sink-{}", std::process::id()));
+        let dir = work_dir.join("frames");
+        let frames = PaintSink::Frames(PathBuf::from("frames"));
+        let send_bytes = |sink: &SurfacePaintSink, bytes: &'static [u8]| {
+            sink.send("test", |writer| {
+                writer.write_all(bytes).unwrap();
+                Ok(())
+            })
+        };
+
+        let sink = SurfacePaintSink::new(&work_dir, &frames).unwrap();
+        send_bytes(&sink, b"first");
+        send_bytes(&sink, b"second");
+        let restarted = SurfacePaintSink::new(&work_dir, &frames).unwrap();
+        send_bytes(&restarted, b"after restart");
+        send_bytes(&SurfacePaintSink::default(), b"disabled");
+
+        for (frame, bytes) in [&b"first"[..], b"second", b"after restart"]
+            .into_iter()
+            .enumerate()
+        {
+            assert_eq!(read(dir.join(frame_file_name(frame))).unwrap(), bytes);
+        }
+        assert!(!dir.join(frame_file_name(3)).exists());
+        remove_dir_all(&work_dir).unwrap();
+    }
+}
@@ -1,12 +1,14 @@
 use crate::surfacev::mine::MineLocation;
 use crate::surfacev::ventity_map::{VEntityMap, VPixel};
 use crate::surfacev::vpatch::VPatch;
+use crate::surfacev::vspatial::{SpatialKey, VSpatialIndex};
 use facto_loop_miner_fac_engine::common::vpoint::VPoint;
-use tracing::{debug, info};
+use tracing::info;
 
 pub struct PlugMut<'s> {
     pub(super) pixels: &'s mut VEntityMap<VPixel>,
     pub(super) patches: &'s mut Vec<VPatch>,
+    pub(super) spatial: &'s mut VSpatialIndex,
 }
 
 impl<'s> PlugMut<'s> {
@@ -34,6 +36,7 @@ impl<'s> PlugMut<'s> {
         for patch_index in patches_to_remove {
             self.patches.remove(patch_index);
         }
+        self.spatial.replace_patches(self.patches);
     }
 
     pub fn remove_patches_in_column(&mut self, radius: u32) {
@@ -58,10 +61,12 @@ impl<'s> PlugMut<'s> {
         for patch_index in patches_to_remove {
             self.patches.remove(patch_index);
         }
+        self.spatial.replace_patches(self.patches);
     }
 
     pub fn add_patches(&mut self, patches: impl IntoIterator<Item = VPatch>) {
-        self.patches.extend(patches)
+        self.patches.extend(patches);
+        self.spatial.replace_patches(self.patches);
     }
 }
 
@@ -69,6 +74,7 @@ impl<'s> PlugMut<'s> {
 pub struct Plug<'s> {
     pub(super) pixels: &'s VEntityMap<VPixel>,
     pub(super) patches: &'s Vec<VPatch>,
+    pub(super) spatial: &'s VSpatialIndex,
 }
 
 impl<'s> Plug<'s> {
@@ -82,35 +88,22 @@ impl<'s> Plug<'s> {
             .map(|patch_index| &self.patches[*patch_index])
     }
 
-    pub fn mine_patches_len(mine: &MineLocation) -> usize {
-        mine.patch_indexes().len()
+    /// Patches with any part of their area within distance of the point, sorted by index
+    pub fn patch_indexes_within(&self, point: VPoint, distance: f32) -> Vec<usize> {
+        let mut patch_indexes: Vec<usize> = self
+            .spatial
+            .within_distance(point, distance)
+            .filter_map(|key| match key {
+                SpatialKey::Patch(patch_index) => Some(patch_index),
+                _ => None,
+            })
+            .collect();
+        patch_indexes.sort_unstable();
+        patch_indexes
     }
 
-    /// Anti-entropy
-    pub fn validate(&self) {
-        self.pixels.validate();
-        self.validate_patches();
-    }
-
-    fn validate_patches(&self) {
-        if self.patches.is_empty() {
-            panic!("no patches to validate")
-        }
-        let mut checks = 0;
-        let mut points_history: Vec<&VPoint> = Vec::new();
-        for patch in self.patches.as_slice() {
-            for point in &patch.pixel_indexes {
-                if points_history.contains(&point) {
-                    panic!("dupe {patch:?}");
-                }
-                points_history.push(point);
-
-                let pixel = self.pixels.get_entity_by_point(point).unwrap();
-                assert_eq!(pixel.pixel, patch.resource);
-                checks += 1;
-            }
-        }
-        debug!("validate {checks} checks");
+    pub fn mine_patches_len(mine: &MineLocation) -> usize {
+        mine.patch_indexes().len()
     }
 
     pub fn get_patch_index(&self, patch: &VPatch) -> usize {
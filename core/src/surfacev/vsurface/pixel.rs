@@ -1,23 +1,25 @@
+#[cfg(feature = "opencv_re")]
 use crate::opencv::{GeneratedMat, draw_text_cv, draw_text_size, mat_into_points};
 use crate::surface::pixel::Pixel;
 use crate::surfacev::err::{CoreConvertPathResult, VResult};
 use crate::surfacev::fast_metrics::{FastMetric, FastMetrics};
-use crate::surfacev::ventity_map::{VEntityMap, VMapChange, VPixel};
+use crate::surfacev::ventity_map::{VCheckpoint, VEntityMap, VMapChange, VPixel};
 use crate::surfacev::vsurface::core::path_pixel_xy_indexes_clone;
+use crate::surfacev::vsurface::{SurfacePaintSink, tiles};
 use colorgrad::Gradient;
 use facto_loop_miner_common::LOCALE;
 use facto_loop_miner_common::duration::BasicWatch;
 use facto_loop_miner_fac_engine::common::varea::VArea;
 use facto_loop_miner_fac_engine::common::vpoint::{VPOINT_ONE, VPoint};
+#[cfg(feature = "opencv_re")]
 use facto_loop_miner_fac_engine::opencv_re::core::{CV_8U, Mat, MatTrait, Point, Scalar};
 use image::codecs::png::{CompressionType, FilterType, PngEncoder};
-use image::{ExtendedColorType, ImageEncoder};
+use image::{ExtendedColorType, ImageEncoder, ImageResult};
 use num_format::ToFormattedString;
 use std::borrow::Borrow;
 use std::collections::HashMap;
 use std::fs::File;
 use std::io::{BufWriter, Write};
-use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
 use std::path::Path;
 use tracing::{debug, info, trace};
 
@@ -30,11 +32,23 @@ impl<'s> PlugMut<'s> {
         self.pixels.load_clone_prep(&path_pixel_xy_indexes_clone())
     }
 
+    /// Journal every following change until `commit`, see `VEntityMap::begin`
+    pub fn begin(&mut self) -> VCheckpoint {
+        self.pixels.begin()
+    }
+
+    pub fn rollback_to(&mut self, checkpoint: VCheckpoint) {
+        self.pixels.rollback_to(checkpoint)
+    }
+
+    pub fn commit(&mut self) {
+        self.pixels.commit()
+    }
+
     pub fn crop(&mut self, new_radius: u32) {
         let old_radius = self.pixels.radius();
         info!("Crop from {} to {}", old_radius, new_radius);
         assert!(old_radius > new_radius);
-        // self.entities.crop(new_radius);
         self.pixels.crop(new_radius);
     }
 
@@ -77,6 +91,7 @@ impl<'s> PlugMut<'s> {
         self.pixels.change(positions)
     }
 
+    #[cfg(feature = "opencv_re")]
     pub fn draw_text_at(&mut self, pos: VPoint, text: &str) {
         let watch = BasicWatch::start();
 
@@ -118,6 +133,12 @@ impl<'s> PlugMut<'s> {
         self.change_pixels(new_points).stomp(Pixel::Highlighter);
         // trace!("set {new_points_len} points in {watch}");
     }
+
+    /// Text rendering is OpenCV only
+    #[cfg(not(feature = "opencv_re"))]
+    pub fn draw_text_at(&mut self, pos: VPoint, text: &str) {
+        trace!("Text \"{text}\" at {pos} skipped, built without opencv_re");
+    }
 }
 
 #[derive(Clone, Copy)]
@@ -258,12 +279,66 @@ impl<'s> Plug<'s> {
         }
     }
 
+    /// Browsable `{z}/{x}/{y}.png` tile pyramid with an `index.html` viewer
+    pub fn export_tiles(&self, out_dir: &Path) -> VResult<()> {
+        tiles::export_tiles(
+            self.pixels.iter_xy_pixels(),
+            self.pixels.diameter(),
+            out_dir,
+        )
+    }
+
+    /// Rails only in self are green, rails only in before are red, everything else is dimmed
+    #[must_use]
+    pub fn paint_pixel_diff(&self, before: Plug) -> SurfacePainting {
+        const ADDED: [u8; 3] = [0x00, 0xFF, 0x00];
+        const REMOVED: [u8; 3] = [0xFF, 0x00, 0x00];
+        let build_watch = BasicWatch::start();
+
+        let mut output: Vec<u8> = Vec::with_capacity(self.pixels.xy_array_length_from_radius() * 3);
+        for (point, after_pixel) in self.get_pixels_all() {
+            let before_pixel = if before.is_point_out_of_bounds(&point) {
+                Pixel::Empty
+            } else {
+                before.get_pixel(point)
+            };
+            let color = match (before_pixel == Pixel::Rail, after_pixel == Pixel::Rail) {
+                (false, true) => ADDED,
+                (true, false) => REMOVED,
+                _ => after_pixel.color().map(|v| v / 3),
+            };
+            output.extend(color);
+        }
+
+        let debug_description = format!(
+            "diff ({} in {})",
+            output.len().to_formatted_string(&LOCALE),
+            build_watch
+        );
+        SurfacePainting {
+            output,
+            diameter: self.get_radius() * 2,
+            color_type: ExtendedColorType::Rgb8,
+            file_name: "pixel-diff.png",
+            debug_description,
+        }
+    }
+
     //</editor-fold>
 
+    #[cfg(feature = "opencv_re")]
     pub fn get_pixel_cv_image(&self, filter: Option<Pixel>) -> GeneratedMat {
         self.pixels.map_pixel_xy_to_cv(filter)
     }
 
+    /// Row major, same layout as `get_pixel_cv_image`
+    pub fn get_pixel_mask(&self, filter: Pixel) -> Vec<bool> {
+        self.pixels
+            .iter_xy_pixels()
+            .map(|pixel| *pixel == filter)
+            .collect()
+    }
+
     pub fn get_radius(&self) -> u32 {
         self.pixels.radius()
     }
@@ -345,50 +420,38 @@ pub struct SurfacePainting {
 }
 
 impl SurfacePainting {
-    fn encoder<W: Write>(writer: W) -> PngEncoder<W> {
+    pub(super) fn encoder<W: Write>(writer: W) -> PngEncoder<W> {
         // For input 2000x2000 image:
         // Custom takes 0.121 seconds to save
         // Default takes 2.4 seconds to save
         PngEncoder::new_with_quality(writer, CompressionType::Fast, FilterType::NoFilter)
     }
 
-    pub fn save_to_oculante(self) {
-        let Self {
-            output,
-            diameter,
-            color_type,
-            file_name: _,
-            debug_description,
-        } = self;
-        const ADDR: (&str, u16) = ("peko.g.xana.sh", 5689);
-        // const ADDR: (&str, u16) = ("127.0.0.1", 5689);
-        let address: SocketAddr = ADDR.to_socket_addrs().unwrap().next().unwrap();
-        debug!("Painting {debug_description} to oculante {address}");
-        let stream = TcpStream::connect(address).unwrap();
+    fn write_png<W: Write>(&self, writer: W) -> ImageResult<()> {
+        Self::encoder(writer).write_image(
+            &self.output,
+            self.diameter,
+            self.diameter,
+            self.color_type,
+        )
+    }
 
-        Self::encoder(stream)
-            .write_image(&output, diameter, diameter, color_type)
-            .unwrap();
+    /// Progress frame for the viewer, see `save.paint_sink`. Failures are logged, never fatal
+    pub fn save_to_sink(self, sink: &SurfacePaintSink) {
+        sink.send(&self.debug_description, |writer| self.write_png(writer));
     }
 
     pub fn save_to_file(self, dir: &Path) -> VResult<()> {
-        let Self {
-            output,
-            diameter,
-            color_type,
-            file_name,
-            debug_description,
-        } = self;
-
         let watch = BasicWatch::start();
-        let path = dir.join(file_name);
-        debug!("Painting {debug_description} to file {}", path.display());
+        let path = dir.join(self.file_name);
+        debug!(
+            "Painting {} to file {}",
+            self.debug_description,
+            path.display()
+        );
 
         let file = File::create(&path).convert(&path)?;
-        let writer = BufWriter::new(&file);
-        Self::encoder(writer)
-            .write_image(&output, diameter, diameter, color_type)
-            .convert(&path)?;
+        self.write_png(BufWriter::new(&file)).convert(&path)?;
 
         let size = file.metadata().convert(&path)?.len();
         debug!(
@@ -405,7 +468,7 @@ impl SurfacePainting {
 mod test {
     use crate::surface::pixel::Pixel;
     use crate::surfacev::vsurface::core::VSurface;
-    use crate::surfacev::vsurface::{VSurfacePixelAsVs, VSurfacePixelAsVsMut};
+    use crate::surfacev::vsurface::{SurfacePaintSink, VSurfacePixelAsVs, VSurfacePixelAsVsMut};
     use facto_loop_miner_common::log_init_trace;
     use facto_loop_miner_fac_engine::blueprint::output::FacItemOutput;
     use facto_loop_miner_fac_engine::common::varea::VArea;
@@ -457,7 +520,7 @@ mod test {
         surface
             .pixels()
             .paint_pixel_colored_entire()
-            .save_to_oculante();
+            .save_to_sink(&SurfacePaintSink::default());
     }
 
     #[test]
@@ -1,14 +1,17 @@
 use crate::surface::pixel::Pixel;
-use crate::surfacev::mine::MinePath;
-use crate::surfacev::ventity_map::{VEntityMap, VPixel};
+use crate::surfacev::mine::{MineLocation, MinePath};
+use crate::surfacev::ventity_map::{VCheckpoint, VEntityMap, VPixel};
+use crate::surfacev::vspatial::{SpatialKey, VSpatialIndex};
 use crate::surfacev::vsurface::{VSurfacePixel, VSurfacePixelAsVs, VSurfacePixelAsVsMut};
 use facto_loop_miner_fac_engine::common::vpoint::VPoint;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use tracing::{error, trace};
 
 pub struct PlugMut<'s> {
     pub(super) rails: &'s mut Vec<MinePath>,
     pub(super) pixels: &'s mut VEntityMap<VPixel>,
+    pub(super) spatial: &'s mut VSpatialIndex,
+    pub(super) failed_mines: &'s mut Vec<MineLocation>,
 }
 
 impl<'s> PlugMut<'s> {
@@ -16,12 +19,13 @@ impl<'s> PlugMut<'s> {
         self.add_mine_path_with_pixel(mine_path, Pixel::Rail)
     }
 
-    pub fn add_mine_path_with_pixel(&mut self, mine_path: MinePath, pixel: Pixel) {
+    pub fn add_mine_path_with_pixel(&mut self, mut mine_path: MinePath, pixel: Pixel) {
         trace!(
             "{} {}",
             nu_ansi_term::Color::Red.paint("mine add"),
             mine_path.segment
         );
+        mine_path.replaced_obstacles = mine_path.find_obstacles(self.pixels());
         let new_points = mine_path.total_area();
         self.pixels_mut().change_pixels(new_points).stomp(pixel);
 
@@ -30,9 +34,49 @@ impl<'s> PlugMut<'s> {
         // let start_points: Vec<VPoint> = mine_path.links.iter().map(|v| v.start).collect_vec();
         // self.set_pixels(Pixel::EdgeWall, start_points)?;
 
+        self.spatial.insert_path(&mine_path, self.rails.len());
         self.rails.push(mine_path);
     }
 
+    /// Not rolled back, a failure stays recorded after its pixels are undone
+    pub fn record_failed_mine(&mut self, location: MineLocation) {
+        self.failed_mines.push(location);
+    }
+
+    /// Journal pixel changes. Rails must only be added until the rollback
+    pub fn begin(&mut self) -> RailCheckpoint {
+        RailCheckpoint {
+            pixels: self.pixels.begin(),
+            rails_len: self.rails.len(),
+        }
+    }
+
+    /// Exact restore of pixels and rails, returning the rails added since the checkpoint
+    pub fn rollback_to(&mut self, checkpoint: RailCheckpoint) -> Vec<MinePath> {
+        assert!(
+            self.rails.len() >= checkpoint.rails_len,
+            "rails removed since checkpoint"
+        );
+        self.pixels.rollback_to(checkpoint.pixels);
+        let removed = self.rails.split_off(checkpoint.rails_len);
+        for (offset, mine_path) in removed.iter().enumerate().rev() {
+            self.spatial
+                .remove_path(mine_path, checkpoint.rails_len + offset);
+        }
+        trace!(
+            "{} to {} removed {}",
+            nu_ansi_term::Color::Red.paint("mine rollback"),
+            checkpoint.rails_len,
+            removed.len(),
+        );
+        removed
+    }
+
+    /// Rails before the checkpoint can't be rolled back anymore, drop their pixel history
+    pub fn commit_before(&mut self, checkpoint: RailCheckpoint) {
+        self.pixels.commit_before(checkpoint.pixels);
+    }
+
     pub fn remove_mine_path_at(&mut self, index: usize) -> Option<(MinePath, Vec<VPoint>)> {
         let mine_path = self.rails.remove(index);
         trace!(
@@ -42,7 +86,7 @@ impl<'s> PlugMut<'s> {
             mine_path.segment,
         );
 
-        let removed_points = self.remove_mine_path_cleanup(&mine_path);
+        let removed_points = self.remove_mine_path_cleanup(&mine_path, index);
         Some((mine_path, removed_points))
     }
 
@@ -53,11 +97,12 @@ impl<'s> PlugMut<'s> {
             self.rails.len()
         );
         let mine_path = self.rails.pop()?;
-        let removed_points = self.remove_mine_path_cleanup(&mine_path);
+        let removed_points = self.remove_mine_path_cleanup(&mine_path, self.rails.len());
         Some((mine_path, removed_points))
     }
 
-    fn remove_mine_path_cleanup(&mut self, mine_path: &MinePath) -> Vec<VPoint> {
+    fn remove_mine_path_cleanup(&mut self, mine_path: &MinePath, index: usize) -> Vec<VPoint> {
+        self.spatial.remove_path(mine_path, index);
         let removed_points = mine_path.total_area();
         let surface = self.pixels();
         let mut bad_existing = Vec::new();
@@ -83,6 +128,18 @@ impl<'s> PlugMut<'s> {
             // panic!("existing is not Rail")
         }
         self.pixels.change(removed_points.clone()).remove();
+        if !mine_path.landfill.is_empty() {
+            self.pixels
+                .change(mine_path.landfill.clone())
+                .stomp(Pixel::Water);
+        }
+        let mut obstacles: BTreeMap<Pixel, Vec<VPoint>> = BTreeMap::new();
+        for (point, pixel) in &mine_path.replaced_obstacles {
+            obstacles.entry(*pixel).or_default().push(*point);
+        }
+        for (pixel, points) in obstacles {
+            self.pixels.change(points).stomp(pixel);
+        }
         removed_points
     }
 }
@@ -90,6 +147,8 @@ impl<'s> PlugMut<'s> {
 pub struct Plug<'s> {
     pub(super) rails: &'s [MinePath],
     pub(super) pixels: &'s VEntityMap<VPixel>,
+    pub(super) spatial: &'s VSpatialIndex,
+    pub(super) failed_mines: &'s [MineLocation],
 }
 
 impl<'s> Plug<'s> {
@@ -97,17 +156,59 @@ impl<'s> Plug<'s> {
         self.rails
     }
 
+    pub fn get_failed_mines(&self) -> &'s [MineLocation] {
+        self.failed_mines
+    }
+
+    /// Nearest rail by link footprint, 0 distance if the point is on it
+    pub fn nearest_mine_path_index(&self, point: VPoint) -> Option<usize> {
+        self.nearest_mine_path_index_where(point, |_| true)
+    }
+
+    /// [Self::nearest_mine_path_index] of only the rails matching `filter`
+    pub fn nearest_mine_path_index_where(
+        &self,
+        point: VPoint,
+        filter: impl Fn(&MinePath) -> bool,
+    ) -> Option<usize> {
+        let path_index = |key: &SpatialKey| {
+            let SpatialKey::Rail(start) = key else {
+                return None;
+            };
+            let index = self
+                .spatial
+                .rail_index(start)
+                .expect("spatial index out of sync with rails");
+            Some(index)
+        };
+        let (key, _) = self.spatial.nearest(point, |key| {
+            path_index(key).is_some_and(|index| filter(&self.rails[index]))
+        })?;
+        path_index(&key)
+    }
+
+    /// Copy starts without rails or patches in its spatial index
     pub fn surface_copy(surface: VSurfacePixel) -> PlugCopy {
         PlugCopy {
-            pixels: surface.pixels.clone(),
+            pixels: surface.pixels.clone_without_journal(),
             rails: Vec::new(),
+            spatial: VSpatialIndex::default(),
+            failed_mines: Vec::new(),
         }
     }
 }
 
+#[derive(Clone, Copy, Debug)]
+pub struct RailCheckpoint {
+    pixels: VCheckpoint,
+    rails_len: usize,
+}
+
 pub struct PlugCopy {
     pub(super) pixels: VEntityMap<VPixel>,
     pub(super) rails: Vec<MinePath>,
+    pub(super) spatial: VSpatialIndex,
+    pub(super) failed_mines: Vec<MineLocation>,
 }
 
 impl PlugCopy {
@@ -127,3 +228,58 @@ pub trait AsVs {
 }
 
 //
+
+#[cfg(test)]
+mod test {
+    use crate::surface::pixel::Pixel;
+    use crate::surfacev::mine::MinePath;
+    use crate::surfacev::vsurface::{
+        VSurface, VSurfacePixelAsVs, VSurfacePixelAsVsMut, VSurfaceRailAsVsMut,
+    };
+    use facto_loop_miner_fac_engine::common::vpoint::VPoint;
+    use facto_loop_miner_fac_engine::common::vpoint_direction::VPointDirectionQ;
+    use facto_loop_miner_fac_engine::game_blocks::rail_hope_single::SECTION_POINTS_I32;
+    use facto_loop_miner_fac_engine::game_entities::direction::FacDirectionQuarter;
+
+    #[test]
+    fn test_remove_restores_water_and_obstacles() {
+        let mut surface = VSurface::new(400);
+        let start = VPoint::new(-4 * SECTION_POINTS_I32, 0);
+        let mut path = MinePath::new_test_straight(
+            surface.pixels(),
+            VPointDirectionQ(start, FacDirectionQuarter::East),
+            4,
+        );
+        let area = path.total_area();
+        let water: Vec<VPoint> = area
+            .iter()
+            .filter(|v| (-60..-40).contains(&v.x()))
+            .copied()
+            .collect();
+        let tree = *area.iter().find(|v| v.x() == -80).unwrap();
+        let empty = *area.iter().find(|v| v.x() == -20).unwrap();
+        surface
+            .pixels_mut()
+            .change_pixels(water.clone())
+            .stomp(Pixel::Water);
+        surface
+            .pixels_mut()
+            .change_pixels(vec![tree])
+            .stomp(Pixel::Tree);
+
+        path.landfill = path.find_landfill(surface.pixels());
+        assert_eq!(path.landfill, water);
+        surface.rails_mut().add_mine_path(path);
+        for point in [water[0], tree, empty] {
+            assert_eq!(surface.pixels().get_pixel(point), Pixel::Rail);
+        }
+
+        let (removed, _) = surface.rails_mut().remove_mine_path_pop().unwrap();
+        assert_eq!(removed.replaced_obstacles, [(tree, Pixel::Tree)]);
+        for point in &water {
+            assert_eq!(surface.pixels().get_pixel(point), Pixel::Water);
+        }
+        assert_eq!(surface.pixels().get_pixel(tree), Pixel::Tree);
+        assert_eq!(surface.pixels().get_pixel(empty), Pixel::Empty);
+    }
+}
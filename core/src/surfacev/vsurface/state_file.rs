@@ -0,0 +1,220 @@
+use crate::state::tuneables::Tunables;
+use crate::surfacev::err::{VError, VResult};
+use crate::surfacev::mine::{MineLocation, MinePath};
+use crate::surfacev::vamount_map::VAmountMap;
+use crate::surfacev::ventity_map::{VEntity, VEntityMap, VPixel};
+use crate::surfacev::vpatch::VPatch;
+use facto_loop_miner_common::err_utils::xbt;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::Path;
+
+const STATE_MAGIC: [u8; 8] = *b"LMVSURF\0";
+/// Version 1 was the plain `vsurface-state.json`. Version 2 had no entity layer.
+/// Version 3 had no failed mines
+pub const STATE_VERSION: u32 = 4;
+
+const SECTION_PIXELS: [u8; 4] = *b"PIXL";
+const SECTION_ENTITIES: [u8; 4] = *b"ENTS";
+const SECTION_PATCHES: [u8; 4] = *b"PTCH";
+const SECTION_RAILS: [u8; 4] = *b"RAIL";
+const SECTION_FAILED_MINES: [u8; 4] = *b"FAIL";
+const SECTION_AMOUNTS: [u8; 4] = *b"AMNT";
+/// Record of the run. Not loaded, each run uses the tunables it was started with
+const SECTION_TUNABLES: [u8; 4] = *b"TUNE";
+
+/// Everything in the state file except the xy buffers, which have their own files
+pub struct SurfaceState {
+    /// Of the file, older versions were migrated while reading
+    pub version: u32,
+    pub pixels: VEntityMap<VPixel>,
+    pub entities: VEntityMap<VEntity>,
+    pub patches: Vec<VPatch>,
+    pub rails: Vec<MinePath>,
+    pub failed_mines: Vec<MineLocation>,
+    pub amounts: VAmountMap,
+}
+
+pub struct SurfaceStateRef<'s> {
+    pub pixels: &'s VEntityMap<VPixel>,
+    pub entities: &'s VEntityMap<VEntity>,
+    pub patches: &'s [VPatch],
+    pub rails: &'s [MinePath],
+    pub failed_mines: &'s [MineLocation],
+    pub amounts: &'s VAmountMap,
+    pub tunables: &'s Tunables,
+}
+
+/// Header of magic, version, section count. Then per section a tag, byte length, and CBOR body
+pub fn write_state(writer: &mut impl Write, state: &SurfaceStateRef, path: &Path) -> VResult<()> {
+    let sections = [
+        (SECTION_PIXELS, encode_section(state.pixels, path)?),
+        (SECTION_ENTITIES, encode_section(state.entities, path)?),
+        (SECTION_PATCHES, encode_section(state.patches, path)?),
+        (SECTION_RAILS, encode_section(state.rails, path)?),
+        (
+            SECTION_FAILED_MINES,
+            encode_section(state.failed_mines, path)?,
+        ),
+        (SECTION_AMOUNTS, encode_section(state.amounts, path)?),
+        (SECTION_TUNABLES, encode_section(state.tunables, path)?),
+    ];
+
+    let mut write_all = |data: &[u8]| writer.write_all(data).map_err(|e| invalid_state(path, e));
+    write_all(&STATE_MAGIC)?;
+    write_all(&STATE_VERSION.to_le_bytes())?;
+    write_all(&(sections.len() as u32).to_le_bytes())?;
+    for (tag, body) in &sections {
+        write_all(tag)?;
+        write_all(&(body.len() as u64).to_le_bytes())?;
+        write_all(body)?;
+    }
+    Ok(())
+}
+
+pub fn read_state(data: &[u8], path: &Path) -> VResult<SurfaceState> {
+    let mut reader = SliceReader { data, path };
+    if reader.take(STATE_MAGIC.len())? != STATE_MAGIC {
+        return Err(invalid_state(path, "not a vsurface state file"));
+    }
+    let version = u32::from_le_bytes(reader.take_array()?);
+    let section_count = u32::from_le_bytes(reader.take_array()?);
+
+    let mut sections: HashMap<[u8; 4], &[u8]> = HashMap::new();
+    for _ in 0..section_count {
+        let tag: [u8; 4] = reader.take_array()?;
+        let len = u64::from_le_bytes(reader.take_array()?);
+        sections.insert(tag, reader.take(len as usize)?);
+    }
+
+    if version > STATE_VERSION {
+        return Err(invalid_state(
+            path,
+            format!("version {version} is newer than supported {STATE_VERSION}"),
+        ));
+    }
+
+    if version < 2 {
+        return Err(invalid_state(path, format!("unknown version {version}")));
+    }
+
+    let pixels: VEntityMap<VPixel> = decode_section(&sections, SECTION_PIXELS, path)?;
+    // Older binary versions are migrated here
+    let entities = match version {
+        2 => VEntityMap::new_chunked(pixels.radius()),
+        _ => decode_section(&sections, SECTION_ENTITIES, path)?,
+    };
+    let failed_mines = match version {
+        2 | 3 => Vec::new(),
+        _ => decode_section(&sections, SECTION_FAILED_MINES, path)?,
+    };
+
+    Ok(SurfaceState {
+        version,
+        pixels,
+        entities,
+        patches: decode_section(&sections, SECTION_PATCHES, path)?,
+        rails: decode_section(&sections, SECTION_RAILS, path)?,
+        failed_mines,
+        amounts: decode_section(&sections, SECTION_AMOUNTS, path)?,
+    })
+}
+
+fn encode_section<T: Serialize + ?Sized>(value: &T, path: &Path) -> VResult<Vec<u8>> {
+    let mut body = Vec::new();
+    ciborium::into_writer(value, &mut body).map_err(|e| invalid_state(path, e))?;
+    Ok(body)
+}
+
+fn decode_section<T: DeserializeOwned>(
+    sections: &HashMap<[u8; 4], &[u8]>,
+    tag: [u8; 4],
+    path: &Path,
+) -> VResult<T> {
+    let tag_name = String::from_utf8_lossy(&tag);
+    let body = sections
+        .get(&tag)
+        .ok_or_else(|| invalid_state(path, format!("missing section {tag_name}")))?;
+    ciborium::from_reader(*body).map_err(|e| invalid_state(path, format!("section {tag_name} {e}")))
+}
+
+pub(super) fn invalid_state(path: &Path, reason: impl ToString) -> VError {
+    VError::InvalidState {
+        path: path.to_string_lossy().to_string(),
+        reason: reason.to_string(),
+        backtrace: xbt(),
+    }
+}
+
+struct SliceReader<'d> {
+    data: &'d [u8],
+    path: &'d Path,
+}
+
+impl<'d> SliceReader<'d> {
+    fn take(&mut self, len: usize) -> VResult<&'d [u8]> {
+        if len > self.data.len() {
+            return Err(invalid_state(self.path, "truncated"));
+        }
+        let (taken, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(taken)
+    }
+
+    fn take_array<const N: usize>(&mut self) -> VResult<[u8; N]> {
+        Ok(self.take(N)?.try_into().unwrap())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::state::tuneables::Tunables;
+    use crate::surface::pixel::Pixel;
+    use crate::surfacev::vamount_map::VAmountMap;
+    use crate::surfacev::ventity_map::{VEntity, VEntityMap, VPixel};
+    use crate::surfacev::vsurface::state_file::{
+        STATE_VERSION, SurfaceStateRef, read_state, write_state,
+    };
+    use facto_loop_miner_fac_engine::common::vpoint::VPoint;
+    use std::path::Path;
+
+    #[test]
+    fn test_roundtrip() {
+        let path = Path::new("test");
+        let mut pixels: VEntityMap<VPixel> = VEntityMap::new(20);
+        pixels.change(vec![VPoint::new(1, 2)]).stomp(Pixel::Coal);
+        let mut entities: VEntityMap<VEntity> = VEntityMap::new_chunked(20);
+        entities.place(VEntity {
+            name: "small-electric-pole".to_string(),
+            position: VPoint::new(3, 4),
+            width: 1,
+            height: 1,
+        });
+        let amounts = VAmountMap::new(20);
+
+        let mut data = Vec::new();
+        let state = SurfaceStateRef {
+            pixels: &pixels,
+            entities: &entities,
+            patches: &[],
+            rails: &[],
+            failed_mines: &[],
+            amounts: &amounts,
+            tunables: &Tunables::new(),
+        };
+        write_state(&mut data, &state, path).unwrap();
+
+        let loaded = read_state(&data, path).unwrap();
+        assert_eq!(loaded.version, STATE_VERSION);
+        assert_eq!(loaded.pixels.radius(), 20);
+        assert_eq!(loaded.pixels.iter_entities().count(), 1);
+        assert_eq!(loaded.entities.iter_entities().count(), 1);
+        assert!(loaded.patches.is_empty());
+
+        assert!(read_state(&data[..data.len() - 1], path).is_err());
+        data[8] = 99;
+        assert!(read_state(&data, path).is_err());
+    }
+}
@@ -0,0 +1,188 @@
+use crate::surface::pixel::Pixel;
+use crate::surfacev::err::{CoreConvertPathResult, VResult};
+use crate::surfacev::vsurface::pixel::SurfacePainting;
+use facto_loop_miner_common::LOCALE;
+use facto_loop_miner_common::duration::BasicWatch;
+use image::{ExtendedColorType, ImageEncoder};
+use num_format::ToFormattedString;
+use rayon::prelude::{IntoParallelIterator, ParallelIterator};
+use std::fs::{File, create_dir_all, write};
+use std::io::BufWriter;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tracing::{debug, info};
+
+pub const TILE_SIZE: usize = 256;
+const VIEWER_HTML: &str = include_str!("tiles.html");
+
+/// Square grid of pixels, row major from the top left
+struct TileLevel {
+    pixels: Vec<Pixel>,
+    size: usize,
+}
+
+impl TileLevel {
+    /// Each 2x2 block becomes one pixel
+    fn downsample(&self) -> TileLevel {
+        let size = self.size.div_ceil(2);
+        let mut pixels = Vec::with_capacity(size * size);
+        let mut block = Vec::with_capacity(4);
+        for y in 0..size {
+            for x in 0..size {
+                block.clear();
+                for (dx, dy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+                    let (source_x, source_y) = (x * 2 + dx, y * 2 + dy);
+                    if source_x < self.size && source_y < self.size {
+                        block.push(self.pixels[source_y * self.size + source_x]);
+                    }
+                }
+                pixels.push(merge_block(&block));
+            }
+        }
+        TileLevel { pixels, size }
+    }
+
+    fn tiles_per_side(&self) -> usize {
+        self.size.div_ceil(TILE_SIZE)
+    }
+
+    /// RGBA, transparent past the edge. None if the tile is entirely Empty
+    fn paint_tile(&self, tile_x: usize, tile_y: usize) -> Option<Vec<u8>> {
+        let mut output = vec![0; TILE_SIZE * TILE_SIZE * 4];
+        let mut any_filled = false;
+        for inner_y in 0..TILE_SIZE {
+            let y = tile_y * TILE_SIZE + inner_y;
+            if y >= self.size {
+                break;
+            }
+            for inner_x in 0..TILE_SIZE {
+                let x = tile_x * TILE_SIZE + inner_x;
+                if x >= self.size {
+                    break;
+                }
+                let pixel = self.pixels[y * self.size + x];
+                any_filled |= pixel != Pixel::Empty;
+                let start = (inner_y * TILE_SIZE + inner_x) * 4;
+                output[start..start + 3].copy_from_slice(&pixel.color());
+                output[start + 3] = 0xFF;
+            }
+        }
+        any_filled.then_some(output)
+    }
+}
+
+fn merge_block(block: &[Pixel]) -> Pixel {
+    if let Some(priority) = block
+        .iter()
+        .filter(|pixel| pixel.downsample_priority() != 0)
+        .max_by_key(|pixel| pixel.downsample_priority())
+    {
+        return *priority;
+    }
+    // most common, Empty loses ties
+    let mut best = Pixel::Empty;
+    let mut best_count = 0;
+    for pixel in block {
+        let count = block.iter().filter(|other| *other == pixel).count();
+        if count > best_count || (count == best_count && best == Pixel::Empty) {
+            best = *pixel;
+            best_count = count;
+        }
+    }
+    best
+}
+
+/// Write `{z}/{x}/{y}.png` tiles and `index.html`. The highest zoom is one tile per pixel,
+/// zoom 0 fits the entire surface in one tile. Empty tiles are not written.
+/// `index.html` loads Leaflet from unpkg.com, viewing needs network access
+pub fn export_tiles<'p>(
+    xy_pixels: impl Iterator<Item = &'p Pixel>,
+    diameter: usize,
+    out_dir: &Path,
+) -> VResult<()> {
+    let watch = BasicWatch::start();
+    let max_zoom = diameter.div_ceil(TILE_SIZE).next_power_of_two().ilog2();
+
+    let mut level = TileLevel {
+        pixels: xy_pixels.copied().collect(),
+        size: diameter,
+    };
+    assert_eq!(level.pixels.len(), diameter * diameter);
+
+    let total_tiles = AtomicUsize::new(0);
+    for zoom in (0..=max_zoom).rev() {
+        let tiles_per_side = level.tiles_per_side();
+        (0..tiles_per_side * tiles_per_side)
+            .into_par_iter()
+            .try_for_each(|tile_index| {
+                let (tile_x, tile_y) = (tile_index % tiles_per_side, tile_index / tiles_per_side);
+                let Some(output) = level.paint_tile(tile_x, tile_y) else {
+                    return Ok(());
+                };
+                let dir = out_dir.join(zoom.to_string()).join(tile_x.to_string());
+                create_dir_all(&dir).convert(&dir)?;
+                let path = dir.join(format!("{tile_y}.png"));
+                let file = File::create(&path).convert(&path)?;
+                SurfacePainting::encoder(BufWriter::new(file))
+                    .write_image(
+                        &output,
+                        TILE_SIZE as u32,
+                        TILE_SIZE as u32,
+                        ExtendedColorType::Rgba8,
+                    )
+                    .convert(&path)?;
+                total_tiles.fetch_add(1, Ordering::Relaxed);
+                VResult::Ok(())
+            })?;
+        debug!("zoom {zoom} size {} tiles {tiles_per_side}^2", level.size);
+        if zoom != 0 {
+            level = level.downsample();
+        }
+    }
+
+    let html_path = out_dir.join("index.html");
+    let html = VIEWER_HTML
+        .replace("{{max_zoom}}", &max_zoom.to_string())
+        .replace("{{diameter}}", &diameter.to_string())
+        .replace("{{tile_size}}", &TILE_SIZE.to_string());
+    write(&html_path, html).convert(&html_path)?;
+
+    info!(
+        "Exported {} tiles zoom 0-{max_zoom} to {} in {watch}",
+        total_tiles.into_inner().to_formatted_string(&LOCALE),
+        out_dir.display(),
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::surface::pixel::Pixel;
+    use crate::surfacev::vsurface::tiles::{TileLevel, merge_block};
+
+    #[test]
+    fn test_merge_block() {
+        use Pixel::*;
+        assert_eq!(merge_block(&[Empty, Empty, Empty, Rail]), Rail);
+        assert_eq!(merge_block(&[IronOre, IronOre, Coal, Empty]), IronOre);
+        assert_eq!(merge_block(&[Empty, Empty, Coal, Coal]), Coal);
+        assert_eq!(merge_block(&[Highlighter, Rail]), Highlighter);
+    }
+
+    #[test]
+    fn test_downsample_odd() {
+        use Pixel::*;
+        let level = TileLevel {
+            pixels: [
+                [Empty, Empty, Coal],
+                [Empty, Rail, Coal],
+                [Stone, Stone, Empty],
+            ]
+            .concat(),
+            size: 3,
+        };
+        let next = level.downsample();
+        assert_eq!(next.size, 2);
+        assert_eq!(next.pixels, [Rail, Coal, Stone, Empty]);
+    }
+}
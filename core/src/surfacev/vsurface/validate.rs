@@ -0,0 +1,428 @@
+use crate::surface::pixel::Pixel;
+use crate::surfacev::err::{CoreConvertPathResult, VResult};
+use crate::surfacev::vsurface::{
+    VSurface, VSurfaceEntityAsVs, VSurfacePatchAsVs, VSurfacePixelAsVs, VSurfaceRailAsVs,
+};
+use facto_loop_miner_common::LOCALE;
+use facto_loop_miner_fac_engine::common::vpoint::VPoint;
+use num_format::ToFormattedString;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use tracing::{error, info};
+
+/// Invariants of a surface checked in one pass. Violations are grouped with a count and
+/// the first offending point, not one entry per point
+#[derive(Debug, Default, Serialize)]
+pub struct SurfaceValidation {
+    pub checks: usize,
+    pub violations: Vec<SurfaceViolation>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "kind")]
+pub enum SurfaceViolation {
+    /// xy index past the end of the entity list
+    DanglingXy {
+        layer: &'static str,
+        count: usize,
+        first: VPoint,
+    },
+    /// Rail pixel not in any mine path
+    RailUnowned { count: usize, first: VPoint },
+    /// Mine path point that isn't a Rail pixel
+    PathNotRail {
+        path_index: usize,
+        count: usize,
+        first: VPoint,
+    },
+    /// Mine path points also in an earlier path
+    PathOverlap {
+        path_index: usize,
+        other_index: usize,
+        count: usize,
+        first: VPoint,
+    },
+    /// Mine path endpoint off the rail step grid
+    EndpointMisaligned {
+        path_index: usize,
+        point: VPoint,
+        reason: String,
+    },
+    /// Patch point with a different pixel than the patch resource
+    PatchMismatch {
+        patch_index: usize,
+        resource: Pixel,
+        count: usize,
+        first: VPoint,
+    },
+    /// Patch points also in an earlier patch
+    PatchOverlap {
+        patch_index: usize,
+        other_index: usize,
+        count: usize,
+        first: VPoint,
+    },
+    /// Outside the surface radius
+    OutOfRadius {
+        layer: &'static str,
+        index: usize,
+        count: usize,
+        first: VPoint,
+    },
+}
+
+/// Count and first point per key
+type Tally<K> = BTreeMap<K, (usize, VPoint)>;
+
+fn tally<K: Ord>(tally: &mut Tally<K>, key: K, point: VPoint) {
+    tally.entry(key).or_insert((0, point)).0 += 1;
+}
+
+impl SurfaceValidation {
+    pub fn new(surface: &VSurface) -> Self {
+        let mut validation = SurfaceValidation::default();
+        validation.validate_xy(surface);
+        validation.validate_rails(surface);
+        validation.validate_patches(surface);
+        validation.validate_entities(surface);
+        validation
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.violations.is_empty()
+    }
+
+    fn validate_xy(&mut self, surface: &VSurface) {
+        for (layer, dangling) in [
+            ("pixels", surface.pixels.find_dangling_xy()),
+            ("entities", surface.entities.find_dangling_xy()),
+        ] {
+            self.checks += 1;
+            if let Some(first) = dangling.first() {
+                self.violations.push(SurfaceViolation::DanglingXy {
+                    layer,
+                    count: dangling.len(),
+                    first: *first,
+                });
+            }
+        }
+    }
+
+    fn validate_rails(&mut self, surface: &VSurface) {
+        let pixels = surface.pixels();
+        let mut owners: HashMap<VPoint, usize> = HashMap::new();
+        let mut out_of_radius: Tally<usize> = BTreeMap::new();
+        let mut not_rail: Tally<usize> = BTreeMap::new();
+        let mut overlaps: Tally<(usize, usize)> = BTreeMap::new();
+        for (path_index, mine_path) in surface.rails().get_mine_paths().iter().enumerate() {
+            for endpoint in [&mine_path.segment.start, &mine_path.segment.end] {
+                self.checks += 1;
+                if let Some(reason) = endpoint.0.test_step_rail() {
+                    self.violations.push(SurfaceViolation::EndpointMisaligned {
+                        path_index,
+                        point: endpoint.0,
+                        reason,
+                    });
+                }
+            }
+
+            for point in mine_path.total_area() {
+                self.checks += 1;
+                if pixels.is_point_out_of_bounds(&point) {
+                    tally(&mut out_of_radius, path_index, point);
+                    continue;
+                }
+                if pixels.get_pixel(point) != Pixel::Rail {
+                    tally(&mut not_rail, path_index, point);
+                }
+                if let Some(other_index) = owners.insert(point, path_index) {
+                    tally(&mut overlaps, (path_index, other_index), point);
+                }
+            }
+        }
+
+        let mut unowned: Option<(usize, VPoint)> = None;
+        for (point, pixel) in pixels.get_pixels_all() {
+            if pixel != Pixel::Rail {
+                continue;
+            }
+            self.checks += 1;
+            if !owners.contains_key(&point) {
+                unowned.get_or_insert((0, point)).0 += 1;
+            }
+        }
+
+        self.push_out_of_radius("rails", out_of_radius);
+        for (path_index, (count, first)) in not_rail {
+            self.violations.push(SurfaceViolation::PathNotRail {
+                path_index,
+                count,
+                first,
+            });
+        }
+        for ((path_index, other_index), (count, first)) in overlaps {
+            self.violations.push(SurfaceViolation::PathOverlap {
+                path_index,
+                other_index,
+                count,
+                first,
+            });
+        }
+        if let Some((count, first)) = unowned {
+            self.violations
+                .push(SurfaceViolation::RailUnowned { count, first });
+        }
+    }
+
+    fn validate_patches(&mut self, surface: &VSurface) {
+        let pixels = surface.pixels();
+        let mut owners: HashMap<VPoint, usize> = HashMap::new();
+        let mut out_of_radius: Tally<usize> = BTreeMap::new();
+        let mut mismatches: Tally<usize> = BTreeMap::new();
+        let mut overlaps: Tally<(usize, usize)> = BTreeMap::new();
+        let patches = surface.patches();
+        for (patch_index, patch) in patches.get_patches().iter().enumerate() {
+            for point in &patch.pixel_indexes {
+                self.checks += 1;
+                if pixels.is_point_out_of_bounds(point) {
+                    tally(&mut out_of_radius, patch_index, *point);
+                    continue;
+                }
+                if pixels.get_pixel(point) != patch.resource {
+                    tally(&mut mismatches, patch_index, *point);
+                }
+                if let Some(other_index) = owners.insert(*point, patch_index) {
+                    tally(&mut overlaps, (patch_index, other_index), *point);
+                }
+            }
+        }
+
+        self.push_out_of_radius("patches", out_of_radius);
+        for (patch_index, (count, first)) in mismatches {
+            self.violations.push(SurfaceViolation::PatchMismatch {
+                patch_index,
+                resource: patches.get_patches()[patch_index].resource,
+                count,
+                first,
+            });
+        }
+        for ((patch_index, other_index), (count, first)) in overlaps {
+            self.violations.push(SurfaceViolation::PatchOverlap {
+                patch_index,
+                other_index,
+                count,
+                first,
+            });
+        }
+    }
+
+    fn validate_entities(&mut self, surface: &VSurface) {
+        let pixels = surface.pixels();
+        let mut out_of_radius: Tally<usize> = BTreeMap::new();
+        for (entity_index, entity) in surface.entities().iter_entities().enumerate() {
+            self.checks += 1;
+            let area = entity.area();
+            for corner in [area.point_top_left(), area.point_bottom_right()] {
+                if pixels.is_point_out_of_bounds(&corner) {
+                    tally(&mut out_of_radius, entity_index, corner);
+                }
+            }
+        }
+        self.push_out_of_radius("entities", out_of_radius);
+    }
+
+    fn push_out_of_radius(&mut self, layer: &'static str, out_of_radius: Tally<usize>) {
+        for (index, (count, first)) in out_of_radius {
+            self.violations.push(SurfaceViolation::OutOfRadius {
+                layer,
+                index,
+                count,
+                first,
+            });
+        }
+    }
+
+    pub fn log_report(&self) {
+        if self.is_valid() {
+            info!(
+                "Surface valid after {} checks",
+                self.checks.to_formatted_string(&LOCALE)
+            );
+            return;
+        }
+        error!(
+            "Surface has {} violations after {} checks",
+            self.violations.len(),
+            self.checks.to_formatted_string(&LOCALE)
+        );
+        for violation in &self.violations {
+            error!("  {violation:?}");
+        }
+    }
+
+    pub fn save(&self, out_dir: &Path) -> VResult<()> {
+        let report_path = out_dir.join("surface-validation.json");
+        let output = simd_json::to_vec_pretty(self).convert(&report_path)?;
+        std::fs::write(&report_path, &output).convert(&report_path)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::surface::pixel::Pixel;
+    use crate::surfacev::mine::MinePath;
+    use crate::surfacev::vpatch::VPatch;
+    use crate::surfacev::vsurface::validate::SurfaceViolation;
+    use crate::surfacev::vsurface::{
+        VSurface, VSurfacePatchAsVsMut, VSurfacePixelAsVs, VSurfacePixelAsVsMut,
+        VSurfaceRailAsVsMut,
+    };
+    use facto_loop_miner_fac_engine::common::varea::VArea;
+    use facto_loop_miner_fac_engine::common::vpoint::VPoint;
+    use facto_loop_miner_fac_engine::common::vpoint_direction::VPointDirectionQ;
+    use facto_loop_miner_fac_engine::game_blocks::rail_hope_single::SECTION_POINTS_I32;
+    use facto_loop_miner_fac_engine::game_entities::direction::FacDirectionQuarter;
+
+    fn straight_path(surface: &VSurface) -> MinePath {
+        MinePath::new_test_straight(
+            surface.pixels(),
+            VPointDirectionQ(
+                VPoint::new(-2 * SECTION_POINTS_I32, 0),
+                FacDirectionQuarter::East,
+            ),
+            4,
+        )
+    }
+
+    fn iron_patch(pixel_indexes: Vec<VPoint>) -> VPatch {
+        VPatch {
+            resource: Pixel::IronOre,
+            area: VArea::from_arbitrary_points(pixel_indexes.iter().copied()),
+            pixel_indexes,
+            total_amount: 0,
+            amount_centroid: None,
+        }
+    }
+
+    #[test]
+    fn test_unowned_rail() {
+        let mut surface = VSurface::new(10);
+        assert!(surface.validate().is_valid());
+
+        surface
+            .pixels_mut()
+            .change_pixels(vec![VPoint::new(1, 1), VPoint::new(2, 2)])
+            .stomp(Pixel::Rail);
+        let validation = surface.validate();
+        assert!(matches!(
+            validation.violations.as_slice(),
+            [SurfaceViolation::RailUnowned { count: 2, .. }]
+        ));
+    }
+
+    #[test]
+    fn test_path_overlap() {
+        let mut surface = VSurface::new(200);
+        let path = straight_path(&surface);
+        let total_points = path.total_area().len();
+        surface.rails_mut().add_mine_path(path.clone());
+        assert!(surface.validate().is_valid());
+
+        surface.rails_mut().add_mine_path(path);
+        let validation = surface.validate();
+        let [
+            SurfaceViolation::PathOverlap {
+                path_index: 1,
+                other_index: 0,
+                count,
+                ..
+            },
+        ] = validation.violations.as_slice()
+        else {
+            panic!("{:?}", validation.violations);
+        };
+        assert_eq!(*count, total_points);
+    }
+
+    #[test]
+    fn test_path_not_rail() {
+        let mut surface = VSurface::new(200);
+        let path = straight_path(&surface);
+        let point = path.total_area()[0];
+        surface.rails_mut().add_mine_path(path);
+        surface
+            .pixels_mut()
+            .change_pixels(vec![point])
+            .stomp(Pixel::IronOre);
+
+        let validation = surface.validate();
+        assert!(matches!(
+            validation.violations.as_slice(),
+            [SurfaceViolation::PathNotRail { path_index: 0, count: 1, first }] if *first == point
+        ));
+    }
+
+    #[test]
+    fn test_endpoint_misaligned() {
+        let mut surface = VSurface::new(200);
+        let mut path = straight_path(&surface);
+        let misaligned = path.segment.end.0 + VPoint::new(1, 0);
+        path.segment.end.0 = misaligned;
+        surface.rails_mut().add_mine_path(path);
+
+        let validation = surface.validate();
+        assert!(matches!(
+            validation.violations.as_slice(),
+            [SurfaceViolation::EndpointMisaligned { path_index: 0, point, .. }]
+                if *point == misaligned
+        ));
+    }
+
+    #[test]
+    fn test_patch_mismatch() {
+        let mut surface = VSurface::new(10);
+        let points = vec![VPoint::new(1, 1), VPoint::new(2, 1), VPoint::new(3, 1)];
+        surface
+            .pixels_mut()
+            .change_pixels(points[..2].to_vec())
+            .stomp(Pixel::IronOre);
+        surface.patches_mut().add_patches([iron_patch(points)]);
+
+        let validation = surface.validate();
+        assert!(matches!(
+            validation.violations.as_slice(),
+            [SurfaceViolation::PatchMismatch {
+                patch_index: 0,
+                resource: Pixel::IronOre,
+                count: 1,
+                first,
+            }] if *first == VPoint::new(3, 1)
+        ));
+    }
+
+    #[test]
+    fn test_out_of_radius() {
+        let mut surface = VSurface::new(10);
+        let outside = VPoint::new(20, 20);
+        surface
+            .pixels_mut()
+            .change_pixels(vec![VPoint::new(1, 1)])
+            .stomp(Pixel::IronOre);
+        surface
+            .patches_mut()
+            .add_patches([iron_patch(vec![VPoint::new(1, 1), outside])]);
+
+        let validation = surface.validate();
+        assert!(matches!(
+            validation.violations.as_slice(),
+            [SurfaceViolation::OutOfRadius {
+                layer: "patches",
+                index: 0,
+                count: 1,
+                first,
+            }] if *first == outside
+        ));
+    }
+}
@@ -67,3 +67,153 @@ end
         "#).trim().replace('\n', " ")
     }
 }
+
+/// What a `FacScanPage` exports, same names as the scanner mod files
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FacScanKind {
+    /// Extended format with resource amount
+    Entities,
+    Tiles,
+    Obstacles {
+        trees: bool,
+    },
+}
+
+/// Export one area of the map in the compressed `[name1, x1, y1, ...]` format.
+///
+/// Only things positioned inside the half-open area are returned, so pages never overlap.
+/// Prints only the byte length, the JSON is read with [`FacScanChunk`]
+#[derive(Debug)]
+pub struct FacScanPage {
+    kind: FacScanKind,
+    top_left: [i32; 2],
+    bottom_right: [i32; 2],
+}
+
+impl FacScanPage {
+    pub fn new(kind: FacScanKind, top_left: [i32; 2], bottom_right: [i32; 2]) -> Self {
+        FacScanPage {
+            kind,
+            top_left,
+            bottom_right,
+        }
+    }
+
+    fn make_lua_body(&self) -> String {
+        match self.kind {
+            FacScanKind::Entities => r#"
+for _, entity in ipairs(surface.find_entities_filtered { area = area, name = { "iron-ore", "copper-ore", "stone", "coal", "uranium-ore", "crude-oil", "steel-chest" } }) do
+    if in_page(entity.position) then
+        table.insert(output, entity.name)
+        table.insert(output, entity.position.x)
+        table.insert(output, entity.position.y)
+        if entity.type == "resource" then
+            table.insert(output, entity.amount)
+        else
+            table.insert(output, 0)
+        end
+    end
+end
+            "#
+            .to_string(),
+            FacScanKind::Tiles => r#"
+for _, tile in ipairs(surface.find_tiles_filtered { area = area, name = { "water" } }) do
+    if in_page(tile.position) then
+        table.insert(output, tile.name)
+        table.insert(output, tile.position.x)
+        table.insert(output, tile.position.y)
+    end
+end
+            "#
+            .to_string(),
+            FacScanKind::Obstacles { trees } => format!(
+                r#"
+local function insert_tiles(name, entity)
+    if not in_page(entity.position) then
+        return
+    end
+    local box = entity.bounding_box
+    for x = math.floor(box.left_top.x), math.ceil(box.right_bottom.x) - 1 do
+        for y = math.floor(box.left_top.y), math.ceil(box.right_bottom.y) - 1 do
+            table.insert(output, name)
+            table.insert(output, x + 0.5)
+            table.insert(output, y + 0.5)
+        end
+    end
+end
+for _, entity in ipairs(surface.find_entities_filtered {{ area = area, type = "cliff" }}) do
+    insert_tiles("cliff", entity)
+end
+for _, entity in ipairs(surface.find_entities_filtered {{ area = area, type = "simple-entity" }}) do
+    if string.find(entity.name, "rock", 1, true) then
+        insert_tiles("rock", entity)
+    end
+end
+if {trees} then
+    for _, entity in ipairs(surface.find_entities_filtered {{ area = area, type = "tree" }}) do
+        insert_tiles("tree", entity)
+    end
+end
+for _, entity in ipairs(surface.find_entities_filtered {{ area = area, force = "player" }}) do
+    if entity.name ~= "steel-chest" and entity.type ~= "character" then
+        insert_tiles("player-entity", entity)
+    end
+end
+            "#
+            ),
+        }
+    }
+}
+
+impl LuaCommand for FacScanPage {
+    fn make_lua(&self) -> String {
+        let [left, top] = self.top_left;
+        let [right, bottom] = self.bottom_right;
+        let body = self.make_lua_body();
+        // table_to_json writes an empty table as an object
+        format!(
+            r#"
+local area = {{ {{ {left}, {top} }}, {{ {right}, {bottom} }} }}
+local surface = game.surfaces[1]
+local output = {{}}
+local function in_page(pos)
+    return pos.x >= {left} and pos.x < {right} and pos.y >= {top} and pos.y < {bottom}
+end
+{body}
+if #output == 0 then
+    facscan_page = "[]"
+else
+    facscan_page = game.table_to_json(output)
+end
+rcon.print(#facscan_page)
+        "#
+        )
+        .trim()
+        .replace('\n', " ")
+    }
+}
+
+/// Bounded piece of the last [`FacScanPage`] JSON. Large RCON responses are split or truncated.
+///
+/// Prefixed with `index:` so a lost or out of order response is detected
+#[derive(Debug)]
+pub struct FacScanChunk {
+    index: usize,
+    chunk_bytes: usize,
+}
+
+impl FacScanChunk {
+    pub fn new(index: usize, chunk_bytes: usize) -> Self {
+        FacScanChunk { index, chunk_bytes }
+    }
+}
+
+impl LuaCommand for FacScanChunk {
+    fn make_lua(&self) -> String {
+        let index = self.index;
+        // lua strings are 1 based and inclusive
+        let start = index * self.chunk_bytes + 1;
+        let end = (index + 1) * self.chunk_bytes;
+        format!(r#"rcon.print("{index}:" .. string.sub(facscan_page, {start}, {end}))"#)
+    }
+}
@@ -2,19 +2,19 @@ use serde::{Deserialize, Serialize};
 
 use crate::{
     admiral::lua_command::fac_surface_create_tile::FacSurfaceCreateLua,
-    common::{names_tile::FacTileConcreteType, vpoint::VPoint},
+    common::{names_tile::FacTileName, vpoint::VPoint},
 };
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "kebab-case")]
 #[serde(deny_unknown_fields)]
 pub struct FacBpTile {
-    pub name: FacTileConcreteType,
+    pub name: FacTileName,
     pub position: VPoint,
 }
 
 impl FacBpTile {
-    pub fn new(name: FacTileConcreteType, position: VPoint) -> Self {
+    pub fn new(name: FacTileName, position: VPoint) -> Self {
         Self { name, position }
     }
 
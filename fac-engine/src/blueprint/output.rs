@@ -13,7 +13,11 @@ use crate::{
         executor::{ExecuteResponse, LuaCompiler, client::AdmiralClient},
         lua_command::LuaCommand,
     },
-    common::{entity::FacEntity, names::FacEntityName, vpoint::VPoint},
+    common::{
+        entity::{FacEntity, Size},
+        names::FacEntityName,
+        vpoint::VPoint,
+    },
     util::ansi::{
         C_BLOCK_LINE, C_FULL_BLOCK, Color, ansi_color, ansi_erase_line, ansi_previous_line,
     },
@@ -51,6 +55,7 @@ impl FacItemOutput {
                 cache: Vec::new(),
                 total_write: 0,
                 contexts: Default::default(),
+                placed: None,
             }),
         }
     }
@@ -63,6 +68,7 @@ impl FacItemOutput {
                 cache: Vec::new(),
                 total_write: 0,
                 contexts: Default::default(),
+                placed: None,
             }),
         }
     }
@@ -75,6 +81,7 @@ impl FacItemOutput {
                 cache: Vec::new(),
                 total_write: 0,
                 contexts: Default::default(),
+                placed: None,
             }),
         }
     }
@@ -87,15 +94,36 @@ impl FacItemOutput {
                 cache: Vec::new(),
                 total_write: 0,
                 contexts: Default::default(),
+                placed: None,
             }),
         }
     }
 
+    /// Keep every written entity for [`Self::take_placed`], eg to record them on a map
+    pub fn with_placed_recording(self) -> Self {
+        self.odata.borrow_mut().placed = Some(Vec::new());
+        self
+    }
+
+    pub fn take_placed(&self) -> Vec<FacItemPlaced> {
+        match &mut self.odata.borrow_mut().placed {
+            Some(placed) => std::mem::take(placed),
+            None => panic!("placed recording not enabled"),
+        }
+    }
+
     pub fn writei(&self, entity: impl FacEntity + 'static, position: VPoint) {
         self.write(BlueprintItem::newb(entity, position))
     }
 
     pub fn write(&self, item: BlueprintItem) {
+        if let Some(placed) = &mut self.odata.borrow_mut().placed {
+            placed.push(FacItemPlaced {
+                name: item.entity().name(),
+                position: *item.position(),
+                size: item.entity().rectangle_size(),
+            });
+        }
         if let FacItemOutputType::Null = self.odata.borrow().otype {
             return;
         }
@@ -383,6 +411,15 @@ struct FacItemOutputData {
     cache: Vec<FacItemOutputWrite>,
     total_write: usize,
     contexts: FacItemOutputLogInfo,
+    placed: Option<Vec<FacItemPlaced>>,
+}
+
+/// Entity written to the output. Position is the top left corner
+#[derive(Debug)]
+pub struct FacItemPlaced {
+    pub name: FacEntityName,
+    pub position: VPoint,
+    pub size: Size,
 }
 
 enum FacItemOutputType {
@@ -429,6 +466,7 @@ impl FacItemOutputData {
             cache,
             total_write,
             contexts: _,
+            placed: _,
         } = self;
         match otype {
             FacItemOutputType::AdmiralClient(inner) => {
@@ -1,4 +1,5 @@
 use exhaustive::Exhaustive;
+use serde::de::Error;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use strum::AsRefStr;
 
@@ -10,6 +11,14 @@ pub enum FacTileConcreteType {
     RefinedHazard(FacTileDirection),
 }
 
+/// Any placeable tile
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FacTileName {
+    Concrete(FacTileConcreteType),
+    /// Not concrete, turns water into buildable land
+    Landfill,
+}
+
 #[derive(Clone, Copy, Debug, PartialEq, AsRefStr, Exhaustive)]
 #[strum(serialize_all = "lowercase")]
 pub enum FacTileDirection {
@@ -30,6 +39,15 @@ impl FacTileConcreteType {
     }
 }
 
+impl FacTileName {
+    pub fn to_fac_name(&self) -> String {
+        match self {
+            Self::Concrete(concrete) => concrete.to_fac_name(),
+            Self::Landfill => "landfill".into(),
+        }
+    }
+}
+
 impl Serialize for FacTileConcreteType {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -47,3 +65,28 @@ impl<'de> Deserialize<'de> for FacTileConcreteType {
         todo!("asdf")
     }
 }
+
+impl Serialize for FacTileName {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&self.to_fac_name())
+    }
+}
+
+impl<'de> Deserialize<'de> for FacTileName {
+    fn deserialize<D>(deserializer: D) -> Result<FacTileName, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let name = String::deserialize(deserializer)?;
+        if name == "landfill" {
+            return Ok(Self::Landfill);
+        }
+        FacTileConcreteType::iter_exhaustive(None)
+            .find(|concrete| concrete.to_fac_name() == name)
+            .map(Self::Concrete)
+            .ok_or_else(|| D::Error::custom(format!("unknown tile {name}")))
+    }
+}
@@ -54,6 +54,13 @@ impl VArea {
             && target.y() <= self.bottom_right.y()
     }
 
+    pub fn overlaps(&self, other: &VArea) -> bool {
+        self.top_left.x() <= other.bottom_right.x()
+            && other.top_left.x() <= self.bottom_right.x()
+            && self.top_left.y() <= other.bottom_right.y()
+            && other.top_left.y() <= self.bottom_right.y()
+    }
+
     pub fn contains_points_any(
         &self,
         targets: impl IntoIterator<Item = impl Borrow<VPoint>>,
@@ -183,6 +190,12 @@ mod test {
         assert_eq!(start, VPoint::new(2, 2));
         assert_eq!(end, VPoint::new(4, 4));
 
+        let touching = VArea::from_arbitrary_points_pair(VPoint::new(4, 0), VPoint::new(6, 2));
+        assert!(area.overlaps(&touching));
+        assert!(touching.overlaps(&area));
+        let beside = VArea::from_arbitrary_points_pair(VPoint::new(5, 0), VPoint::new(6, 9));
+        assert!(!area.overlaps(&beside));
+
         let points = area.get_points();
         assert!(!points.contains(&VPoint::new(0, 0)));
         assert!(!points.contains(&VPoint::new(1, 1)));
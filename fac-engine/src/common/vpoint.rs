@@ -6,6 +6,7 @@ use crate::game_blocks::rail_hope_single::SECTION_POINTS_I32;
 use crate::game_entities::direction::FacDirectionQuarter;
 use crate::util::ansi::C_BLOCK_LINE;
 use core::fmt;
+#[cfg(feature = "opencv_re")]
 use opencv::core::{Point, Rect, Size};
 use serde::{Deserialize, Serialize};
 use std::backtrace::Backtrace;
@@ -73,6 +74,7 @@ impl VPoint {
         }
     }
 
+    #[cfg(feature = "opencv_re")]
     pub fn from_cv_point(point: Point) -> Self {
         VPoint {
             x: point.x,
@@ -116,6 +118,7 @@ impl VPoint {
         FacBpPosition::new(self.x as f32, self.y as f32)
     }
 
+    #[cfg(feature = "opencv_re")]
     pub const fn to_cv_point(&self) -> Point {
         Point {
             x: self.x,
@@ -138,6 +141,7 @@ impl VPoint {
             && self.y < center_radius
     }
 
+    #[cfg(feature = "opencv_re")]
     pub const fn from_rect_start(rect: &Rect) -> Self {
         VPoint {
             x: rect.x,
@@ -428,8 +432,10 @@ impl VPoint {
         right: VPoint,
     ) -> Ordering {
         match direction {
+            FacDirectionQuarter::North => right.y.cmp(&left.y),
             FacDirectionQuarter::East => left.x.cmp(&right.x),
-            _ => unimplemented!(),
+            FacDirectionQuarter::South => left.y.cmp(&right.y),
+            FacDirectionQuarter::West => right.x.cmp(&left.x),
         }
     }
 
@@ -568,6 +574,7 @@ impl VPoint {
         self.y - other.y
     }
 
+    #[cfg(feature = "opencv_re")]
     pub fn to_cv_size(&self) -> Size {
         Size {
             height: self.y,
@@ -6,7 +6,7 @@ use tracing::info;
 use super::block::FacBlock;
 use crate::blueprint::bpfac::tile::FacBpTile;
 use crate::blueprint::output::{ContextLevel, FacItemOutput};
-use crate::common::names_tile::FacTileConcreteType;
+use crate::common::names_tile::{FacTileConcreteType, FacTileName};
 use crate::game_blocks::belt_bettel::FacBlkBettelBelt;
 use crate::game_entities::infinity_power::FacEntInfinityPower;
 use crate::{
@@ -271,7 +271,7 @@ impl FacBlkAssemblerThru {
     fn generate_tiles(&self, origin: VPoint) {
         for i in 0..self.total_point_height() {
             self.output.write_tile(FacBpTile::new(
-                FacTileConcreteType::Basic,
+                FacTileName::Concrete(FacTileConcreteType::Basic),
                 origin.move_y_usize(i),
             ));
         }
@@ -418,8 +418,19 @@ impl RailHopeLink for HopeLink {
                 //     panic!("uhh {size}")
                 // }
             }
-            HopeLinkType::Shift45 { .. } => {
-                todo!("shift 45 area")
+            HopeLinkType::Shift45 { clockwise, length } => {
+                // same hack, diagonal steps between 3 straights
+                let mut rail = self.start;
+                for step in 0..(length + 7) {
+                    output.extend(rail.area_2x2());
+                    rail = rail.move_direction_usz(self.next_direction, RAIL_STRAIGHT_DIAMETER);
+                    if (2..length + 5).contains(&step) {
+                        rail = rail.move_direction_sideways_int(
+                            self.next_direction,
+                            neg_if_false(*clockwise, RAIL_STRAIGHT_DIAMETER as i32),
+                        );
+                    }
+                }
             }
         }
     }
@@ -462,7 +473,7 @@ impl HopeFactoRail {
     }
 }
 
-fn neg_if_false(flag: bool, value: i32) -> i32 {
+pub(super) fn neg_if_false(flag: bool, value: i32) -> i32 {
     if flag { value } else { -value }
 }
 
@@ -489,7 +500,13 @@ impl Display for HopeLinkType {
             HopeLinkType::Turn90 { clockwise } => {
                 write!(f, "Turn90-{}", if *clockwise { "clw" } else { "ccw" })
             }
-            HopeLinkType::Shift45 { .. } => todo!(),
+            HopeLinkType::Shift45 { clockwise, length } => {
+                write!(
+                    f,
+                    "Shift45-{}#{length}",
+                    if *clockwise { "clw" } else { "ccw" }
+                )
+            }
         }
     }
 }
@@ -648,6 +665,7 @@ mod test {
         hope.add_straight(1);
         let links = hope.links.clone();
         drop(hope);
+        assert_eq!(links[0].rtype.to_string(), "Shift45-clw#1");
 
         let bpcontents = output.consume_rc().into_blueprint_contents();
         compare_output(
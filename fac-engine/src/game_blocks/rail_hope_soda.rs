@@ -1,7 +1,9 @@
 use crate::common::vpoint::VPoint;
 use crate::common::vpoint_direction::VPointDirectionQ;
 use crate::game_blocks::rail_hope::RailHopeLink;
-use crate::game_blocks::rail_hope_single::{HopeFactoRail, HopeLink, HopeLinkType, RailHopeSingle};
+use crate::game_blocks::rail_hope_single::{
+    HopeFactoRail, HopeLink, HopeLinkType, RailHopeSingle, neg_if_false,
+};
 use crate::game_entities::direction::FacDirectionQuarter;
 use serde::{Deserialize, Serialize};
 use std::borrow::Borrow;
@@ -21,7 +23,14 @@ pub struct HopeSodaLink {
 #[derive(Serialize, Deserialize, Eq, PartialEq, Hash, Clone, Debug)]
 enum SodaType {
     Straight,
-    Turn90 { clockwise: bool },
+    Turn90 {
+        clockwise: bool,
+    },
+    /// Diagonal over `length + 1` sodas forward and `length` sodas sideways
+    Shift45 {
+        clockwise: bool,
+        length: u8,
+    },
 }
 
 pub(super) const SODA_RAILS_NUM: usize = 13;
@@ -60,7 +69,7 @@ impl HopeSodaLink {
 
     pub fn links_source(&self) -> [HopeLink; 2] {
         let direction = match self.stype {
-            SodaType::Straight => self.source_direction,
+            SodaType::Straight | SodaType::Shift45 { .. } => self.source_direction,
             SodaType::Turn90 { clockwise } => {
                 // undo rotation
                 self.source_direction.rotate_clockwise(!clockwise)
@@ -68,7 +77,7 @@ impl HopeSodaLink {
         };
 
         let border = self
-            .center
+            .first_center()
             .move_direction_int(direction, -SODA_CENTER_OFFSET_I32 + 1);
         let source_a = border.move_direction_sideways_axis_int(direction, 2);
         source_a.assert_even_position();
@@ -84,6 +93,16 @@ impl HopeSodaLink {
         {
             sources.swap(0, 1);
         }
+        // outer track first
+        if let SodaType::Shift45 { clockwise, .. } = self.stype {
+            let source_a_clockwise = matches!(
+                direction,
+                FacDirectionQuarter::North | FacDirectionQuarter::East
+            );
+            if source_a_clockwise == clockwise {
+                sources.swap(0, 1);
+            }
+        }
         sources
     }
 
@@ -103,19 +122,78 @@ impl HopeSodaLink {
                 // assert_eq!(output.len(), 4); // sanity
                 output
             }
+            SodaType::Shift45 { clockwise, length } => {
+                // outer track starts 2 rails later, inner track catches up after
+                let shift_length = SODA_RAILS_NUM * length as usize - 3;
+                let mut output = Vec::with_capacity(5);
+                let first = sources[0].add_straight(2);
+                let middle = first.add_shift45(clockwise, shift_length);
+                let last = middle.add_straight(SODA_RAILS_NUM - 6);
+                output.extend([first, middle, last]);
+
+                let middle = sources[1].add_shift45(clockwise, shift_length);
+                let last = middle.add_straight(SODA_RAILS_NUM - 4);
+                output.extend([middle, last]);
+                output
+            }
+        }
+    }
+
+    /// Center of the first soda entered. Shifts end diagonally away from it
+    fn first_center(&self) -> VPoint {
+        match self.stype {
+            SodaType::Straight | SodaType::Turn90 { .. } => self.center,
+            SodaType::Shift45 { clockwise, length } => {
+                let back = -SODA_SIZE * length as i32;
+                self.center
+                    .move_direction_int(self.source_direction, back)
+                    .move_direction_sideways_int(
+                        self.source_direction,
+                        neg_if_false(clockwise, back),
+                    )
+            }
+        }
+    }
+
+    /// Sodas moved forward, each with a 104 point area
+    pub fn length_sodas(&self) -> usize {
+        match self.stype {
+            SodaType::Straight | SodaType::Turn90 { .. } => 1,
+            SodaType::Shift45 { length, .. } => length as usize + 1,
+        }
+    }
+
+    /// Centers of every soda the rails pass through
+    pub fn cells(&self) -> Vec<VPoint> {
+        let mut cells = vec![self.center];
+        if let SodaType::Shift45 { clockwise, length } = self.stype {
+            for back in 1..=length as i32 {
+                let behind = self
+                    .center
+                    .move_direction_int(self.source_direction, -SODA_SIZE * back);
+                for sideways in [back, back - 1] {
+                    cells.push(behind.move_direction_sideways_int(
+                        self.source_direction,
+                        neg_if_false(clockwise, -SODA_SIZE * sideways),
+                    ));
+                }
+            }
         }
+        cells
     }
 
+    /// Bounding box, shifts include every soda they pass through
     pub fn corners(&self) -> [VPoint; 4] {
+        let first = self.first_center();
+        let min_x = first.x().min(self.center.x()) - SODA_CENTER_OFFSET_I32;
+        let min_y = first.y().min(self.center.y()) - SODA_CENTER_OFFSET_I32;
+        let max_x = first.x().max(self.center.x()) + SODA_CENTER_OFFSET_I32;
+        let max_y = first.y().max(self.center.y()) + SODA_CENTER_OFFSET_I32;
         [
-            self.center
-                .move_xy(-SODA_CENTER_OFFSET_I32, -SODA_CENTER_OFFSET_I32),
-            self.center
-                .move_xy(-SODA_CENTER_OFFSET_I32, SODA_CENTER_OFFSET_I32),
-            self.center
-                .move_xy(SODA_CENTER_OFFSET_I32, -SODA_CENTER_OFFSET_I32),
-            self.center
-                .move_xy(SODA_CENTER_OFFSET_I32, SODA_CENTER_OFFSET_I32),
+            VPoint::new(min_x, min_y),
+            VPoint::new(min_x, max_y),
+            VPoint::new(max_x, min_y),
+            VPoint::new(max_x, max_y),
         ]
     }
 
@@ -147,8 +225,25 @@ impl RailHopeLink for HopeSodaLink {
         next
     }
 
-    fn add_shift45(&self, _clockwise: bool, _length: usize) -> Self {
-        todo!()
+    /// Unlike `HopeLink`, length is in sodas sideways
+    fn add_shift45(&self, clockwise: bool, length: usize) -> Self {
+        assert!(length > 0, "shift must move sideways");
+        let length_i32 = length as i32;
+        let center = self
+            .center
+            .move_direction_int(self.source_direction, SODA_SIZE * (length_i32 + 1))
+            .move_direction_sideways_int(
+                self.source_direction,
+                neg_if_false(clockwise, SODA_SIZE * length_i32),
+            );
+        Self {
+            stype: SodaType::Shift45 {
+                clockwise,
+                length: length.try_into().unwrap(),
+            },
+            center,
+            source_direction: self.source_direction,
+        }
     }
 
     fn link_type(&self) -> HopeLinkType {
@@ -157,6 +252,10 @@ impl RailHopeLink for HopeSodaLink {
                 length: SODA_RAILS_NUM,
             },
             SodaType::Turn90 { clockwise } => HopeLinkType::Turn90 { clockwise },
+            SodaType::Shift45 { clockwise, length } => HopeLinkType::Shift45 {
+                clockwise,
+                length: length as usize,
+            },
         }
     }
 
@@ -203,9 +302,10 @@ mod test {
     use crate::blueprint::output::FacItemOutput;
     use crate::common::vpoint::VPOINT_TEN;
     use crate::game_blocks::rail_hope::RailHopeLink;
-    use crate::game_blocks::rail_hope_soda::{HopeSodaLink, sodas_to_rails};
+    use crate::game_blocks::rail_hope_soda::{HopeSodaLink, sodas_to_links, sodas_to_rails};
     use crate::game_entities::direction::FacDirectionQuarter;
     use itertools::Itertools;
+    use strum::VariantArray;
 
     #[test]
     fn straight_chain() {
@@ -277,4 +377,51 @@ mod test {
         assert_ne!(straight.area_vec(), turn_right.area_vec());
         assert_ne!(turn_left.area_vec(), turn_right.area_vec());
     }
+
+    #[test]
+    fn shift_area() {
+        for direction in FacDirectionQuarter::VARIANTS {
+            let source = HopeSodaLink::new_soda_straight(VPOINT_TEN, *direction);
+            for clockwise in [true, false] {
+                for length in 1..=3 {
+                    let shift = source.add_shift45(clockwise, length);
+                    let area = shift.area_vec();
+                    assert_eq!(area.len(), 104 * shift.length_sodas());
+                    assert_eq!(area.iter().unique().count(), area.len(), "tracks overlap");
+
+                    let cells = shift.cells();
+                    for point in &area {
+                        let in_cell = cells.iter().any(|cell| {
+                            let offset = *point - *cell;
+                            (-12..=13).contains(&offset.x()) && (-12..=13).contains(&offset.y())
+                        });
+                        assert!(in_cell, "{direction} {clockwise} {point:?} outside cells");
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn shift_connects() {
+        for direction in FacDirectionQuarter::VARIANTS {
+            let source = HopeSodaLink::new_soda_straight(VPOINT_TEN, *direction);
+            for clockwise in [true, false] {
+                let shift = source.add_shift45(clockwise, 1);
+                let after = shift.add_straight_section();
+
+                let nexts = sodas_to_links([&source, &shift])
+                    .map(|v| v.pos_next())
+                    .collect_vec();
+                let sources = [shift.links_source(), after.links_source()];
+                for link in sources.iter().flatten() {
+                    assert!(
+                        nexts.contains(&link.pos_next()),
+                        "{direction} {clockwise} gap at {:?}",
+                        link.pos_next()
+                    );
+                }
+            }
+        }
+    }
 }
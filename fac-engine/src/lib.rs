@@ -27,4 +27,5 @@ pub mod tests;
 pub mod util;
 pub mod visualizer;
 
+#[cfg(feature = "opencv_re")]
 pub use opencv as opencv_re;
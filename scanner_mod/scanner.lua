@@ -3,6 +3,8 @@
 --
 -- Uses compressed one-big-array format: [name1, x1, y1, name2, ...]
 -- Avoids significant JSON overhead from [{name: "coal", position: { x: -5.5, y: -5.5 }, ...]
+-- Entities use the extended format with resource amount: [name1, x1, y1, amount1, name2, ...]
+-- Obstacles are grouped into cliff/rock/tree/player-entity names, one entry per occupied tile center
 --
 -- Splits area into 4 sectors going to separate files.
 -- On extremely large 2000x2000 chunk plus maps,
@@ -76,11 +78,58 @@ local function mega_export_entities_compressed()
         table.insert(output, entity.name)
         table.insert(output, entity.position.x)
         table.insert(output, entity.position.y)
+        if entity.type == "resource" then
+            table.insert(output, entity.amount)
+        else
+            table.insert(output, 0)
+        end
     end
     game.write_file(file, game.table_to_json(output))
 end
 mega_export_entities_compressed()
 
+-- /c
+local function mega_export_obstacles_compressed()
+    local include_trees = true
+    local chunks = 125 * 32
+    local file = "big-obstacles-a.json"
+    log("write " .. file .. "...")
+    local output = {}
+    local surface = game.surfaces[1]
+    local area = { { -chunks, -chunks }, { chunks, chunks } }
+    local function insert_tiles(name, entity)
+        local box = entity.bounding_box
+        for x = math.floor(box.left_top.x), math.ceil(box.right_bottom.x) - 1 do
+            for y = math.floor(box.left_top.y), math.ceil(box.right_bottom.y) - 1 do
+                table.insert(output, name)
+                table.insert(output, x + 0.5)
+                table.insert(output, y + 0.5)
+            end
+        end
+    end
+    for _, entity in ipairs(surface.find_entities_filtered { area = area, type = "cliff" }) do
+        insert_tiles("cliff", entity)
+    end
+    for _, entity in ipairs(surface.find_entities_filtered { area = area, type = "simple-entity" }) do
+        if string.find(entity.name, "rock", 1, true) then
+            insert_tiles("rock", entity)
+        end
+    end
+    if include_trees then
+        for _, entity in ipairs(surface.find_entities_filtered { area = area, type = "tree" }) do
+            insert_tiles("tree", entity)
+        end
+    end
+    for _, entity in ipairs(surface.find_entities_filtered { area = area, force = "player" }) do
+        -- skip the 0x0 marker and the player themselves
+        if entity.name ~= "steel-chest" and entity.type ~= "character" then
+            insert_tiles("player-entity", entity)
+        end
+    end
+    game.write_file(file, game.table_to_json(output))
+end
+mega_export_obstacles_compressed()
+
 -- /c local test = game.surfaces[1].find_entity('steel-chest', {0.5,0.5}) if test == nill then log('nope') else log('exists') end
 -- /c
 local function insert_0x0_crate()
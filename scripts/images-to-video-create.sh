@@ -1,6 +1,12 @@
 set -x
 
+# Input is a save.paint_sink Frames directory, or the stream from images-to-video-listen.sh
+INPUT="${1:-raw-output-stream.dat}"
+if [ -d "$INPUT" ]; then
+  INPUT="$INPUT/frame-%06d.png"
+fi
+
 rm out.mp4
-ffmpeg -framerate 2 -i raw-output-stream.dat -loop 0 -c:v libx265 out.mp4
+ffmpeg -framerate 2 -i "$INPUT" -loop 0 -c:v libx265 out.mp4
 rm out.webp
-ffmpeg -framerate 2 -i raw-output-stream.dat -loop 0 out.webp
\ No newline at end of file
+ffmpeg -framerate 2 -i "$INPUT" -loop 0 out.webp